@@ -0,0 +1,15 @@
+-- Add up migration script here
+
+CREATE TABLE joke_revisions (
+    id SERIAL PRIMARY KEY NOT NULL,
+    joke_id INTEGER NOT NULL REFERENCES jokes(id) ON DELETE CASCADE,
+    revision_number INTEGER NOT NULL,
+    title VARCHAR NOT NULL,
+    editor_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL,
+    UNIQUE (joke_id, revision_number)
+);
+
+-- Existing jokes start their history at their current state
+INSERT INTO joke_revisions ( joke_id, revision_number, title, editor_id, created_at )
+SELECT id, 1, title, author_id, modified_at FROM jokes;
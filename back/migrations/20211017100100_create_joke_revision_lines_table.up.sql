@@ -0,0 +1,13 @@
+-- Add up migration script here
+
+CREATE TABLE joke_revision_lines (
+    id SERIAL PRIMARY KEY NOT NULL,
+    speaker VARCHAR NOT NULL,
+    content TEXT NOT NULL,
+    index_within_joke INTEGER NOT NULL,
+    revision_id INTEGER NOT NULL REFERENCES joke_revisions(id) ON DELETE CASCADE
+);
+
+INSERT INTO joke_revision_lines ( speaker, content, index_within_joke, revision_id )
+SELECT l.speaker, l.content, l.index_within_joke, r.id
+FROM joke_lines l INNER JOIN joke_revisions r ON r.joke_id = l.joke_id;
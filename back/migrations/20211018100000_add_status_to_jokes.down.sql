@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+ALTER TABLE jokes
+    DROP COLUMN status,
+    DROP COLUMN published_at;
@@ -0,0 +1,10 @@
+-- Add up migration script here
+
+-- Jokes were all public before statuses existed, they stay published
+ALTER TABLE jokes
+    ADD COLUMN status INTEGER NOT NULL DEFAULT 1,
+    ADD COLUMN published_at TIMESTAMP;
+
+UPDATE jokes SET published_at = created_at;
+
+ALTER TABLE jokes ALTER COLUMN status DROP DEFAULT;
@@ -0,0 +1,9 @@
+-- Add up migration script here
+
+CREATE TABLE joke_reactions (
+    joke_id INTEGER NOT NULL REFERENCES jokes(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    reaction INTEGER NOT NULL,
+    reacted_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (joke_id, user_id)
+)
@@ -0,0 +1,15 @@
+-- Add up migration script here
+
+CREATE TABLE joke_comments (
+    id SERIAL PRIMARY KEY NOT NULL,
+    joke_id INTEGER NOT NULL REFERENCES jokes(id) ON DELETE CASCADE,
+    parent_id INTEGER REFERENCES joke_comments(id) ON DELETE CASCADE,
+    author_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    hidden BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL,
+    modified_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX joke_comments_joke_id_idx ON joke_comments (joke_id, created_at);
+CREATE INDEX joke_comments_parent_id_idx ON joke_comments (parent_id)
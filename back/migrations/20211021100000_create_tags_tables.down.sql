@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TABLE joke_tags;
+DROP TABLE tags;
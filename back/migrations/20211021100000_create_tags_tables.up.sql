@@ -0,0 +1,14 @@
+-- Add up migration script here
+
+CREATE TABLE tags (
+    id SERIAL PRIMARY KEY NOT NULL,
+    name VARCHAR NOT NULL UNIQUE
+);
+
+CREATE TABLE joke_tags (
+    joke_id INTEGER NOT NULL REFERENCES jokes(id) ON DELETE CASCADE,
+    tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    PRIMARY KEY (joke_id, tag_id)
+);
+
+CREATE INDEX joke_tags_tag_id_idx ON joke_tags (tag_id);
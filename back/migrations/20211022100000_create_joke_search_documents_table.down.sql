@@ -0,0 +1,8 @@
+-- Add down migration script here
+
+DROP TRIGGER joke_lines_search_document ON joke_lines;
+DROP FUNCTION joke_lines_refresh_search_document;
+DROP TRIGGER jokes_search_document ON jokes;
+DROP FUNCTION jokes_refresh_search_document;
+DROP FUNCTION refresh_joke_search_document;
+DROP TABLE joke_search_documents;
@@ -0,0 +1,51 @@
+-- Add up migration script here
+
+CREATE TABLE joke_search_documents (
+    joke_id INTEGER PRIMARY KEY NOT NULL REFERENCES jokes(id) ON DELETE CASCADE,
+    document TSVECTOR NOT NULL
+);
+
+CREATE INDEX joke_search_documents_document_idx ON joke_search_documents USING GIN (document);
+
+-- Titles weigh more than lines, which weigh more than speakers
+CREATE FUNCTION refresh_joke_search_document(target_joke_id INTEGER) RETURNS VOID AS $$
+    INSERT INTO joke_search_documents ( joke_id, document )
+    SELECT j.id,
+        setweight(to_tsvector('english', j.title), 'A')
+        || setweight(to_tsvector('english', COALESCE(
+            (SELECT string_agg(l.content, ' ') FROM joke_lines l WHERE l.joke_id = j.id), ''
+        )), 'B')
+        || setweight(to_tsvector('english', COALESCE(
+            (SELECT string_agg(l.speaker, ' ') FROM joke_lines l WHERE l.joke_id = j.id), ''
+        )), 'C')
+    FROM jokes j WHERE j.id = target_joke_id
+    ON CONFLICT ( joke_id ) DO UPDATE SET document = EXCLUDED.document
+$$ LANGUAGE SQL;
+
+CREATE FUNCTION jokes_refresh_search_document() RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM refresh_joke_search_document(NEW.id);
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER jokes_search_document
+AFTER INSERT OR UPDATE OF title ON jokes
+FOR EACH ROW EXECUTE FUNCTION jokes_refresh_search_document();
+
+CREATE FUNCTION joke_lines_refresh_search_document() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        PERFORM refresh_joke_search_document(OLD.joke_id);
+    ELSE
+        PERFORM refresh_joke_search_document(NEW.joke_id);
+    END IF;
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER joke_lines_search_document
+AFTER INSERT OR UPDATE OR DELETE ON joke_lines
+FOR EACH ROW EXECUTE FUNCTION joke_lines_refresh_search_document();
+
+SELECT refresh_joke_search_document(id) FROM jokes;
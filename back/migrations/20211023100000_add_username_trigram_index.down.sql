@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+DROP INDEX users_username_trgm_idx;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX users_username_trgm_idx ON users USING GIN (lower(username) gin_trgm_ops);
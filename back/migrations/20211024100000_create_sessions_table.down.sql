@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TABLE retired_refresh_tokens;
+DROP TABLE sessions;
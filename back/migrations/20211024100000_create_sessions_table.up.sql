@@ -0,0 +1,18 @@
+-- Add up migration script here
+
+CREATE TABLE sessions (
+    id SERIAL PRIMARY KEY NOT NULL,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    refresh_token_hash VARCHAR NOT NULL UNIQUE,
+    created_at TIMESTAMP NOT NULL,
+    refreshed_at TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP
+);
+
+-- Refresh tokens already exchanged, kept to detect their reuse
+CREATE TABLE retired_refresh_tokens (
+    token_hash VARCHAR PRIMARY KEY NOT NULL,
+    session_id INTEGER NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    retired_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,4 @@
+-- Add up migration script here
+
+-- Bumped whenever outstanding access tokens of the user must stop working
+ALTER TABLE users ADD COLUMN token_version INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DROP TABLE settings;
+ALTER TABLE users DROP COLUMN email_verification_sent_at;
+ALTER TABLE users DROP COLUMN email_verified_at;
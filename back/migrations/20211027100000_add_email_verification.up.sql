@@ -0,0 +1,13 @@
+-- Add up migration script here
+
+ALTER TABLE users ADD COLUMN email_verified_at TIMESTAMP;
+-- Last time a verification link was mailed, resending is throttled
+ALTER TABLE users ADD COLUMN email_verification_sent_at TIMESTAMP;
+
+-- Site wide settings, the table holds a single row
+CREATE TABLE settings (
+    id BOOLEAN PRIMARY KEY NOT NULL DEFAULT TRUE CHECK (id),
+    authors_need_verified_email BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+INSERT INTO settings DEFAULT VALUES;
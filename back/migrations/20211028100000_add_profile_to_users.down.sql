@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+ALTER TABLE users DROP COLUMN website;
+ALTER TABLE users DROP COLUMN bio;
+ALTER TABLE users DROP COLUMN display_name;
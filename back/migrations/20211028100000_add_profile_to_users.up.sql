@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+ALTER TABLE users ADD COLUMN display_name VARCHAR;
+ALTER TABLE users ADD COLUMN bio TEXT;
+ALTER TABLE users ADD COLUMN website VARCHAR;
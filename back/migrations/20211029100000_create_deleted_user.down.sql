@@ -0,0 +1,14 @@
+-- Add down migration script here
+
+-- Deleting the placeholder would delete along with it the jokes and comments
+-- of the users who deleted their account, they have to be dealt with first
+DO $$
+BEGIN
+    IF EXISTS ( SELECT 1 FROM jokes WHERE author_id = ( SELECT deleted_user_id FROM settings ) )
+    OR EXISTS ( SELECT 1 FROM joke_comments WHERE author_id = ( SELECT deleted_user_id FROM settings ) ) THEN
+        RAISE EXCEPTION 'the [deleted] user still holds jokes or comments';
+    END IF;
+END $$;
+
+ALTER TABLE settings DROP COLUMN deleted_user_id;
+DELETE FROM users WHERE username = '[deleted]' AND password = '!';
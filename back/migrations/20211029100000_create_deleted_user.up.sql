@@ -0,0 +1,14 @@
+-- Add up migration script here
+
+ALTER TABLE settings ADD COLUMN deleted_user_id INTEGER REFERENCES users(id);
+
+-- Takes over the jokes and comments of users deleting their account without
+-- deleting them. Its password hash matches no password.
+WITH deleted_user AS (
+    INSERT INTO users ( username, email, password, role )
+    VALUES ( '[deleted]', '', '!', 2 )
+    RETURNING id
+)
+UPDATE settings SET deleted_user_id = ( SELECT id FROM deleted_user );
+
+ALTER TABLE settings ALTER COLUMN deleted_user_id SET NOT NULL;
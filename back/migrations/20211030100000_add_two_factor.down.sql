@@ -0,0 +1,8 @@
+-- Add down migration script here
+
+ALTER TABLE settings DROP COLUMN admins_need_two_factor;
+ALTER TABLE sessions DROP COLUMN two_factor;
+DROP TABLE recovery_codes;
+ALTER TABLE users DROP COLUMN totp_last_used_step;
+ALTER TABLE users DROP COLUMN totp_enabled_at;
+ALTER TABLE users DROP COLUMN totp_secret;
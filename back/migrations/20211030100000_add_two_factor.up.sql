@@ -0,0 +1,21 @@
+-- Add up migration script here
+
+-- Set when enrolment starts, in use once enabled
+ALTER TABLE users ADD COLUMN totp_secret VARCHAR;
+ALTER TABLE users ADD COLUMN totp_enabled_at TIMESTAMP;
+-- Codes of this time step or earlier cannot be used again
+ALTER TABLE users ADD COLUMN totp_last_used_step BIGINT;
+
+CREATE TABLE recovery_codes (
+    id SERIAL PRIMARY KEY NOT NULL,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    code_hash VARCHAR NOT NULL,
+    used_at TIMESTAMP
+);
+
+CREATE INDEX recovery_codes_user_id_idx ON recovery_codes (user_id);
+
+-- Whether the session was opened with a second factor
+ALTER TABLE sessions ADD COLUMN two_factor BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE settings ADD COLUMN admins_need_two_factor BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+ALTER TABLE settings DROP COLUMN generic_login_denials;
+DROP TABLE login_failures;
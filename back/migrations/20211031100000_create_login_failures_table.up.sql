@@ -0,0 +1,13 @@
+-- Add up migration script here
+
+-- Failed logins per account ("user:<id>"), unknown login ("login:<login>")
+-- or client address ("ip:<address>")
+CREATE TABLE login_failures (
+    subject VARCHAR PRIMARY KEY NOT NULL,
+    failures INTEGER NOT NULL,
+    last_failure_at TIMESTAMP NOT NULL,
+    locked_until TIMESTAMP
+);
+
+-- Gives the same denial reason whether the login or the password is wrong
+ALTER TABLE settings ADD COLUMN generic_login_denials BOOLEAN NOT NULL DEFAULT FALSE;
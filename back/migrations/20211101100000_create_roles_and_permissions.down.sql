@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DROP TABLE role_permissions;
+ALTER TABLE users DROP CONSTRAINT users_role_fkey;
+DROP TABLE roles;
@@ -0,0 +1,37 @@
+-- Add up migration script here
+
+-- Ids are the ones users.role always used, see users::Role
+CREATE TABLE roles (
+    id INTEGER PRIMARY KEY NOT NULL,
+    name VARCHAR NOT NULL UNIQUE
+);
+
+INSERT INTO roles ( id, name ) VALUES
+    ( 0, 'Admin' ),
+    ( 1, 'Author' ),
+    ( 2, 'None' ),
+    ( 3, 'Moderator' );
+
+-- Users of a role nothing knows of could not be loaded, they get no role
+UPDATE users SET role = 2 WHERE role NOT IN ( SELECT id FROM roles );
+
+ALTER TABLE users ADD CONSTRAINT users_role_fkey FOREIGN KEY ( role ) REFERENCES roles ( id );
+
+-- Permissions are named as users::Permission serializes them
+CREATE TABLE role_permissions (
+    role INTEGER NOT NULL REFERENCES roles(id) ON DELETE CASCADE,
+    permission VARCHAR NOT NULL,
+    PRIMARY KEY ( role, permission )
+);
+
+INSERT INTO role_permissions ( role, permission ) VALUES
+    ( 0, 'create_joke' ),
+    ( 0, 'edit_any_joke' ),
+    ( 0, 'view_any_joke' ),
+    ( 0, 'moderate' ),
+    ( 0, 'manage_users' ),
+    ( 0, 'manage_roles' ),
+    ( 0, 'manage_settings' ),
+    ( 1, 'create_joke' ),
+    ( 3, 'create_joke' ),
+    ( 3, 'moderate' );
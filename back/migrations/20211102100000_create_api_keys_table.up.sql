@@ -0,0 +1,20 @@
+-- Add up migration script here
+
+-- Long lived credentials of scripts and bots, see users::api_key
+CREATE TABLE api_keys (
+    id SERIAL PRIMARY KEY NOT NULL,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR NOT NULL,
+    key_hash VARCHAR NOT NULL UNIQUE,
+    -- Start of the key, for its owner to recognise it
+    prefix VARCHAR NOT NULL,
+    scopes VARCHAR[] NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP,
+    last_used_at TIMESTAMP,
+    -- Created in a session opened with a second factor, which keys of admins
+    -- need when settings.admins_need_two_factor is set
+    two_factor BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX api_keys_user_id_idx ON api_keys (user_id);
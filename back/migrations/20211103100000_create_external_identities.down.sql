@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TABLE oidc_login_attempts;
+DROP TABLE external_identities;
@@ -0,0 +1,21 @@
+-- Add up migration script here
+
+-- Accounts of users at OpenID Connect providers, see users::oidc_login
+CREATE TABLE external_identities (
+    provider VARCHAR NOT NULL,
+    subject VARCHAR NOT NULL,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (provider, subject)
+);
+
+-- Logins started with a provider, until it sends the user back
+CREATE TABLE oidc_login_attempts (
+    state_hash VARCHAR PRIMARY KEY NOT NULL,
+    provider VARCHAR NOT NULL,
+    nonce VARCHAR NOT NULL,
+    code_verifier VARCHAR NOT NULL,
+    -- Set when a logged in user links an identity to their account
+    user_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
+    expires_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+ALTER TABLE users DROP CONSTRAINT users_username_key;
@@ -0,0 +1,11 @@
+-- Add up migration script here
+
+-- Usernames were only checked before being taken, so later duplicates get
+-- their id appended to keep them apart
+UPDATE users SET username = username || id
+WHERE EXISTS (
+    SELECT 1 FROM users AS earlier
+    WHERE earlier.username = users.username AND earlier.id < users.id
+);
+
+ALTER TABLE users ADD CONSTRAINT users_username_key UNIQUE (username);
@@ -1,16 +1,32 @@
-use camion::web::{application::{Application, Config}};
+use camion::{
+    core::{jwt_keys, mail, oidc},
+    web::{
+        application::{Application, Config},
+        client_ip,
+        rate_limit::{self, InMemoryStore, Limiter},
+    },
+};
 use dotenv::dotenv;
-use std::env;
+use std::{env, sync::Arc};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
+    // Failing now rather than on the first login
+    jwt_keys::get();
     let db_url = env::var("DATABASE_URL")
         .expect("DATABASE_URL not set");
     let config = Config {
         db_url,
         host: "127.0.0.1".to_owned(),
-        port: 8080
+        port: 8080,
+        mailer: mail::from_env().expect("invalid mail configuration"),
+        rate_limiter: Limiter::new(
+            rate_limit::from_env().expect("invalid rate limits configuration"),
+            Arc::new(InMemoryStore::default()),
+        ),
+        oidc_providers: oidc::from_env().expect("invalid OpenID Connect configuration"),
+        trusted_proxies: client_ip::from_env().expect("invalid trusted proxies configuration"),
     };
     let app = Application::create(&config).await?;
     app.run().await
@@ -0,0 +1,25 @@
+use serde::Serialize;
+
+pub const MAX_LENGTH: usize = 2000;
+
+#[derive(Serialize, Debug)]
+pub enum Issues {
+    Empty,
+    TooLong,
+}
+
+pub fn find_issues(content: &str) -> Option<Vec<Issues>> {
+    let mut issues = vec![];
+
+    if content.trim().is_empty() {
+        issues.push(Issues::Empty);
+    } else if content.chars().count() > MAX_LENGTH {
+        issues.push(Issues::TooLong);
+    }
+
+    if !issues.is_empty() {
+        Some(issues)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,181 @@
+use super::{Comment, Visibility};
+use crate::core::db;
+use chrono::{NaiveDateTime, Utc};
+
+pub struct CommentPostgres {
+    pub id: i32,
+    pub joke_id: i32,
+    pub parent_id: Option<i32>,
+    pub author_id: i32,
+    pub author_username: String,
+    pub content: String,
+    pub hidden: bool,
+    pub created_at: NaiveDateTime,
+    pub modified_at: NaiveDateTime,
+}
+
+impl From<CommentPostgres> for Comment {
+    fn from(comment: CommentPostgres) -> Self {
+        Comment {
+            id: comment.id,
+            joke_id: comment.joke_id,
+            parent_id: comment.parent_id,
+            author_id: comment.author_id,
+            author_username: comment.author_username,
+            content: comment.content,
+            hidden: comment.hidden,
+            created_at: comment.created_at,
+            modified_at: comment.modified_at,
+            replies: vec![],
+        }
+    }
+}
+
+pub async fn insert_comment(
+    joke_id: i32,
+    parent_id: Option<i32>,
+    author_id: i32,
+    content: &str,
+    pool: &db::DbPool,
+) -> Result<Comment, sqlx::Error> {
+    let now = Utc::now().naive_utc();
+
+    let id = sqlx::query!(
+        r#"
+        INSERT INTO joke_comments ( joke_id, parent_id, author_id, content, created_at, modified_at )
+        VALUES ( $1, $2, $3, $4, $5, $6 )
+        RETURNING id
+        "#,
+        joke_id,
+        parent_id,
+        author_id,
+        content,
+        now,
+        now
+    )
+    .fetch_one(pool)
+    .await?
+    .id;
+
+    find_comment_by_id(joke_id, id, pool).await
+}
+
+pub async fn find_comment_by_id(
+    joke_id: i32,
+    id: i32,
+    pool: &db::DbPool,
+) -> Result<Comment, sqlx::Error> {
+    sqlx::query_as!(
+        CommentPostgres,
+        r#"
+        SELECT c.id, c.joke_id, c.parent_id, c.author_id, u.username AS author_username,
+            c.content, c.hidden, c.created_at, c.modified_at
+        FROM joke_comments c INNER JOIN users u ON u.id = c.author_id
+        WHERE c.joke_id = $1 AND c.id = $2
+        "#,
+        joke_id,
+        id
+    )
+    .fetch_one(pool)
+    .await
+    .map(|comment_pg| comment_pg.into())
+}
+
+pub async fn update_comment_content(
+    id: i32,
+    content: &str,
+    pool: &db::DbPool,
+) -> Result<NaiveDateTime, sqlx::Error> {
+    let now = Utc::now().naive_utc();
+
+    sqlx::query!(
+        r#"
+        UPDATE joke_comments SET content = $2, modified_at = $3
+        WHERE id = $1
+        RETURNING modified_at
+        "#,
+        id,
+        content,
+        now
+    )
+    .fetch_one(pool)
+    .await
+    .map(|row| row.modified_at)
+}
+
+pub async fn update_comment_hidden(id: i32, hidden: bool, pool: &db::DbPool) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE joke_comments SET hidden = $2 WHERE id = $1 RETURNING id",
+        id,
+        hidden
+    )
+    .fetch_one(pool)
+    .await
+    .map(|_| ())
+}
+
+pub async fn delete_comment(id: i32, pool: &db::DbPool) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM joke_comments WHERE id = $1 RETURNING id", id)
+        .fetch_one(pool)
+        .await
+        .map(|_| ())
+}
+
+pub async fn list_comments(
+    joke_id: i32,
+    visibility: &Visibility,
+    limit: i64,
+    offset: i64,
+    pool: &db::DbPool,
+) -> Result<Vec<Comment>, sqlx::Error> {
+    let comments_pg = sqlx::query_as!(
+        CommentPostgres,
+        r#"
+        SELECT c.id, c.joke_id, c.parent_id, c.author_id, u.username AS author_username,
+            c.content, c.hidden, c.created_at, c.modified_at
+        FROM joke_comments c INNER JOIN users u ON u.id = c.author_id
+        WHERE c.joke_id = $1 AND c.parent_id IS NULL
+        AND ( NOT c.hidden OR $2 OR c.author_id = $3 )
+        ORDER BY c.created_at ASC, c.id ASC
+        LIMIT $4 OFFSET $5
+        "#,
+        joke_id,
+        visibility.sees_hidden,
+        visibility.viewer_id,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let parent_ids: Vec<i32> = comments_pg.iter().map(|comment_pg| comment_pg.id).collect();
+    let mut replies_pg = sqlx::query_as!(
+        CommentPostgres,
+        r#"
+        SELECT c.id, c.joke_id, c.parent_id, c.author_id, u.username AS author_username,
+            c.content, c.hidden, c.created_at, c.modified_at
+        FROM joke_comments c INNER JOIN users u ON u.id = c.author_id
+        WHERE c.parent_id = ANY($1)
+        AND ( NOT c.hidden OR $2 OR c.author_id = $3 )
+        ORDER BY c.created_at ASC, c.id ASC
+        "#,
+        &parent_ids,
+        visibility.sees_hidden,
+        visibility.viewer_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut comments = Vec::with_capacity(comments_pg.len());
+    for comment_pg in comments_pg {
+        let (own_replies, other_replies): (Vec<CommentPostgres>, _) = replies_pg
+            .into_iter()
+            .partition(|reply_pg| reply_pg.parent_id == Some(comment_pg.id));
+        replies_pg = other_replies;
+        let mut comment: Comment = comment_pg.into();
+        comment.replies = own_replies.into_iter().map(|reply_pg| reply_pg.into()).collect();
+        comments.push(comment);
+    }
+
+    Ok(comments)
+}
@@ -0,0 +1,38 @@
+use crate::core::{db, jokes::Joke, users::token::Claims};
+use serde::Deserialize;
+
+use super::{dl, Comment, Error, Visibility};
+
+pub const DEFAULT_LIMIT: i64 = 20;
+pub const MAX_LIMIT: i64 = 100;
+
+#[derive(Deserialize)]
+pub struct Query {
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+impl Query {
+    // Pages start at 1, out of range values are clamped rather than rejected
+    pub fn page(&self) -> i64 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+
+    // Pagination applies to top level comments, each comes with all its replies
+    pub async fn list(
+        &self,
+        joke: &Joke,
+        claims: &Option<Claims>,
+        pool: &db::DbPool,
+    ) -> Result<Vec<Comment>, Error> {
+        let limit = self.limit();
+        let offset = (self.page() - 1) * limit;
+        dl::list_comments(joke.id, &Visibility::of(joke, claims), limit, offset, pool)
+            .await
+            .map_err(|_| Error::DataLayerFailure)
+    }
+}
@@ -0,0 +1,141 @@
+use super::{
+    db,
+    jokes::{self, Joke},
+    users::{token::Claims, Permission},
+};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+mod content;
+mod dl;
+
+pub mod listing;
+
+#[derive(Deserialize)]
+pub struct CommentTemplate {
+    pub content: String,
+}
+
+#[derive(Serialize, Debug)]
+pub enum Error {
+    NotFound,
+    InvalidContent(Vec<content::Issues>),
+    ParentNotFound,
+    // Replies can only be made to top level comments
+    NestedReply,
+    DataLayerFailure,
+}
+
+impl CommentTemplate {
+    pub async fn insert_on_joke(
+        &self,
+        joke: &Joke,
+        author_id: i32,
+        reply_to: Option<i32>,
+        pool: &db::DbPool,
+    ) -> Result<Comment, Error> {
+        if let Some(issues) = content::find_issues(&self.content) {
+            return Err(Error::InvalidContent(issues));
+        }
+
+        // Hidden comments cannot be answered, even by those who still see them
+        if let Some(parent_id) = reply_to {
+            match find_by_id(joke.id, parent_id, pool).await {
+                Ok(parent) if parent.hidden => return Err(Error::ParentNotFound),
+                Ok(parent) if parent.parent_id.is_some() => return Err(Error::NestedReply),
+                Ok(_) => (),
+                Err(Error::NotFound) => return Err(Error::ParentNotFound),
+                Err(error) => return Err(error),
+            }
+        }
+
+        dl::insert_comment(joke.id, reply_to, author_id, &self.content, pool)
+            .await
+            .map_err(|error| match error {
+                // Parent deleted since it was found
+                sqlx::Error::Database(error) if error.code().as_deref() == Some("23503") => Error::ParentNotFound,
+                _ => Error::DataLayerFailure,
+            })
+    }
+}
+
+#[derive(Serialize)]
+pub struct Comment {
+    pub id: i32,
+    #[serde(skip_serializing)]
+    pub joke_id: i32,
+    pub parent_id: Option<i32>,
+    #[serde(skip_serializing)]
+    pub author_id: i32,
+    pub author_username: String,
+    pub content: String,
+    pub hidden: bool,
+    pub created_at: NaiveDateTime,
+    pub modified_at: NaiveDateTime,
+    pub replies: Vec<Comment>,
+}
+
+// Who is looking at comments, as far as deciding which hidden ones they can see goes
+pub(crate) struct Visibility {
+    pub viewer_id: Option<i32>,
+    pub sees_hidden: bool,
+}
+
+impl Visibility {
+    // Hidden comments stay visible to their author, the joke's author and moderators
+    pub(crate) fn of(joke: &Joke, claims: &Option<Claims>) -> Self {
+        let viewer_id = jokes::Visibility::of(claims).viewer_id;
+        Visibility {
+            viewer_id,
+            sees_hidden: viewer_id == Some(joke.author_id)
+                || matches!(claims, Some(claims) if claims.can(Permission::Moderate)),
+        }
+    }
+}
+
+impl Comment {
+    pub fn is_visible_by(&self, joke: &Joke, claims: &Option<Claims>) -> bool {
+        let visibility = Visibility::of(joke, claims);
+        !self.hidden || visibility.sees_hidden || visibility.viewer_id == Some(self.author_id)
+    }
+
+    pub async fn update(&mut self, template: &CommentTemplate, pool: &db::DbPool) -> Result<(), Error> {
+        if let Some(issues) = content::find_issues(&template.content) {
+            return Err(Error::InvalidContent(issues));
+        }
+
+        self.modified_at = dl::update_comment_content(self.id, &template.content, pool)
+            .await
+            .map_err(|_| Error::DataLayerFailure)?;
+        self.content = template.content.clone();
+        Ok(())
+    }
+
+    pub async fn set_hidden(&mut self, hidden: bool, pool: &db::DbPool) -> Result<(), Error> {
+        dl::update_comment_hidden(self.id, hidden, pool)
+            .await
+            .map_err(|_| Error::DataLayerFailure)?;
+        self.hidden = hidden;
+        Ok(())
+    }
+
+    // Replies are deleted along with the comment they answer
+    pub async fn delete(self, pool: &db::DbPool) -> Result<(), Error> {
+        dl::delete_comment(self.id, pool)
+            .await
+            .map_err(|error| match error {
+                sqlx::Error::RowNotFound => Error::NotFound,
+                _ => Error::DataLayerFailure,
+            })
+    }
+}
+
+// Replies are not loaded, only listings come with them
+pub async fn find_by_id(joke_id: i32, id: i32, pool: &db::DbPool) -> Result<Comment, Error> {
+    dl::find_comment_by_id(joke_id, id, pool)
+        .await
+        .map_err(|error| match error {
+            sqlx::Error::RowNotFound => Error::NotFound,
+            _ => Error::DataLayerFailure,
+        })
+}
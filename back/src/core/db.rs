@@ -2,8 +2,9 @@ use sqlx::postgres::{PgPoolOptions};
 use sqlx::{Postgres, Pool};
 
 pub type DbPool = Pool<Postgres>;
+pub type Transaction<'a> = sqlx::Transaction<'a, Postgres>;
 
-pub async fn build_pool(database_url: &String) -> Pool<Postgres> {
+pub async fn build_pool(database_url: &str) -> Pool<Postgres> {
     PgPoolOptions::new()
         .max_connections(5)
         .connect(database_url).await.unwrap()
@@ -25,5 +26,19 @@ macro_rules! check_field_is_unique {
             }
         }
     };
+    // Rows of the given id do not count, e.g. the user's own row when editing it
+    ($table:literal, $field:literal, $value:ident, except_id: $id:ident, $pool:ident) => {
+        match sqlx::query!(
+            "SELECT " + $field + " FROM " + $table + " WHERE " + $field + " = $1 AND ( $2::INTEGER IS NULL OR id != $2 )",
+            $value,
+            $id
+        )
+        .fetch_optional($pool)
+        .await
+        {
+            Ok(row) => Ok(row.is_none()),
+            Err(db_error) => Err(db_error)
+        }
+    };
 }
 pub(crate) use check_field_is_unique;
@@ -0,0 +1,93 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use super::{tags, JokeLineTemplate, JokeTemplate};
+
+pub const TITLE_MAX_LENGTH: usize = 200;
+pub const SPEAKER_MAX_LENGTH: usize = 100;
+pub const CONTENT_MAX_LENGTH: usize = 2000;
+pub const MAX_LINES: usize = 200;
+
+#[derive(Serialize, Debug)]
+pub enum Issues {
+    Empty,
+    TooLong,
+    TooMany,
+}
+
+// Issues of each line are keyed by its index within the joke
+#[derive(Serialize, Debug, Default)]
+pub struct DataIssues {
+    pub title: Option<Vec<Issues>>,
+    pub lines: Option<Vec<Issues>>,
+    pub speakers: Option<BTreeMap<usize, Vec<Issues>>>,
+    pub contents: Option<BTreeMap<usize, Vec<Issues>>>,
+    pub tags: Option<BTreeMap<String, Vec<tags::Issues>>>,
+}
+
+impl DataIssues {
+    fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.lines.is_none()
+            && self.speakers.is_none()
+            && self.contents.is_none()
+            && self.tags.is_none()
+    }
+}
+
+pub fn find_issues(template: &JokeTemplate) -> Option<DataIssues> {
+    let mut issues = DataIssues {
+        title: text_issues(&template.title, TITLE_MAX_LENGTH, false),
+        ..DataIssues::default()
+    };
+
+    if template.lines.is_empty() {
+        issues.lines = Some(vec![Issues::Empty]);
+    } else if template.lines.len() > MAX_LINES {
+        issues.lines = Some(vec![Issues::TooMany]);
+    }
+    for (index, line) in template.lines.iter().enumerate() {
+        add_line_issues(&mut issues, index, line);
+    }
+
+    if let Some(tags) = template.tags.as_deref() {
+        issues.tags = tags::normalize_all(tags).err();
+    }
+
+    if issues.is_empty() {
+        None
+    } else {
+        Some(issues)
+    }
+}
+
+pub fn find_line_issues(index: usize, line: &JokeLineTemplate) -> Option<DataIssues> {
+    let mut issues = DataIssues::default();
+    add_line_issues(&mut issues, index, line);
+
+    if issues.is_empty() {
+        None
+    } else {
+        Some(issues)
+    }
+}
+
+// Speakers may be left empty, for lines of narration
+fn add_line_issues(issues: &mut DataIssues, index: usize, line: &JokeLineTemplate) {
+    if let Some(speaker_issues) = text_issues(&line.speaker, SPEAKER_MAX_LENGTH, true) {
+        issues.speakers.get_or_insert_with(BTreeMap::new).insert(index, speaker_issues);
+    }
+    if let Some(content_issues) = text_issues(&line.content, CONTENT_MAX_LENGTH, false) {
+        issues.contents.get_or_insert_with(BTreeMap::new).insert(index, content_issues);
+    }
+}
+
+fn text_issues(text: &str, max_length: usize, may_be_empty: bool) -> Option<Vec<Issues>> {
+    if !may_be_empty && text.trim().is_empty() {
+        Some(vec![Issues::Empty])
+    } else if text.chars().count() > max_length {
+        Some(vec![Issues::TooLong])
+    } else {
+        None
+    }
+}
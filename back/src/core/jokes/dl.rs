@@ -1,28 +1,51 @@
-use super::super::users::{self, User};
-use super::{Joke, JokeLine, JokeTemplate};
+use super::super::users::User;
+use super::listing::{Order, Sort};
+use super::revisions::{Revision, RevisionLine, RevisionSummary};
+use super::search::{highlighted, SearchHit, Snippet, MATCH_END, MATCH_START};
+use super::tags::TagCount;
+use super::{
+    Joke, JokeLine, JokeLineTemplate, JokeTemplate, Reaction, ReactionCounts, Status, Visibility,
+};
 use crate::core::db;
 use chrono::{NaiveDateTime, Utc};
+use sqlx::postgres::PgExecutor;
+use std::{collections::HashMap, convert::TryFrom};
 
+// Along with the username of its author, every query reading jokes joins users
+#[derive(sqlx::FromRow)]
 pub struct JokePostgres {
     pub id: i32,
     pub title: String,
     pub author_id: i32,
     pub created_at: NaiveDateTime,
     pub modified_at: NaiveDateTime,
+    pub status: i32,
+    pub published_at: Option<NaiveDateTime>,
+    pub author_username: String,
 }
 
 impl JokePostgres {
-    pub async fn to_joke(self, lines_pg: Vec<JokeLinePostgres>, pool: &db::DbPool) -> Joke {
+    // Fails on statuses this version does not know of
+    pub fn into_joke(
+        self,
+        lines_pg: Vec<JokeLinePostgres>,
+        reactions: ReactionCounts,
+        tags: Vec<String>,
+    ) -> Result<Joke, sqlx::Error> {
         let lines: Vec<JokeLine> = lines_pg.into_iter().map(|j| j.into()).collect();
-        let author = users::find_by_id(self.author_id, pool).await.expect(&format!("Tried converting joke_pg to joke but author with id {} does not exist. Incoherent data.", self.author_id));
-        Joke {
+        Ok(Joke {
             id: self.id,
             title: self.title,
             lines,
-            author_username: author.username,
+            author_id: self.author_id,
+            author_username: self.author_username,
             created_at: self.created_at,
             modified_at: self.modified_at,
-        }
+            status: Status::try_from(self.status).map_err(|error| sqlx::Error::Decode(Box::new(error)))?,
+            published_at: self.published_at,
+            reactions,
+            tags,
+        })
     }
 }
 
@@ -34,13 +57,13 @@ pub struct JokeLinePostgres {
     pub content: String,
 }
 
-impl Into<JokeLine> for JokeLinePostgres {
-    fn into(self) -> JokeLine {
+impl From<JokeLinePostgres> for JokeLine {
+    fn from(line: JokeLinePostgres) -> Self {
         JokeLine {
-            id: self.id,
-            index_within_joke: self.index_within_joke,
-            speaker: self.speaker,
-            content: self.content,
+            id: line.id,
+            index_within_joke: line.index_within_joke,
+            speaker: line.speaker,
+            content: line.content,
         }
     }
 }
@@ -48,59 +71,729 @@ impl Into<JokeLine> for JokeLinePostgres {
 pub async fn insert_joke(
     author: &User,
     template: &JokeTemplate,
+    tags: &[String],
     pool: &db::DbPool,
 ) -> Result<Joke, sqlx::Error> {
     let now = Utc::now().naive_utc();
+    let mut tx = pool.begin().await?;
 
     let joke_pg = sqlx::query_as!(
         JokePostgres,
         r#"
-        INSERT INTO jokes ( title, author_id, created_at, modified_at )
-        VALUES ( $1, $2, $3, $4 )
-        RETURNING *
+        INSERT INTO jokes ( title, author_id, created_at, modified_at, status )
+        VALUES ( $1, $2, $3, $4, $5 )
+        RETURNING *, ( SELECT username FROM users WHERE id = author_id ) AS "author_username!"
         "#,
         template.title,
         author.id,
         now,
-        now
+        now,
+        Status::Draft as i32
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    let lines_pg = insert_lines(joke_pg.id, &template.lines, &mut tx).await?;
+    insert_tags(joke_pg.id, tags, &mut tx).await?;
+    insert_revision(&joke_pg, &lines_pg, author.id, &mut tx).await?;
+
+    // Dropping the transaction on any early return above rolls everything back
+    tx.commit().await?;
+
+    joke_pg.into_joke(lines_pg, ReactionCounts::default(), tags.to_vec())
+}
+
+// Inserts all the lines of a joke in a single statement, indexed by their
+// position in the slice
+async fn insert_lines(
+    joke_id: i32,
+    lines: &[JokeLineTemplate],
+    tx: &mut db::Transaction<'_>,
+) -> Result<Vec<JokeLinePostgres>, sqlx::Error> {
+    let speakers: Vec<String> = lines.iter().map(|line| line.speaker.clone()).collect();
+    let contents: Vec<String> = lines.iter().map(|line| line.content.clone()).collect();
+    let indexes: Vec<i32> = (0..lines.len() as i32).collect();
+
+    let mut lines_pg = sqlx::query_as!(
+        JokeLinePostgres,
+        r#"
+        INSERT INTO joke_lines ( speaker, content, index_within_joke, joke_id )
+        SELECT speaker, content, index_within_joke, $4
+        FROM UNNEST($1::VARCHAR[], $2::TEXT[], $3::INTEGER[])
+            AS line ( speaker, content, index_within_joke )
+        RETURNING *
+        "#,
+        &speakers,
+        &contents,
+        &indexes,
+        joke_id
+    )
+    .fetch_all(tx)
+    .await?;
+
+    lines_pg.sort_by_key(|line_pg| line_pg.index_within_joke);
+    Ok(lines_pg)
+}
+
+pub async fn update_joke(
+    id: i32,
+    template: &JokeTemplate,
+    tags: Option<&[String]>,
+    editor_id: i32,
+    pool: &db::DbPool,
+) -> Result<Joke, sqlx::Error> {
+    let now = Utc::now().naive_utc();
+    let mut tx = pool.begin().await?;
+
+    let joke_pg = sqlx::query_as!(
+        JokePostgres,
+        r#"
+        UPDATE jokes
+        SET title = $1, modified_at = $2
+        WHERE id = $3
+        RETURNING *, ( SELECT username FROM users WHERE id = author_id ) AS "author_username!"
+        "#,
+        template.title,
+        now,
+        id
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    sqlx::query!("DELETE FROM joke_lines WHERE joke_id = $1", id)
+        .execute(&mut tx)
+        .await?;
+
+    let lines_pg = insert_lines(id, &template.lines, &mut tx).await?;
+
+    if let Some(tags) = tags {
+        sqlx::query!("DELETE FROM joke_tags WHERE joke_id = $1", id)
+            .execute(&mut tx)
+            .await?;
+        insert_tags(id, tags, &mut tx).await?;
+    }
+
+    insert_revision(&joke_pg, &lines_pg, editor_id, &mut tx).await?;
+
+    tx.commit().await?;
+
+    let reactions = count_reactions_of_joke(joke_pg.id, pool).await?;
+    let tags = find_tags_of_joke(joke_pg.id, pool).await?;
+
+    joke_pg.into_joke(lines_pg, reactions, tags)
+}
+
+pub async fn update_joke_line(
+    joke_id: i32,
+    index_within_joke: i32,
+    line_template: &JokeLineTemplate,
+    editor_id: i32,
+    pool: &db::DbPool,
+) -> Result<Joke, sqlx::Error> {
+    let now = Utc::now().naive_utc();
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE joke_lines
+        SET speaker = $1, content = $2
+        WHERE joke_id = $3 AND index_within_joke = $4
+        RETURNING id
+        "#,
+        line_template.speaker,
+        line_template.content,
+        joke_id,
+        index_within_joke
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    let joke_pg = sqlx::query_as!(
+        JokePostgres,
+        r#"
+        UPDATE jokes
+        SET modified_at = $1
+        WHERE id = $2
+        RETURNING *, ( SELECT username FROM users WHERE id = author_id ) AS "author_username!"
+        "#,
+        now,
+        joke_id
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    let lines_pg = find_lines_of_jokes(&[joke_id], &mut tx).await?;
+    insert_revision(&joke_pg, &lines_pg, editor_id, &mut tx).await?;
+
+    tx.commit().await?;
+
+    let reactions = count_reactions_of_joke(joke_pg.id, pool).await?;
+    let tags = find_tags_of_joke(joke_pg.id, pool).await?;
+
+    joke_pg.into_joke(lines_pg, reactions, tags)
+}
+
+pub async fn update_joke_status(
+    id: i32,
+    status: Status,
+    pool: &db::DbPool,
+) -> Result<Joke, sqlx::Error> {
+    let now = Utc::now().naive_utc();
+    let published = status == Status::Published;
+
+    // Only the first publication is dated, archiving and publishing again keeps it
+    let joke_pg = sqlx::query_as!(
+        JokePostgres,
+        r#"
+        UPDATE jokes
+        SET status = $1,
+            published_at = CASE WHEN $2 THEN COALESCE(published_at, $3) ELSE published_at END
+        WHERE id = $4
+        RETURNING *, ( SELECT username FROM users WHERE id = author_id ) AS "author_username!"
+        "#,
+        status as i32,
+        published,
+        now,
+        id
     )
     .fetch_one(pool)
     .await?;
 
-    let mut lines_pg = Vec::<JokeLinePostgres>::new();
-    let mut i = 0;
-    for line_template in template.lines.iter() {
-        let maybe_line_pg = sqlx::query_as!(
-            JokeLinePostgres,
-            r#"
-            INSERT INTO joke_lines ( speaker, content, index_within_joke, joke_id )
-            VALUES ( $1, $2, $3, $4 )
-            RETURNING *
-            "#,
-            line_template.speaker,
-            line_template.content,
-            i,
-            joke_pg.id
-        )
+    let lines_pg = find_lines_of_jokes(&[joke_pg.id], pool).await?;
+
+    let reactions = count_reactions_of_joke(joke_pg.id, pool).await?;
+    let tags = find_tags_of_joke(joke_pg.id, pool).await?;
+
+    joke_pg.into_joke(lines_pg, reactions, tags)
+}
+
+pub async fn delete_joke(id: i32, pool: &db::DbPool) -> Result<(), sqlx::Error> {
+    // Lines are removed along with the joke by the ON DELETE CASCADE constraint
+    sqlx::query!("DELETE FROM jokes WHERE id = $1 RETURNING id", id)
         .fetch_one(pool)
-        .await;
-
-        match maybe_line_pg {
-            Ok(line_pg) => lines_pg.push(line_pg),
-            Err(error) => {
-                sqlx::query!("DELETE FROM jokes WHERE id = $1", joke_pg.id)
-                    .execute(pool)
-                    .await
-                    .expect(&format!(
-                        "Tried inserting joke with id {}. One of the line couldn't be inserted. Tried deleting parent joke but it failed.",
-                        joke_pg.id
-                    ));
-                return Err(error);
-            }
-        };
-
-        i += 1;
+        .await
+        .map(|_| ())
+}
+
+pub async fn find_joke_by_id(id: i32, pool: &db::DbPool) -> Result<Joke, sqlx::Error> {
+    let joke_pg = sqlx::query_as!(
+        JokePostgres,
+        r#"
+        SELECT j.id, j.title, j.author_id, j.created_at, j.modified_at, j.status, j.published_at,
+            u.username AS author_username
+        FROM jokes j INNER JOIN users u ON u.id = j.author_id
+        WHERE j.id = $1
+        "#,
+        id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let lines_pg = find_lines_of_jokes(&[joke_pg.id], pool).await?;
+
+    let reactions = count_reactions_of_joke(joke_pg.id, pool).await?;
+    let tags = find_tags_of_joke(joke_pg.id, pool).await?;
+
+    joke_pg.into_joke(lines_pg, reactions, tags)
+}
+
+async fn find_lines_of_jokes<'c>(
+    joke_ids: &[i32],
+    executor: impl PgExecutor<'c>,
+) -> Result<Vec<JokeLinePostgres>, sqlx::Error> {
+    sqlx::query_as!(
+        JokeLinePostgres,
+        r#"
+        SELECT id, index_within_joke, joke_id, speaker, content
+        FROM joke_lines WHERE joke_id = ANY($1)
+        ORDER BY joke_id, index_within_joke
+        "#,
+        joke_ids
+    )
+    .fetch_all(executor)
+    .await
+}
+
+// Upserts the tags themselves before linking them to the joke
+async fn insert_tags(
+    joke_id: i32,
+    tags: &[String],
+    tx: &mut db::Transaction<'_>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO tags ( name )
+        SELECT name FROM UNNEST($1::VARCHAR[]) AS tag ( name )
+        ON CONFLICT ( name ) DO NOTHING
+        "#,
+        tags
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO joke_tags ( joke_id, tag_id )
+        SELECT $1, id FROM tags WHERE name = ANY($2)
+        "#,
+        joke_id,
+        tags
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+async fn find_tags_of_jokes<'c>(
+    joke_ids: &[i32],
+    executor: impl PgExecutor<'c>,
+) -> Result<HashMap<i32, Vec<String>>, sqlx::Error> {
+    let records = sqlx::query!(
+        r#"
+        SELECT jt.joke_id, t.name
+        FROM joke_tags jt INNER JOIN tags t ON t.id = jt.tag_id
+        WHERE jt.joke_id = ANY($1)
+        ORDER BY t.name
+        "#,
+        joke_ids
+    )
+    .fetch_all(executor)
+    .await?;
+
+    let mut tags = HashMap::<i32, Vec<String>>::new();
+    for record in records {
+        tags.entry(record.joke_id).or_default().push(record.name);
+    }
+    Ok(tags)
+}
+
+async fn find_tags_of_joke(joke_id: i32, pool: &db::DbPool) -> Result<Vec<String>, sqlx::Error> {
+    find_tags_of_jokes(&[joke_id], pool)
+        .await
+        .map(|mut tags| tags.remove(&joke_id).unwrap_or_default())
+}
+
+pub async fn count_tags(visibility: &Visibility, pool: &db::DbPool) -> Result<Vec<TagCount>, sqlx::Error> {
+    sqlx::query_as!(
+        TagCount,
+        r#"
+        SELECT t.name, COUNT(*) as "count!"
+        FROM tags t
+        INNER JOIN joke_tags jt ON jt.tag_id = t.id
+        INNER JOIN jokes j ON j.id = jt.joke_id
+        WHERE ( j.status = $1 OR $2 OR j.author_id = $3 )
+        GROUP BY t.name
+        ORDER BY COUNT(*) DESC, t.name ASC
+        "#,
+        Status::Published as i32,
+        visibility.sees_everything,
+        visibility.viewer_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+async fn count_reactions_of_jokes<'c>(
+    joke_ids: &[i32],
+    executor: impl PgExecutor<'c>,
+) -> Result<HashMap<i32, ReactionCounts>, sqlx::Error> {
+    let records = sqlx::query!(
+        r#"
+        SELECT joke_id, reaction, COUNT(*) as "count!"
+        FROM joke_reactions WHERE joke_id = ANY($1)
+        GROUP BY joke_id, reaction
+        "#,
+        joke_ids
+    )
+    .fetch_all(executor)
+    .await?;
+
+    let mut reactions = HashMap::<i32, ReactionCounts>::new();
+    for record in records {
+        reactions
+            .entry(record.joke_id)
+            .or_default()
+            .add(
+                Reaction::try_from(record.reaction).map_err(|error| sqlx::Error::Decode(Box::new(error)))?,
+                record.count,
+            );
+    }
+    Ok(reactions)
+}
+
+pub async fn count_reactions_of_joke(
+    joke_id: i32,
+    pool: &db::DbPool,
+) -> Result<ReactionCounts, sqlx::Error> {
+    count_reactions_of_jokes(&[joke_id], pool)
+        .await
+        .map(|mut reactions| reactions.remove(&joke_id).unwrap_or_default())
+}
+
+pub async fn upsert_reaction(
+    joke_id: i32,
+    user_id: i32,
+    reaction: Reaction,
+    pool: &db::DbPool,
+) -> Result<(), sqlx::Error> {
+    let now = Utc::now().naive_utc();
+    sqlx::query!(
+        r#"
+        INSERT INTO joke_reactions ( joke_id, user_id, reaction, reacted_at )
+        VALUES ( $1, $2, $3, $4 )
+        ON CONFLICT ( joke_id, user_id )
+        DO UPDATE SET reaction = EXCLUDED.reaction, reacted_at = EXCLUDED.reacted_at
+        "#,
+        joke_id,
+        user_id,
+        reaction as i32,
+        now
+    )
+    .execute(pool)
+    .await
+    .map(|_| ())
+}
+
+pub async fn delete_reaction(
+    joke_id: i32,
+    user_id: i32,
+    pool: &db::DbPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM joke_reactions WHERE joke_id = $1 AND user_id = $2",
+        joke_id,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .map(|_| ())
+}
+
+pub struct ListingFilter<'a> {
+    pub author_username: Option<&'a str>,
+    pub status: Option<Status>,
+    pub tag: Option<&'a str>,
+}
+
+pub async fn list_jokes(
+    filter: &ListingFilter<'_>,
+    visibility: &Visibility,
+    sort: Sort,
+    order: Order,
+    limit: i64,
+    offset: i64,
+    pool: &db::DbPool,
+) -> Result<Vec<Joke>, sqlx::Error> {
+    // Only ever one of the whitelisted clauses below is put in the query
+    let order_by = match (sort, order) {
+        (Sort::CreatedAt, Order::Asc) => "j.created_at ASC, j.id ASC",
+        (Sort::CreatedAt, Order::Desc) => "j.created_at DESC, j.id DESC",
+        (Sort::ModifiedAt, Order::Asc) => "j.modified_at ASC, j.id ASC",
+        (Sort::ModifiedAt, Order::Desc) => "j.modified_at DESC, j.id DESC",
+    };
+    let query = format!(
+        r#"
+        SELECT j.id, j.title, j.author_id, j.created_at, j.modified_at, j.status, j.published_at,
+            u.username AS author_username
+        FROM jokes j INNER JOIN users u ON u.id = j.author_id
+        WHERE ( $1::VARCHAR IS NULL OR u.username = $1 )
+        AND ( $2::INTEGER IS NULL OR j.status = $2 )
+        AND ( j.status = $3 OR $4 OR j.author_id = $5 )
+        AND ( $6::VARCHAR IS NULL OR EXISTS (
+            SELECT 1 FROM joke_tags jt INNER JOIN tags t ON t.id = jt.tag_id
+            WHERE jt.joke_id = j.id AND t.name = $6
+        ) )
+        ORDER BY {}
+        LIMIT $7 OFFSET $8
+        "#,
+        order_by
+    );
+    let jokes_pg = sqlx::query_as::<_, JokePostgres>(&query)
+        .bind(filter.author_username)
+        .bind(filter.status.map(|status| status as i32))
+        .bind(Status::Published as i32)
+        .bind(visibility.sees_everything)
+        .bind(visibility.viewer_id)
+        .bind(filter.tag)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+
+    into_jokes(jokes_pg, pool).await
+}
+
+// Whatever their status, oldest first
+pub async fn list_jokes_of_author(author_id: i32, pool: &db::DbPool) -> Result<Vec<Joke>, sqlx::Error> {
+    let jokes_pg = sqlx::query_as!(
+        JokePostgres,
+        r#"
+        SELECT j.id, j.title, j.author_id, j.created_at, j.modified_at, j.status, j.published_at,
+            u.username AS author_username
+        FROM jokes j INNER JOIN users u ON u.id = j.author_id
+        WHERE j.author_id = $1
+        ORDER BY j.created_at, j.id
+        "#,
+        author_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    into_jokes(jokes_pg, pool).await
+}
+
+// Loads the lines, reactions and tags of many jokes at once, keeping their order
+async fn into_jokes(jokes_pg: Vec<JokePostgres>, pool: &db::DbPool) -> Result<Vec<Joke>, sqlx::Error> {
+    let joke_ids: Vec<i32> = jokes_pg.iter().map(|joke_pg| joke_pg.id).collect();
+    let mut lines_pg = find_lines_of_jokes(&joke_ids, pool).await?;
+    let mut reactions = count_reactions_of_jokes(&joke_ids, pool).await?;
+    let mut tags = find_tags_of_jokes(&joke_ids, pool).await?;
+
+    let mut jokes = Vec::with_capacity(jokes_pg.len());
+    for joke_pg in jokes_pg {
+        let (own_lines, other_lines) = lines_pg
+            .into_iter()
+            .partition(|line_pg| line_pg.joke_id == joke_pg.id);
+        lines_pg = other_lines;
+        let own_reactions = reactions.remove(&joke_pg.id).unwrap_or_default();
+        let own_tags = tags.remove(&joke_pg.id).unwrap_or_default();
+        jokes.push(joke_pg.into_joke(own_lines, own_reactions, own_tags)?);
+    }
+
+    Ok(jokes)
+}
+
+pub async fn search_jokes(
+    terms: &str,
+    filter: &ListingFilter<'_>,
+    visibility: &Visibility,
+    limit: i64,
+    offset: i64,
+    pool: &db::DbPool,
+) -> Result<Vec<SearchHit>, sqlx::Error> {
+    let jokes_pg = sqlx::query_as!(
+        JokePostgres,
+        r#"
+        SELECT j.id, j.title, j.author_id, j.created_at, j.modified_at, j.status, j.published_at,
+            u.username AS author_username
+        FROM jokes j
+        INNER JOIN users u ON u.id = j.author_id
+        INNER JOIN joke_search_documents d ON d.joke_id = j.id
+        CROSS JOIN websearch_to_tsquery('english', $1) AS query
+        WHERE d.document @@ query
+        AND ( $2::VARCHAR IS NULL OR u.username = $2 )
+        AND ( $3::INTEGER IS NULL OR j.status = $3 )
+        AND ( $4::VARCHAR IS NULL OR EXISTS (
+            SELECT 1 FROM joke_tags jt INNER JOIN tags t ON t.id = jt.tag_id
+            WHERE jt.joke_id = j.id AND t.name = $4
+        ) )
+        AND ( j.status = $5 OR $6 OR j.author_id = $7 )
+        ORDER BY ts_rank(d.document, query) DESC, j.id DESC
+        LIMIT $8 OFFSET $9
+        "#,
+        terms,
+        filter.author_username,
+        filter.status.map(|status| status as i32),
+        filter.tag,
+        Status::Published as i32,
+        visibility.sees_everything,
+        visibility.viewer_id,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let joke_ids: Vec<i32> = jokes_pg.iter().map(|joke_pg| joke_pg.id).collect();
+
+    // The best matching line of each joke, if the match is not only in its title
+    let mut snippets: HashMap<i32, Snippet> = sqlx::query!(
+        r#"
+        SELECT DISTINCT ON ( l.joke_id ) l.joke_id, l.index_within_joke,
+            ts_headline('english', l.speaker, query, $3) AS "speaker!",
+            ts_headline('english', l.content, query, $3) AS "content!"
+        FROM joke_lines l
+        CROSS JOIN websearch_to_tsquery('english', $1) AS query
+        WHERE l.joke_id = ANY($2)
+        AND to_tsvector('english', l.speaker || ' ' || l.content) @@ query
+        ORDER BY l.joke_id,
+            ts_rank(to_tsvector('english', l.speaker || ' ' || l.content), query) DESC,
+            l.index_within_joke
+        "#,
+        terms,
+        &joke_ids,
+        format!("StartSel={}, StopSel={}, HighlightAll=true", MATCH_START, MATCH_END)
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|record| {
+        (
+            record.joke_id,
+            Snippet {
+                index_within_joke: record.index_within_joke,
+                speaker: highlighted(&record.speaker),
+                content: highlighted(&record.content),
+            },
+        )
+    })
+    .collect();
+
+    Ok(into_jokes(jokes_pg, pool)
+        .await?
+        .into_iter()
+        .map(|joke| SearchHit {
+            snippet: snippets.remove(&joke.id),
+            joke,
+        })
+        .collect())
+}
+
+pub struct JokeRevisionPostgres {
+    pub id: i32,
+    pub revision_number: i32,
+    pub title: String,
+    pub editor_username: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<JokeRevisionPostgres> for RevisionSummary {
+    fn from(revision: JokeRevisionPostgres) -> Self {
+        RevisionSummary {
+            revision_number: revision.revision_number,
+            title: revision.title,
+            editor_username: revision.editor_username,
+            created_at: revision.created_at,
+        }
+    }
+}
+
+pub struct JokeRevisionLinePostgres {
+    pub index_within_joke: i32,
+    pub speaker: String,
+    pub content: String,
+}
+
+impl From<JokeRevisionLinePostgres> for RevisionLine {
+    fn from(line: JokeRevisionLinePostgres) -> Self {
+        RevisionLine {
+            index_within_joke: line.index_within_joke,
+            speaker: line.speaker,
+            content: line.content,
+        }
     }
+}
+
+// Snapshots the current state of a joke as its next revision. The joke must
+// have been inserted or updated within the transaction, its row then being
+// locked until commit so that concurrent edits number theirs after this one.
+async fn insert_revision(
+    joke_pg: &JokePostgres,
+    lines_pg: &[JokeLinePostgres],
+    editor_id: i32,
+    tx: &mut db::Transaction<'_>,
+) -> Result<(), sqlx::Error> {
+    let revision_id = sqlx::query!(
+        r#"
+        INSERT INTO joke_revisions ( joke_id, revision_number, title, editor_id, created_at )
+        SELECT $1, COALESCE(MAX(revision_number), 0) + 1, $2, $3, $4
+        FROM joke_revisions WHERE joke_id = $1
+        RETURNING id
+        "#,
+        joke_pg.id,
+        joke_pg.title,
+        editor_id,
+        joke_pg.modified_at
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .id;
+
+    let speakers: Vec<String> = lines_pg.iter().map(|line| line.speaker.clone()).collect();
+    let contents: Vec<String> = lines_pg.iter().map(|line| line.content.clone()).collect();
+    let indexes: Vec<i32> = lines_pg.iter().map(|line| line.index_within_joke).collect();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO joke_revision_lines ( speaker, content, index_within_joke, revision_id )
+        SELECT speaker, content, index_within_joke, $4
+        FROM UNNEST($1::VARCHAR[], $2::TEXT[], $3::INTEGER[])
+            AS line ( speaker, content, index_within_joke )
+        "#,
+        &speakers,
+        &contents,
+        &indexes,
+        revision_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn list_revisions(
+    joke_id: i32,
+    pool: &db::DbPool,
+) -> Result<Vec<RevisionSummary>, sqlx::Error> {
+    sqlx::query_as!(
+        JokeRevisionPostgres,
+        r#"
+        SELECT r.id, r.revision_number, r.title, u.username as "editor_username?", r.created_at
+        FROM joke_revisions r LEFT JOIN users u ON u.id = r.editor_id
+        WHERE r.joke_id = $1
+        ORDER BY r.revision_number
+        "#,
+        joke_id
+    )
+    .fetch_all(pool)
+    .await
+    .map(|records| {
+        records
+            .into_iter()
+            .map(|revision| revision.into())
+            .collect()
+    })
+}
+
+pub async fn find_revision(
+    joke_id: i32,
+    revision_number: i32,
+    pool: &db::DbPool,
+) -> Result<Revision, sqlx::Error> {
+    let revision_pg = sqlx::query_as!(
+        JokeRevisionPostgres,
+        r#"
+        SELECT r.id, r.revision_number, r.title, u.username as "editor_username?", r.created_at
+        FROM joke_revisions r LEFT JOIN users u ON u.id = r.editor_id
+        WHERE r.joke_id = $1 AND r.revision_number = $2
+        "#,
+        joke_id,
+        revision_number
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let lines = sqlx::query_as!(
+        JokeRevisionLinePostgres,
+        r#"
+        SELECT index_within_joke, speaker, content
+        FROM joke_revision_lines WHERE revision_id = $1
+        ORDER BY index_within_joke
+        "#,
+        revision_pg.id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|line| line.into())
+    .collect();
 
-    Ok(joke_pg.to_joke(lines_pg, pool).await)
+    Ok(Revision {
+        revision_number: revision_pg.revision_number,
+        title: revision_pg.title,
+        lines,
+        editor_username: revision_pg.editor_username,
+        created_at: revision_pg.created_at,
+    })
 }
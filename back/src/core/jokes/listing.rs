@@ -0,0 +1,62 @@
+use crate::core::{db, users::token::Claims};
+use serde::Deserialize;
+
+use super::{dl, tags, Error, Joke, Status, Visibility};
+
+pub const DEFAULT_LIMIT: i64 = 20;
+pub const MAX_LIMIT: i64 = 100;
+
+#[derive(Deserialize)]
+pub struct Query {
+    pub author: Option<String>,
+    pub status: Option<Status>,
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub sort: Sort,
+    #[serde(default)]
+    pub order: Order,
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Sort {
+    #[default]
+    CreatedAt,
+    ModifiedAt,
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Order {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl Query {
+    // Pages start at 1, out of range values are clamped rather than rejected
+    pub fn page(&self) -> i64 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+
+    // Only jokes visible by the claims' owner are listed, see Joke::is_visible_by
+    pub async fn list(&self, claims: &Option<Claims>, pool: &db::DbPool) -> Result<Vec<Joke>, Error> {
+        let limit = self.limit();
+        let offset = (self.page() - 1) * limit;
+        let tag = self.tag.as_deref().map(tags::normalize);
+        let filter = dl::ListingFilter {
+            author_username: self.author.as_deref(),
+            status: self.status,
+            tag: tag.as_deref(),
+        };
+        dl::list_jokes(&filter, &Visibility::of(claims), self.sort, self.order, limit, offset, pool)
+            .await
+            .map_err(|_| Error::DataLayerFailure)
+    }
+}
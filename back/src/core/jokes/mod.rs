@@ -1,26 +1,57 @@
 use super::{
     db,
-    users::User
+    users::{token::Claims, Permission, User}
 };
 use chrono::{NaiveDateTime};
 use serde::{Deserialize, Serialize};
 
+use self::revisions::{Revision, RevisionSummary};
+
 mod dl;
+mod reaction;
+mod status;
+
+pub mod content;
+pub mod listing;
+pub mod revisions;
+pub mod search;
+pub mod tags;
+pub use reaction::*;
+pub use status::*;
 
 #[derive(Deserialize)]
 pub struct JokeTemplate {
     pub title: String,
-    pub lines: Vec<JokeLineTemplate>
+    pub lines: Vec<JokeLineTemplate>,
+    // Left out when updating a joke, its tags are kept as they are
+    #[serde(default)]
+    pub tags: Option<Vec<String>>
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Debug)]
 pub enum Error {
+    NotFound,
+    // Boxed, line issues making it much larger than the other variants
+    Data(Box<content::DataIssues>),
     DataLayerFailure
 }
 
 impl JokeTemplate {
     pub async fn insert_and_set_author(&self, author: &User, pool: &db::DbPool) -> Result<Joke, Error> {
-        dl::insert_joke(author, self, pool).await.map_err(|_| Error::DataLayerFailure)
+        let tags = self.checked_tags()?.unwrap_or_default();
+        dl::insert_joke(author, self, &tags, pool).await.map_err(|_| Error::DataLayerFailure)
+    }
+
+    // Its tags normalized, once the whole template is found valid
+    fn checked_tags(&self) -> Result<Option<Vec<String>>, Error> {
+        if let Some(issues) = content::find_issues(self) {
+            return Err(Error::Data(Box::new(issues)));
+        }
+        self.tags
+            .as_deref()
+            .map(tags::normalize_all)
+            .transpose()
+            .map_err(|tags| Error::Data(Box::new(content::DataIssues { tags: Some(tags), ..Default::default() })))
     }
 }
 
@@ -35,9 +66,146 @@ pub struct Joke {
     pub id: i32,
     pub title: String,
     pub lines: Vec<JokeLine>,
+    #[serde(skip_serializing)]
+    pub author_id: i32,
     pub author_username: String,
     pub created_at: NaiveDateTime,
-    pub modified_at: NaiveDateTime
+    pub modified_at: NaiveDateTime,
+    pub status: Status,
+    pub published_at: Option<NaiveDateTime>,
+    pub reactions: ReactionCounts,
+    pub tags: Vec<String>
+}
+
+// Who is looking at jokes, as far as deciding which ones they can see goes
+pub(crate) struct Visibility {
+    pub viewer_id: Option<i32>,
+    pub sees_everything: bool,
+}
+
+impl Visibility {
+    pub(crate) fn of(claims: &Option<Claims>) -> Self {
+        match claims {
+            Some(claims) => Visibility {
+                viewer_id: Some(claims.id),
+                sees_everything: claims.can(Permission::ViewAnyJoke),
+            },
+            None => Visibility {
+                viewer_id: None,
+                sees_everything: false,
+            },
+        }
+    }
+}
+
+impl Joke {
+    // Drafts and archived jokes are only visible by their author and admins
+    pub fn is_visible_by(&self, claims: &Option<Claims>) -> bool {
+        let visibility = Visibility::of(claims);
+        self.status == Status::Published
+            || visibility.sees_everything
+            || visibility.viewer_id == Some(self.author_id)
+    }
+
+    pub async fn set_status(&mut self, status: Status, pool: &db::DbPool) -> Result<(), Error> {
+        *self = dl::update_joke_status(self.id, status, pool)
+            .await
+            .map_err(|_| Error::DataLayerFailure)?;
+        Ok(())
+    }
+
+    pub async fn update(
+        &mut self,
+        template: &JokeTemplate,
+        editor_id: i32,
+        pool: &db::DbPool,
+    ) -> Result<(), Error> {
+        let tags = template.checked_tags()?;
+        *self = dl::update_joke(self.id, template, tags.as_deref(), editor_id, pool)
+            .await
+            .map_err(|_| Error::DataLayerFailure)?;
+        Ok(())
+    }
+
+    pub async fn update_line(
+        &mut self,
+        index_within_joke: i32,
+        line_template: &JokeLineTemplate,
+        editor_id: i32,
+        pool: &db::DbPool,
+    ) -> Result<(), Error> {
+        if let Some(issues) = content::find_line_issues(index_within_joke.max(0) as usize, line_template) {
+            return Err(Error::Data(Box::new(issues)));
+        }
+        *self = dl::update_joke_line(self.id, index_within_joke, line_template, editor_id, pool)
+            .await
+            .map_err(|error| match error {
+                sqlx::Error::RowNotFound => Error::NotFound,
+                _ => Error::DataLayerFailure,
+            })?;
+        Ok(())
+    }
+
+    pub async fn set_reaction(
+        &mut self,
+        user_id: i32,
+        reaction: Reaction,
+        pool: &db::DbPool,
+    ) -> Result<(), Error> {
+        dl::upsert_reaction(self.id, user_id, reaction, pool)
+            .await
+            .map_err(|_| Error::DataLayerFailure)?;
+        self.reload_reactions(pool).await
+    }
+
+    pub async fn clear_reaction(&mut self, user_id: i32, pool: &db::DbPool) -> Result<(), Error> {
+        dl::delete_reaction(self.id, user_id, pool)
+            .await
+            .map_err(|_| Error::DataLayerFailure)?;
+        self.reload_reactions(pool).await
+    }
+
+    async fn reload_reactions(&mut self, pool: &db::DbPool) -> Result<(), Error> {
+        self.reactions = dl::count_reactions_of_joke(self.id, pool)
+            .await
+            .map_err(|_| Error::DataLayerFailure)?;
+        Ok(())
+    }
+
+    pub async fn delete(self, pool: &db::DbPool) -> Result<(), Error> {
+        dl::delete_joke(self.id, pool)
+            .await
+            .map_err(|error| match error {
+                sqlx::Error::RowNotFound => Error::NotFound,
+                _ => Error::DataLayerFailure,
+            })
+    }
+
+    pub async fn revisions(&self, pool: &db::DbPool) -> Result<Vec<RevisionSummary>, Error> {
+        dl::list_revisions(self.id, pool)
+            .await
+            .map_err(|_| Error::DataLayerFailure)
+    }
+
+    pub async fn revision(&self, revision_number: i32, pool: &db::DbPool) -> Result<Revision, Error> {
+        dl::find_revision(self.id, revision_number, pool)
+            .await
+            .map_err(|error| match error {
+                sqlx::Error::RowNotFound => Error::NotFound,
+                _ => Error::DataLayerFailure,
+            })
+    }
+
+    // Restoring does not rewrite history, the restored state becomes a new revision
+    pub async fn restore_revision(
+        &mut self,
+        revision_number: i32,
+        editor_id: i32,
+        pool: &db::DbPool,
+    ) -> Result<(), Error> {
+        let revision = self.revision(revision_number, pool).await?;
+        self.update(&revision.to_template(), editor_id, pool).await
+    }
 }
 
 #[derive(Serialize)]
@@ -48,3 +216,16 @@ pub struct JokeLine {
     pub content: String,
 }
 
+// Every joke of the author, drafts and archived ones included
+pub async fn list_by_author(author_id: i32, pool: &db::DbPool) -> Result<Vec<Joke>, Error> {
+    dl::list_jokes_of_author(author_id, pool)
+        .await
+        .map_err(|_| Error::DataLayerFailure)
+}
+
+pub async fn find_by_id(id: i32, pool: &db::DbPool) -> Result<Joke, Error> {
+    dl::find_joke_by_id(id, pool).await.map_err(|error| match error {
+        sqlx::Error::RowNotFound => Error::NotFound,
+        _ => Error::DataLayerFailure,
+    })
+}
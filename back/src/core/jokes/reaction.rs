@@ -0,0 +1,52 @@
+use serde::{Serialize, Deserialize};
+use std::{convert::TryFrom, fmt};
+
+// Discriminants are the values stored in joke_reactions
+#[derive(sqlx::Type, Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+#[repr(i32)]
+pub enum Reaction {
+    Laugh = 0,
+    Groan = 1,
+    Meh = 2,
+}
+
+#[derive(Debug)]
+pub struct UnknownReaction(pub i32);
+
+impl TryFrom<i32> for Reaction {
+    type Error = UnknownReaction;
+
+    fn try_from(i: i32) -> Result<Self, Self::Error> {
+        match i {
+            0 => Ok(Reaction::Laugh),
+            1 => Ok(Reaction::Groan),
+            2 => Ok(Reaction::Meh),
+            _ => Err(UnknownReaction(i)),
+        }
+    }
+}
+
+impl fmt::Display for UnknownReaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown reaction {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownReaction {}
+
+#[derive(Serialize, Default, Clone, Copy)]
+pub struct ReactionCounts {
+    pub laugh: i64,
+    pub groan: i64,
+    pub meh: i64,
+}
+
+impl ReactionCounts {
+    pub fn add(&mut self, reaction: Reaction, count: i64) {
+        match reaction {
+            Reaction::Laugh => self.laugh += count,
+            Reaction::Groan => self.groan += count,
+            Reaction::Meh => self.meh += count,
+        }
+    }
+}
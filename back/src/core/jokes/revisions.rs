@@ -0,0 +1,122 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use super::{JokeLineTemplate, JokeTemplate};
+
+#[derive(Serialize)]
+pub struct RevisionSummary {
+    pub revision_number: i32,
+    pub title: String,
+    pub editor_username: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize)]
+pub struct Revision {
+    pub revision_number: i32,
+    pub title: String,
+    pub lines: Vec<RevisionLine>,
+    pub editor_username: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Clone)]
+pub struct RevisionLine {
+    pub index_within_joke: i32,
+    pub speaker: String,
+    pub content: String,
+}
+
+#[derive(Serialize)]
+pub struct Diff {
+    pub from_revision: i32,
+    pub to_revision: i32,
+    pub title: Option<TitleChange>,
+    pub lines: Vec<LineDiff>,
+}
+
+#[derive(Serialize)]
+pub struct TitleChange {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Serialize)]
+pub struct LineDiff {
+    pub index_within_joke: i32,
+    pub change: LineChange,
+    pub from: Option<RevisionLine>,
+    pub to: Option<RevisionLine>,
+}
+
+#[derive(Serialize, PartialEq)]
+pub enum LineChange {
+    Unchanged,
+    Modified,
+    Added,
+    Removed,
+}
+
+impl Revision {
+    pub fn to_template(&self) -> JokeTemplate {
+        JokeTemplate {
+            title: self.title.clone(),
+            lines: self
+                .lines
+                .iter()
+                .map(|line| JokeLineTemplate {
+                    speaker: line.speaker.clone(),
+                    content: line.content.clone(),
+                })
+                .collect(),
+            tags: None,
+        }
+    }
+
+    // Lines are compared position by position, as that is how they are
+    // addressed when editing a joke
+    pub fn diff(&self, to: &Revision) -> Diff {
+        let title = if self.title != to.title {
+            Some(TitleChange {
+                from: self.title.clone(),
+                to: to.title.clone(),
+            })
+        } else {
+            None
+        };
+
+        let lines_count = self.lines.len().max(to.lines.len());
+        let lines = (0..lines_count)
+            .map(|i| {
+                let from_line = self.lines.get(i).cloned();
+                let to_line = to.lines.get(i).cloned();
+                let change = match (&from_line, &to_line) {
+                    (Some(from_line), Some(to_line)) => {
+                        if from_line.speaker == to_line.speaker
+                            && from_line.content == to_line.content
+                        {
+                            LineChange::Unchanged
+                        } else {
+                            LineChange::Modified
+                        }
+                    }
+                    (None, _) => LineChange::Added,
+                    (_, None) => LineChange::Removed,
+                };
+                LineDiff {
+                    index_within_joke: i as i32,
+                    change,
+                    from: from_line,
+                    to: to_line,
+                }
+            })
+            .collect();
+
+        Diff {
+            from_revision: self.revision_number,
+            to_revision: to.revision_number,
+            title,
+            lines,
+        }
+    }
+}
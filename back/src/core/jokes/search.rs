@@ -0,0 +1,85 @@
+use crate::core::{db, users::token::Claims};
+use serde::{Deserialize, Serialize};
+
+use super::{dl, listing, tags, Error, Joke, Visibility};
+
+#[derive(Deserialize)]
+pub struct Query {
+    // Understood the way web search engines do: quoted phrases, `or`, and
+    // `-` to exclude words. Never fails to parse.
+    pub q: String,
+    pub author: Option<String>,
+    pub tag: Option<String>,
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct SearchHit {
+    pub joke: Joke,
+    pub snippet: Option<Snippet>,
+}
+
+// A line of the joke, HTML escaped, with the matching words wrapped in <mark>
+// tags
+#[derive(Serialize)]
+pub struct Snippet {
+    pub index_within_joke: i32,
+    pub speaker: String,
+    pub content: String,
+}
+
+// Private use characters ts_headline wraps matches in, only turned into tags
+// once the text around them is escaped
+pub(crate) const MATCH_START: char = '\u{E000}';
+pub(crate) const MATCH_END: char = '\u{E001}';
+
+pub(crate) fn highlighted(headline: &str) -> String {
+    let mut html = String::with_capacity(headline.len());
+    for c in headline.chars() {
+        match c {
+            MATCH_START => html.push_str("<mark>"),
+            MATCH_END => html.push_str("</mark>"),
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+    html
+}
+
+impl Query {
+    // Pages start at 1, out of range values are clamped rather than rejected
+    pub fn page(&self) -> i64 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn limit(&self) -> i64 {
+        self.limit
+            .unwrap_or(listing::DEFAULT_LIMIT)
+            .clamp(1, listing::MAX_LIMIT)
+    }
+
+    // Best matches come first. Only jokes visible by the claims' owner are
+    // searched, see Joke::is_visible_by
+    pub async fn search(&self, claims: &Option<Claims>, pool: &db::DbPool) -> Result<Vec<SearchHit>, Error> {
+        if self.q.trim().is_empty() {
+            return Ok(vec![]);
+        }
+
+        let limit = self.limit();
+        let offset = (self.page() - 1) * limit;
+        let tag = self.tag.as_deref().map(tags::normalize);
+        let filter = dl::ListingFilter {
+            author_username: self.author.as_deref(),
+            status: None,
+            tag: tag.as_deref(),
+        };
+        dl::search_jokes(&self.q, &filter, &Visibility::of(claims), limit, offset, pool)
+            .await
+            .map_err(|_| Error::DataLayerFailure)
+    }
+}
@@ -0,0 +1,33 @@
+use serde::{Serialize, Deserialize};
+use std::{convert::TryFrom, fmt};
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+pub enum Status {
+    Draft,
+    Published,
+    Archived,
+}
+
+#[derive(Debug)]
+pub struct UnknownStatus(pub i32);
+
+impl TryFrom<i32> for Status {
+    type Error = UnknownStatus;
+
+    fn try_from(i: i32) -> Result<Self, Self::Error> {
+        match i {
+            0 => Ok(Status::Draft),
+            1 => Ok(Status::Published),
+            2 => Ok(Status::Archived),
+            _ => Err(UnknownStatus(i)),
+        }
+    }
+}
+
+impl fmt::Display for UnknownStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown joke status {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownStatus {}
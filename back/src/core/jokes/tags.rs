@@ -0,0 +1,78 @@
+use crate::core::{db, users::token::Claims};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use super::{dl, Error, Visibility};
+
+pub const MIN_LENGTH: usize = 2;
+pub const MAX_LENGTH: usize = 32;
+
+#[derive(Serialize, Debug)]
+pub enum Issues {
+    TooShort,
+    TooLong,
+    // Tags end up in urls, so only letters, digits, spaces, dashes and
+    // underscores are allowed
+    ForbiddenCharacters,
+}
+
+#[derive(Serialize)]
+pub struct TagCount {
+    pub name: String,
+    pub count: i64,
+}
+
+// Tags are free-form but compared case-insensitively and without the
+// surrounding whitespace
+pub fn normalize(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+pub fn find_issues(tag: &str) -> Option<Vec<Issues>> {
+    let mut issues = vec![];
+    let length = tag.chars().count();
+
+    if length < MIN_LENGTH {
+        issues.push(Issues::TooShort);
+    } else if length > MAX_LENGTH {
+        issues.push(Issues::TooLong);
+    }
+
+    if !tag
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == ' ' || c == '-' || c == '_')
+    {
+        issues.push(Issues::ForbiddenCharacters);
+    }
+
+    if !issues.is_empty() {
+        Some(issues)
+    } else {
+        None
+    }
+}
+
+// Normalized and deduplicated tags, or the issues of every faulty one
+pub fn normalize_all(tags: &[String]) -> Result<Vec<String>, BTreeMap<String, Vec<Issues>>> {
+    let mut normalized: Vec<String> = tags.iter().map(|tag| normalize(tag)).collect();
+    normalized.sort();
+    normalized.dedup();
+
+    let issues: BTreeMap<String, Vec<Issues>> = normalized
+        .iter()
+        .filter_map(|tag| find_issues(tag).map(|issues| (tag.clone(), issues)))
+        .collect();
+
+    if issues.is_empty() {
+        Ok(normalized)
+    } else {
+        Err(issues)
+    }
+}
+
+// Only jokes visible by the claims' owner are counted, see Joke::is_visible_by
+pub async fn list_with_counts(claims: &Option<Claims>, pool: &db::DbPool) -> Result<Vec<TagCount>, Error> {
+    dl::count_tags(&Visibility::of(claims), pool)
+        .await
+        .map_err(|_| Error::DataLayerFailure)
+}
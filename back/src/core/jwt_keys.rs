@@ -0,0 +1,260 @@
+use data_encoding::BASE64URL_NOPAD;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+use simple_asn1::ASN1Block;
+use std::{env, fs, sync::OnceLock};
+
+use super::security::JwtError;
+
+const DEFAULT_ISSUER: &str = "camion";
+
+static KEYS: OnceLock<JwtKeys> = OnceLock::new();
+
+// Keys our JWTs are signed and checked with, along with the issuer and
+// audience they carry
+pub struct JwtKeys {
+    signing: SigningKey,
+    // Public parts of the RSA signing key and of retired ones, whose tokens are
+    // still accepted until they expire
+    verification: Vec<RsaPublicKey>,
+    // HS512 secret, checked once an RSA key signs only when asked to, so that
+    // switching to it does not log everyone out
+    secret: Option<Vec<u8>>,
+    // Whether HS512 tokens signed before they carried an issuer and audience
+    // are still accepted, see JWT_ACCEPT_LEGACY_SECRET
+    accept_legacy: bool,
+    issuer: String,
+    audience: String,
+}
+
+enum SigningKey {
+    Rsa { kid: String, key: EncodingKey },
+    Secret,
+}
+
+// As published in the JWKS, components being base64url encoded
+#[derive(Serialize, Clone)]
+pub struct RsaPublicKey {
+    pub kty: &'static str,
+    #[serde(rename = "use")]
+    pub usage: &'static str,
+    pub alg: &'static str,
+    // RFC 7638 thumbprint of the key
+    pub kid: String,
+    pub n: String,
+    pub e: String,
+}
+
+#[derive(Serialize)]
+pub struct Jwks {
+    pub keys: Vec<RsaPublicKey>,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    // Neither JWT_SIGNING_KEY nor JWT_SECRET_KEY is set
+    Missing,
+    // JWT_SECRET_KEY is set along with JWT_SIGNING_KEY, without
+    // JWT_ACCEPT_LEGACY_SECRET telling whether it is still checked
+    LegacySecretUndecided,
+    Unreadable(String),
+    Invalid(String),
+}
+
+// Registered claims added to every token
+#[derive(Serialize)]
+struct Registered<'a, C> {
+    #[serde(flatten)]
+    claims: &'a C,
+    iss: &'a str,
+    aud: &'a str,
+}
+
+// Read from the environment on first use, see sample.env
+pub fn get() -> &'static JwtKeys {
+    KEYS.get_or_init(|| from_env().expect("invalid JWT keys configuration"))
+}
+
+// JWT_SIGNING_KEY is the path to the PEM RSA private key signing tokens with
+// RS256, JWT_VERIFICATION_KEYS the comma separated paths to PEM keys of
+// retired ones. JWT_SECRET_KEY signs tokens with HS512 when there is no
+// signing key. JWT_ACCEPT_LEGACY_SECRET, "true" or "false", tells whether
+// the secret is still checked once there is one, and whether tokens without an
+// issuer and audience it signed are accepted.
+pub fn from_env() -> Result<JwtKeys, ConfigError> {
+    let var = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+
+    let accept_legacy = match var("JWT_ACCEPT_LEGACY_SECRET").as_deref() {
+        None => None,
+        Some("true") => Some(true),
+        Some("false") => Some(false),
+        Some(_) => return Err(ConfigError::Invalid("JWT_ACCEPT_LEGACY_SECRET".to_owned())),
+    };
+    let mut secret = var("JWT_SECRET_KEY").map(String::into_bytes);
+    let mut verification = Vec::new();
+    let signing = match var("JWT_SIGNING_KEY") {
+        Some(path) => {
+            let pem = read_key(&path)?;
+            let key = EncodingKey::from_rsa_pem(&pem).map_err(|_| ConfigError::Invalid(path.clone()))?;
+            let public_key = rsa_public_key(&pem).ok_or(ConfigError::Invalid(path))?;
+            let kid = public_key.kid.clone();
+            verification.push(public_key);
+            SigningKey::Rsa { kid, key }
+        }
+        None if secret.is_some() => SigningKey::Secret,
+        None => return Err(ConfigError::Missing),
+    };
+    if let SigningKey::Rsa { .. } = signing {
+        match (&secret, accept_legacy) {
+            (Some(_), None) => return Err(ConfigError::LegacySecretUndecided),
+            (_, Some(false)) => secret = None,
+            _ => (),
+        }
+    }
+    for path in var("JWT_VERIFICATION_KEYS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+    {
+        let public_key = rsa_public_key(&read_key(path)?).ok_or_else(|| ConfigError::Invalid(path.to_owned()))?;
+        if verification.iter().all(|key| key.kid != public_key.kid) {
+            verification.push(public_key);
+        }
+    }
+
+    let issuer = var("JWT_ISSUER").unwrap_or_else(|| DEFAULT_ISSUER.to_owned());
+    let audience = var("JWT_AUDIENCE").unwrap_or_else(|| issuer.clone());
+    Ok(JwtKeys {
+        signing,
+        verification,
+        secret,
+        accept_legacy: accept_legacy.unwrap_or(false),
+        issuer,
+        audience,
+    })
+}
+
+impl JwtKeys {
+    pub fn encode<C: Serialize>(&self, claims: &C) -> Result<String, JwtError> {
+        let claims = Registered {
+            claims,
+            iss: &self.issuer,
+            aud: &self.audience,
+        };
+        match &self.signing {
+            SigningKey::Rsa { kid, key } => {
+                let mut header = Header::new(Algorithm::RS256);
+                header.kid = Some(kid.clone());
+                jsonwebtoken::encode(&header, &claims, key)
+            }
+            SigningKey::Secret => jsonwebtoken::encode(
+                &Header::new(Algorithm::HS512),
+                &claims,
+                &EncodingKey::from_secret(self.secret.as_deref().unwrap_or_default()),
+            ),
+        }
+        .map_err(|_| JwtError::Creation)
+    }
+
+    // Checks the signature with the key the header names, then the
+    // expiration, issuer and audience
+    pub fn decode<C: DeserializeOwned>(&self, jwt: &str) -> Result<C, JwtError> {
+        let header = jsonwebtoken::decode_header(jwt).map_err(|_| JwtError::Invalid)?;
+        let mut validation = Validation::new(header.alg);
+        validation.iss = Some(self.issuer.clone());
+        validation.set_audience(&[&self.audience]);
+
+        match (header.alg, header.kid) {
+            (Algorithm::RS256, Some(kid)) => {
+                let key = self.verification.iter().find(|key| key.kid == kid).ok_or(JwtError::Invalid)?;
+                jsonwebtoken::decode::<C>(jwt, &DecodingKey::from_rsa_components(&key.n, &key.e), &validation)
+            }
+            (Algorithm::HS512, _) => {
+                let secret = self.secret.as_deref().ok_or(JwtError::Invalid)?;
+                let key = DecodingKey::from_secret(secret);
+                match jsonwebtoken::decode::<C>(jwt, &key, &validation) {
+                    Err(_) if self.accept_legacy && is_legacy(jwt, &key) => {
+                        jsonwebtoken::decode::<C>(jwt, &key, &Validation::new(Algorithm::HS512))
+                    }
+                    decoded => decoded,
+                }
+            }
+            _ => return Err(JwtError::Invalid),
+        }
+        .map(|decoded| decoded.claims)
+        .map_err(|_| JwtError::Invalid)
+    }
+
+    // What others need to check our tokens, secrets never being published
+    pub fn jwks(&self) -> Jwks {
+        Jwks {
+            keys: self.verification.clone(),
+        }
+    }
+}
+
+// Well signed with the secret, without any issuer nor audience
+fn is_legacy(jwt: &str, key: &DecodingKey) -> bool {
+    jsonwebtoken::decode::<serde_json::Value>(jwt, key, &Validation::new(Algorithm::HS512))
+        .map(|decoded| decoded.claims.get("iss").is_none() && decoded.claims.get("aud").is_none())
+        .unwrap_or(false)
+}
+
+fn read_key(path: &str) -> Result<Vec<u8>, ConfigError> {
+    fs::read(path).map_err(|_| ConfigError::Unreadable(path.to_owned()))
+}
+
+// Of RSA keys in PEM, either private or public, PKCS#1 or PKCS#8/SPKI
+fn rsa_public_key(pem: &[u8]) -> Option<RsaPublicKey> {
+    let pem = pem::parse(pem).ok()?;
+    let (n, e) = match pem.tag.as_str() {
+        "RSA PRIVATE KEY" => rsa_private_components(&pem.contents)?,
+        "PRIVATE KEY" => match der_sequence(&pem.contents)?.get(2)? {
+            ASN1Block::OctetString(_, der) => rsa_private_components(der)?,
+            _ => return None,
+        },
+        "RSA PUBLIC KEY" => rsa_public_components(&pem.contents)?,
+        "PUBLIC KEY" => match der_sequence(&pem.contents)?.get(1)? {
+            ASN1Block::BitString(_, _, der) => rsa_public_components(der)?,
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    let (n, e) = (BASE64URL_NOPAD.encode(&n), BASE64URL_NOPAD.encode(&e));
+    // Members in lexicographic order, as RFC 7638 wants
+    let thumbprint_input = format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, e, n);
+    Some(RsaPublicKey {
+        kty: "RSA",
+        usage: "sig",
+        alg: "RS256",
+        kid: BASE64URL_NOPAD.encode(&Sha256::digest(thumbprint_input.as_bytes())),
+        n,
+        e,
+    })
+}
+
+// RSAPrivateKey ::= SEQUENCE { version, modulus, publicExponent, ... }
+fn rsa_private_components(der: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+    match der_sequence(der)?.as_slice() {
+        [_, ASN1Block::Integer(_, n), ASN1Block::Integer(_, e), ..] => Some((n.to_bytes_be().1, e.to_bytes_be().1)),
+        _ => None,
+    }
+}
+
+// RSAPublicKey ::= SEQUENCE { modulus, publicExponent }
+fn rsa_public_components(der: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+    match der_sequence(der)?.as_slice() {
+        [ASN1Block::Integer(_, n), ASN1Block::Integer(_, e)] => Some((n.to_bytes_be().1, e.to_bytes_be().1)),
+        _ => None,
+    }
+}
+
+fn der_sequence(der: &[u8]) -> Option<Vec<ASN1Block>> {
+    match simple_asn1::from_der(der).ok()?.into_iter().next()? {
+        ASN1Block::Sequence(_, blocks) => Some(blocks),
+        _ => None,
+    }
+}
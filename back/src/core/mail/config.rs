@@ -0,0 +1,64 @@
+use lettre::message::Mailbox;
+use std::{env, sync::Arc};
+
+use super::{FileMailer, Mailer, SmtpConfig, SmtpMailer, Tls};
+
+const DEFAULT_FROM: &str = "Camion <camion@localhost>";
+const DEFAULT_DIRECTORY: &str = "mails";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    UnknownBackend(String),
+    Missing(&'static str),
+    Invalid(&'static str),
+}
+
+// MAIL_BACKEND picks "file" or "smtp", see sample.env for the variables each
+// of them reads. Only debug builds default to "file", release ones would
+// otherwise silently drop every mail in a directory.
+pub fn from_env() -> Result<Arc<dyn Mailer>, ConfigError> {
+    let from = env::var("MAIL_FROM")
+        .unwrap_or_else(|_| DEFAULT_FROM.to_owned())
+        .parse::<Mailbox>()
+        .map_err(|_| ConfigError::Invalid("MAIL_FROM"))?;
+
+    let backend = match env::var("MAIL_BACKEND") {
+        Ok(backend) => backend,
+        Err(_) if cfg!(debug_assertions) => "file".to_owned(),
+        Err(_) => return Err(ConfigError::Missing("MAIL_BACKEND")),
+    };
+    match backend.as_str() {
+        "file" => {
+            let directory = env::var("MAIL_DIRECTORY").unwrap_or_else(|_| DEFAULT_DIRECTORY.to_owned());
+            Ok(Arc::new(FileMailer::new(directory, from)))
+        }
+        "smtp" => {
+            let tls = match env::var("SMTP_TLS").as_deref().unwrap_or("starttls") {
+                "none" => Tls::None,
+                "starttls" => Tls::StartTls,
+                "tls" => Tls::Wrapper,
+                _ => return Err(ConfigError::Invalid("SMTP_TLS")),
+            };
+            let port = match env::var("SMTP_PORT") {
+                Ok(port) => port.parse().map_err(|_| ConfigError::Invalid("SMTP_PORT"))?,
+                Err(_) => match tls {
+                    Tls::None => 25,
+                    Tls::StartTls => 587,
+                    Tls::Wrapper => 465,
+                },
+            };
+            let config = SmtpConfig {
+                host: env::var("SMTP_HOST").map_err(|_| ConfigError::Missing("SMTP_HOST"))?,
+                port,
+                username: env::var("SMTP_USERNAME").ok(),
+                password: env::var("SMTP_PASSWORD").ok(),
+                tls,
+                from,
+            };
+            SmtpMailer::new(config)
+                .map(|mailer| Arc::new(mailer) as Arc<dyn Mailer>)
+                .map_err(|_| ConfigError::Invalid("SMTP_HOST"))
+        }
+        backend => Err(ConfigError::UnknownBackend(backend.to_owned())),
+    }
+}
@@ -0,0 +1,36 @@
+use async_trait::async_trait;
+use lettre::{message::Mailbox, AsyncFileTransport, AsyncTransport, Tokio1Executor};
+use std::path::PathBuf;
+
+use super::{Error, Mailer, Message};
+
+// Writes every message as an .eml file in a directory, for development
+pub struct FileMailer {
+    directory: PathBuf,
+    from: Mailbox,
+    transport: AsyncFileTransport<Tokio1Executor>,
+}
+
+impl FileMailer {
+    pub fn new(directory: impl Into<PathBuf>, from: Mailbox) -> Self {
+        let directory = directory.into();
+        FileMailer {
+            transport: AsyncFileTransport::new(&directory),
+            directory,
+            from,
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, message: Message) -> Result<(), Error> {
+        let email = message.to_email(&self.from)?;
+        std::fs::create_dir_all(&self.directory).map_err(|_| Error::Delivery)?;
+        self.transport
+            .send(email)
+            .await
+            .map(|_| ())
+            .map_err(|_| Error::Delivery)
+    }
+}
@@ -0,0 +1,31 @@
+use async_trait::async_trait;
+use std::sync::Mutex;
+
+use super::{Error, Mailer, Message};
+
+// Keeps the messages instead of sending them, so that they can be inspected
+#[derive(Default)]
+pub struct InMemoryMailer {
+    sent: Mutex<Vec<Message>>,
+}
+
+impl InMemoryMailer {
+    pub fn sent(&self) -> Vec<Message> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    pub fn sent_to(&self, to: &str) -> Vec<Message> {
+        self.sent()
+            .into_iter()
+            .filter(|message| message.to == to)
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Mailer for InMemoryMailer {
+    async fn send(&self, message: Message) -> Result<(), Error> {
+        self.sent.lock().unwrap().push(message);
+        Ok(())
+    }
+}
@@ -0,0 +1,48 @@
+use async_trait::async_trait;
+use lettre::message::{Mailbox, MultiPart};
+use serde::Serialize;
+
+mod config;
+mod file;
+mod memory;
+mod smtp;
+pub mod templates;
+
+pub use config::{from_env, ConfigError};
+pub use file::FileMailer;
+pub use memory::InMemoryMailer;
+pub use smtp::{SmtpConfig, SmtpMailer, Tls};
+
+#[derive(Clone, Debug)]
+pub struct Message {
+    pub to: String,
+    pub subject: String,
+    pub text: String,
+    pub html: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub enum Error {
+    InvalidAddress,
+    Delivery,
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, message: Message) -> Result<(), Error>;
+}
+
+impl Message {
+    // HTML bodies come along with their plain text alternative
+    fn to_email(&self, from: &Mailbox) -> Result<lettre::Message, Error> {
+        let builder = lettre::Message::builder()
+            .from(from.clone())
+            .to(self.to.parse().map_err(|_| Error::InvalidAddress)?)
+            .subject(&self.subject);
+        match &self.html {
+            Some(html) => builder.multipart(MultiPart::alternative_plain_html(self.text.clone(), html.clone())),
+            None => builder.body(self.text.clone()),
+        }
+        .map_err(|_| Error::InvalidAddress)
+    }
+}
@@ -0,0 +1,63 @@
+use async_trait::async_trait;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Tokio1Executor,
+};
+
+use super::{Error, Mailer, Message};
+
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub tls: Tls,
+    pub from: Mailbox,
+}
+
+pub enum Tls {
+    // Plain text all along, only fit for local relays
+    None,
+    // Upgrades a plain text connection, usually on port 587
+    StartTls,
+    // TLS from the start, usually on port 465
+    Wrapper,
+}
+
+pub struct SmtpMailer {
+    from: Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpMailer {
+    pub fn new(config: SmtpConfig) -> Result<Self, lettre::transport::smtp::Error> {
+        let builder = match config.tls {
+            Tls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+            Tls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?,
+            Tls::Wrapper => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?,
+        }
+        .port(config.port);
+
+        let builder = match (config.username, config.password) {
+            (Some(username), Some(password)) => builder.credentials(Credentials::new(username, password)),
+            _ => builder,
+        };
+
+        Ok(SmtpMailer {
+            from: config.from,
+            transport: builder.build(),
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, message: Message) -> Result<(), Error> {
+        let email = message.to_email(&self.from)?;
+        self.transport
+            .send(email)
+            .await
+            .map(|_| ())
+            .map_err(|_| Error::Delivery)
+    }
+}
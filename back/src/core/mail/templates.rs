@@ -0,0 +1,46 @@
+use super::Message;
+
+// Placeholders look like {{name}}, values are HTML escaped in HTML bodies
+pub struct Template {
+    subject: &'static str,
+    text: &'static str,
+    html: &'static str,
+}
+
+pub const PASSWORD_RESET: Template = Template {
+    subject: "Resetting your password",
+    text: include_str!("templates/password_reset.txt"),
+    html: include_str!("templates/password_reset.html"),
+};
+
+pub const EMAIL_VERIFICATION: Template = Template {
+    subject: "Verifying your email",
+    text: include_str!("templates/email_verification.txt"),
+    html: include_str!("templates/email_verification.html"),
+};
+
+impl Template {
+    pub fn render(&self, to: &str, values: &[(&str, &str)]) -> Message {
+        Message {
+            to: to.to_owned(),
+            subject: fill(self.subject, values, str::to_owned),
+            text: fill(self.text, values, str::to_owned),
+            html: Some(fill(self.html, values, escape_html)),
+        }
+    }
+}
+
+fn fill(template: &str, values: &[(&str, &str)], escape: fn(&str) -> String) -> String {
+    values.iter().fold(template.to_owned(), |filled, (name, value)| {
+        filled.replace(&format!("{{{{{}}}}}", name), &escape(value))
+    })
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
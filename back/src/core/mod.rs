@@ -1,4 +1,9 @@
 pub mod users;
 pub mod db;
 pub mod security;
-pub mod jokes;
\ No newline at end of file
+pub mod jwt_keys;
+pub mod jokes;
+pub mod comments;
+pub mod mail;
+pub mod settings;
+pub mod oidc;
\ No newline at end of file
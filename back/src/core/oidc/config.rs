@@ -0,0 +1,45 @@
+use std::env;
+
+use super::{Provider, Providers};
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Missing(String),
+    Invalid(String),
+}
+
+// OIDC_PROVIDERS lists the names of the providers, comma separated, see
+// sample.env for the variables each of them reads. None are configured
+// without it.
+pub fn from_env() -> Result<Providers, ConfigError> {
+    let names = env::var("OIDC_PROVIDERS").unwrap_or_default();
+    let providers = names
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(provider_from_env)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Providers::new(providers))
+}
+
+fn provider_from_env(name: &str) -> Result<Provider, ConfigError> {
+    let prefix = format!("OIDC_{}_", name.to_uppercase());
+    let var = |suffix: &str| env::var(format!("{}{}", prefix, suffix)).ok().filter(|value| !value.is_empty());
+    let required = |suffix: &str| var(suffix).ok_or_else(|| ConfigError::Missing(format!("{}{}", prefix, suffix)));
+
+    let default_role = match var("DEFAULT_ROLE") {
+        Some(role) => Some(
+            role.parse()
+                .map_err(|_| ConfigError::Invalid(format!("{}DEFAULT_ROLE", prefix)))?,
+        ),
+        None => None,
+    };
+    Ok(Provider {
+        name: name.to_owned(),
+        issuer: required("ISSUER")?,
+        client_id: required("CLIENT_ID")?,
+        client_secret: var("CLIENT_SECRET"),
+        redirect_uri: required("REDIRECT_URI")?,
+        default_role,
+    })
+}
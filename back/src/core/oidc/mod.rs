@@ -0,0 +1,214 @@
+use reqwest::{Client as HttpClient, Url};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use super::{security, users::Role};
+
+mod config;
+
+pub use config::{from_env, ConfigError};
+
+// An OpenID Connect provider users may log in with
+#[derive(Clone, Debug)]
+pub struct Provider {
+    // In routes and in the identities linked to users
+    pub name: String,
+    // Its discovery document is at <issuer>/.well-known/openid-configuration
+    pub issuer: String,
+    pub client_id: String,
+    // Public clients, relying on PKCE alone, have none
+    pub client_secret: Option<String>,
+    // Where the provider sends users back to with the authorization code
+    pub redirect_uri: String,
+    // Role of users provisioned on their first login, none are without one
+    pub default_role: Option<Role>,
+}
+
+// The configured providers along with the client talking to them
+#[derive(Clone, Default)]
+pub struct Providers {
+    providers: Arc<Vec<Provider>>,
+    http: HttpClient,
+}
+
+#[derive(Serialize, Debug)]
+pub enum Error {
+    // Down, or answering with something else than expected
+    Unavailable,
+    // The token endpoint refused the code, e.g. already used or expired
+    CodeRejected,
+    InvalidIdToken,
+}
+
+// The claims of ID tokens we rely on, sub being the user's stable identifier
+// at the provider
+#[derive(Deserialize)]
+pub struct IdClaims {
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    pub preferred_username: Option<String>,
+    pub name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Deserialize)]
+struct Jwks {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Deserialize)]
+struct Jwk {
+    kty: String,
+    kid: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+}
+
+impl Providers {
+    pub fn new(providers: Vec<Provider>) -> Self {
+        Providers {
+            providers: Arc::new(providers),
+            http: HttpClient::new(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Provider> {
+        self.providers.iter().find(|provider| provider.name == name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.providers.iter().map(|provider| provider.name.as_str()).collect()
+    }
+
+    // Where to send the user to log in, the code challenge being the PKCE one
+    // of a verifier kept until the user comes back
+    pub async fn authorization_url(
+        &self,
+        provider: &Provider,
+        state: &str,
+        nonce: &str,
+        code_challenge: &str,
+    ) -> Result<String, Error> {
+        let discovery = self.discover(provider).await?;
+        let mut url = Url::parse(&discovery.authorization_endpoint).map_err(|_| Error::Unavailable)?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &provider.client_id)
+            .append_pair("redirect_uri", &provider.redirect_uri)
+            .append_pair("scope", "openid email profile")
+            .append_pair("state", state)
+            .append_pair("nonce", nonce)
+            .append_pair("code_challenge", code_challenge)
+            .append_pair("code_challenge_method", "S256");
+        Ok(url.into())
+    }
+
+    // Claims of the ID token the code is exchanged for, once its signature,
+    // issuer, audience and expiration are checked. Checking its nonce is left
+    // to the caller.
+    pub async fn exchange_code(
+        &self,
+        provider: &Provider,
+        code: &str,
+        code_verifier: &str,
+    ) -> Result<IdClaims, Error> {
+        let discovery = self.discover(provider).await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &provider.redirect_uri),
+            ("client_id", &provider.client_id),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(client_secret) = &provider.client_secret {
+            form.push(("client_secret", client_secret));
+        }
+        let response = self
+            .http
+            .post(&discovery.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|_| Error::Unavailable)?;
+        if response.status().is_client_error() {
+            return Err(Error::CodeRejected);
+        }
+        let tokens: TokenResponse = response
+            .error_for_status()
+            .map_err(|_| Error::Unavailable)?
+            .json()
+            .await
+            .map_err(|_| Error::Unavailable)?;
+
+        let jwks: Jwks = self.get_json(&discovery.jwks_uri).await?;
+        let key = signing_key(&jwks, security::jwt_key_id(&tokens.id_token).as_deref())
+            .ok_or(Error::InvalidIdToken)?;
+        security::jwt_decode_rs256(
+            &tokens.id_token,
+            key.n.as_deref().unwrap_or_default(),
+            key.e.as_deref().unwrap_or_default(),
+            &discovery.issuer,
+            &provider.client_id,
+        )
+        .map_err(|_| Error::InvalidIdToken)
+    }
+
+    // Fetched for every login, which keeps up with providers rotating their
+    // keys
+    async fn discover(&self, provider: &Provider) -> Result<Discovery, Error> {
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            provider.issuer.trim_end_matches('/')
+        );
+        let discovery: Discovery = self.get_json(&url).await?;
+        if discovery.issuer.trim_end_matches('/') != provider.issuer.trim_end_matches('/') {
+            return Err(Error::Unavailable);
+        }
+        Ok(discovery)
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T, Error> {
+        self.http
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|_| Error::Unavailable)?
+            .json()
+            .await
+            .map_err(|_| Error::Unavailable)
+    }
+}
+
+// RS256 being the only algorithm providers have to support, only RSA keys are
+// looked at. Tokens without a key id may only come from providers with a
+// single key.
+fn signing_key<'a>(jwks: &'a Jwks, kid: Option<&str>) -> Option<&'a Jwk> {
+    let mut rsa_keys = jwks
+        .keys
+        .iter()
+        .filter(|key| key.kty == "RSA" && key.n.is_some() && key.e.is_some());
+    match kid {
+        Some(kid) => rsa_keys.find(|key| key.kid.as_deref() == Some(kid)),
+        None => match (rsa_keys.next(), rsa_keys.next()) {
+            (Some(key), None) => Some(key),
+            _ => None,
+        },
+    }
+}
@@ -2,8 +2,16 @@ use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Argon2,
 };
+use hmac::{Hmac, Mac, NewMac};
+use rand_core::RngCore;
 use serde::{Serialize, de::DeserializeOwned};
-use std::env;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use super::jwt_keys;
+
+pub const TOTP_STEP_SEC: u64 = 30;
+pub const TOTP_DIGITS: u32 = 6;
 
 pub fn password_salt_and_hash(password: &String) -> Result<String, argon2::password_hash::Error> {
     let salt = SaltString::generate(&mut OsRng);
@@ -17,7 +25,7 @@ pub fn password_salt_and_hash(password: &String) -> Result<String, argon2::passw
         .to_string())
 }
 
-pub fn password_verify(password: &String, hash: &String) -> bool {
+pub fn password_verify(password: &str, hash: &str) -> bool {
     let argon2 = Argon2::default();
     match PasswordHash::new(hash) {
         Ok(parsed_hash) => argon2
@@ -29,47 +37,110 @@ pub fn password_verify(password: &String, hash: &String) -> bool {
 
 pub fn fake_password_verify() {
     password_verify(
-        &"iadAIDAG~~~ZI##123611#{{__".to_string(),
-        &"$argon2id$v=19$m=4096,t=3,p=1$KaoNKL8Ce5qieyRqDMbaXg$O2z+Xx2GUZOMmf2zFtyt7nu9xGm8nvfKSKS7bxTN9wg".to_string()
+        "iadAIDAG~~~ZI##123611#{{__",
+        "$argon2id$v=19$m=4096,t=3,p=1$KaoNKL8Ce5qieyRqDMbaXg$O2z+Xx2GUZOMmf2zFtyt7nu9xGm8nvfKSKS7bxTN9wg"
     );
 }
 
+// Opaque secret handed out to clients, e.g. refresh tokens
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+// Random tokens carry enough entropy for a fast unsalted hash, which keeps
+// them searchable by their hash
+pub fn token_hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+// PKCE S256 challenge (RFC 7636) of a verifier, e.g. a random token
+pub fn pkce_challenge(verifier: &str) -> String {
+    data_encoding::BASE64URL_NOPAD.encode(&Sha256::digest(verifier.as_bytes()))
+}
+
+pub fn random_bytes(length: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; length];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+// RFC 6238 with its usual parameters (HMAC-SHA1, 6 digits, 30 seconds steps),
+// the ones authenticator apps assume
+pub fn totp(secret: &[u8], step: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC takes keys of any size");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    // Dynamic truncation from RFC 4226
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    binary % 10u32.pow(TOTP_DIGITS)
+}
+
+pub fn totp_step(unix_time_sec: u64) -> u64 {
+    unix_time_sec / TOTP_STEP_SEC
+}
+
+// Why a JWT could not be created, or was refused
+#[derive(Debug)]
+pub enum JwtError {
+    Creation,
+    // Malformed, wrongly signed, expired or meant for someone else
+    Invalid,
+}
+
 pub trait JwtClaims {
     fn set_expiration(&mut self, exp: usize);
 }
 
-pub fn jwt_decode<C: JwtClaims + DeserializeOwned>(jwt: &String) -> Result<C, ()> {
-    jsonwebtoken::decode::<C>(
-        jwt,
-        &jsonwebtoken::DecodingKey::from_secret(
-            env::var("JWT_SECRET_KEY")
-                .expect("JWT_SECRET_KEY not set")
-                .as_bytes()
-        ),
-        &jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::HS512)
-    )
-    .map(|decoded| decoded.claims)
-    .map_err(|_| ())
+// Signed by one of our keys, see jwt_keys
+pub fn jwt_decode<C: JwtClaims + DeserializeOwned>(jwt: &str) -> Result<C, JwtError> {
+    jwt_keys::get().decode(jwt)
 }
 
 pub fn jwt_create<C: JwtClaims + Serialize>(
     base_claims: &mut C,
     expiration_sec: i64,
-) -> Result<String, ()> {
+) -> Result<String, JwtError> {
     let expiration = chrono::Utc::now()
         .checked_add_signed(chrono::Duration::seconds(expiration_sec))
         .expect("valid timestamp")
         .timestamp();
     base_claims.set_expiration(expiration as usize);
-    let header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS512);
-    jsonwebtoken::encode(
-        &header,
-        &base_claims,
-        &jsonwebtoken::EncodingKey::from_secret(
-            env::var("JWT_SECRET_KEY")
-                .expect("JWT_SECRET_KEY not set")
-                .as_bytes(),
-        ),
+    jwt_keys::get().encode(base_claims)
+}
+
+// Key id in the header of a JWT, telling which of its signer's keys to check
+// it with
+pub fn jwt_key_id(jwt: &str) -> Option<String> {
+    jsonwebtoken::decode_header(jwt).ok().and_then(|header| header.kid)
+}
+
+// For JWTs signed by others with RS256, whose public key is given by its
+// base64url encoded modulus and exponent as in JWKs. The expiration is
+// checked along with the issuer and audience.
+pub fn jwt_decode_rs256<C: DeserializeOwned>(
+    jwt: &str,
+    modulus: &str,
+    exponent: &str,
+    issuer: &str,
+    audience: &str,
+) -> Result<C, JwtError> {
+    let mut validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::RS256);
+    validation.iss = Some(issuer.to_owned());
+    validation.set_audience(&[audience]);
+    jsonwebtoken::decode::<C>(
+        jwt,
+        &jsonwebtoken::DecodingKey::from_rsa_components(modulus, exponent),
+        &validation,
     )
-    .map_err(|_| ())
+    .map(|decoded| decoded.claims)
+    .map_err(|_| JwtError::Invalid)
 }
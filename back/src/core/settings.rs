@@ -0,0 +1,58 @@
+use crate::core::db;
+use serde::{Deserialize, Serialize};
+
+// Site wide switches, changed by admins at runtime
+#[derive(Serialize)]
+pub struct Settings {
+    // Users must verify their email before being promoted to Author
+    pub authors_need_verified_email: bool,
+    // Admins must have logged in with a second factor to act as admins
+    pub admins_need_two_factor: bool,
+    // Logins fail with InvalidCredentials whatever was wrong, hiding which
+    // usernames exist
+    pub generic_login_denials: bool,
+}
+
+// Missing fields are left as they are
+#[derive(Deserialize)]
+pub struct Update {
+    pub authors_need_verified_email: Option<bool>,
+    pub admins_need_two_factor: Option<bool>,
+    pub generic_login_denials: Option<bool>,
+}
+
+#[derive(Serialize, Debug)]
+pub enum Error {
+    DataLayerFailure,
+}
+
+pub async fn get(pool: &db::DbPool) -> Result<Settings, Error> {
+    sqlx::query_as!(
+        Settings,
+        "SELECT authors_need_verified_email, admins_need_two_factor, generic_login_denials FROM settings"
+    )
+        .fetch_one(pool)
+        .await
+        .map_err(|_| Error::DataLayerFailure)
+}
+
+impl Update {
+    pub async fn apply(&self, pool: &db::DbPool) -> Result<Settings, Error> {
+        sqlx::query_as!(
+            Settings,
+            r#"
+            UPDATE settings
+            SET authors_need_verified_email = COALESCE($1, authors_need_verified_email),
+                admins_need_two_factor = COALESCE($2, admins_need_two_factor),
+                generic_login_denials = COALESCE($3, generic_login_denials)
+            RETURNING authors_need_verified_email, admins_need_two_factor, generic_login_denials
+            "#,
+            self.authors_need_verified_email,
+            self.admins_need_two_factor,
+            self.generic_login_denials
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|_| Error::DataLayerFailure)
+    }
+}
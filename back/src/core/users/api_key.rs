@@ -0,0 +1,221 @@
+use crate::core::{db, security};
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+use super::{dl, permissions_of, token::Claims, Role};
+
+// Tells keys apart from access tokens in Authorization headers
+pub const KEY_PREFIX: &str = "camion_";
+pub const NAME_MAX_LENGTH: usize = 100;
+// How much of the key is kept in clear for its owner to recognise it
+const SHOWN_LENGTH: usize = KEY_PREFIX.len() + 8;
+
+// What a key may be used for. Routes declare the one they need, keys are
+// refused on the others, see web::api::users::scope.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    // Jokes, comments, tags and users as seen by the key's owner
+    Read,
+    // Creating, editing, publishing and deleting jokes
+    WriteJokes,
+    // Reacting to jokes, commenting and moderating comments
+    Interact,
+}
+
+#[derive(Deserialize)]
+pub struct CreateData {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    // Never expires without one
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize)]
+pub struct ApiKey {
+    pub id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+}
+
+// The only time the key is handed out, just its hash is kept
+#[derive(Serialize)]
+pub struct CreatedApiKey {
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+}
+
+#[derive(Serialize)]
+pub enum Error {
+    Data(DataIssues),
+    NotFound,
+    Failure(Failure),
+}
+
+#[derive(Serialize)]
+pub enum Failure {
+    Database,
+}
+
+#[derive(Serialize)]
+pub enum Issues {
+    Blank,
+    TooLong,
+    Empty,
+    Past,
+}
+
+#[derive(Serialize, Default)]
+pub struct DataIssues {
+    pub name: Option<Vec<Issues>>,
+    pub scopes: Option<Vec<Issues>>,
+    pub expires_at: Option<Vec<Issues>>,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 3] = [Scope::Read, Scope::WriteJokes, Scope::Interact];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::WriteJokes => "write_jokes",
+            Scope::Interact => "interact",
+        }
+    }
+
+    // Names this version does not know of are ignored
+    fn from_names(names: Vec<String>) -> Vec<Scope> {
+        names
+            .iter()
+            .filter_map(|name| Scope::ALL.iter().find(|s| s.name() == name).copied())
+            .collect()
+    }
+}
+
+impl From<dl::ApiKeyPostgres> for ApiKey {
+    fn from(api_key: dl::ApiKeyPostgres) -> Self {
+        ApiKey {
+            id: api_key.id,
+            name: api_key.name,
+            prefix: api_key.prefix,
+            scopes: Scope::from_names(api_key.scopes),
+            created_at: api_key.created_at,
+            expires_at: api_key.expires_at,
+            last_used_at: api_key.last_used_at,
+        }
+    }
+}
+
+impl CreateData {
+    pub async fn create(&self, claims: &Claims, pool: &db::DbPool) -> Result<CreatedApiKey, Error> {
+        if let Some(issues) = self.find_issues() {
+            return Err(Error::Data(issues));
+        }
+
+        let key = format!("{}{}", KEY_PREFIX, security::random_token());
+        let mut scopes: Vec<String> = self.scopes.iter().map(|scope| scope.name().to_owned()).collect();
+        scopes.sort();
+        scopes.dedup();
+
+        let api_key = dl::insert_api_key(
+            claims,
+            self.name.trim(),
+            &security::token_hash(&key),
+            &key[..SHOWN_LENGTH],
+            &scopes,
+            self.expires_at,
+            pool,
+        )
+        .await
+        .map_err(|_| Error::Failure(Failure::Database))?;
+
+        Ok(CreatedApiKey {
+            key,
+            api_key: api_key.into(),
+        })
+    }
+
+    fn find_issues(&self) -> Option<DataIssues> {
+        let mut issues = DataIssues::default();
+        let name = self.name.trim();
+        if name.is_empty() {
+            issues.name = Some(vec![Issues::Blank]);
+        } else if name.chars().count() > NAME_MAX_LENGTH {
+            issues.name = Some(vec![Issues::TooLong]);
+        }
+        if self.scopes.is_empty() {
+            issues.scopes = Some(vec![Issues::Empty]);
+        }
+        if matches!(self.expires_at, Some(expires_at) if expires_at <= Utc::now().naive_utc()) {
+            issues.expires_at = Some(vec![Issues::Past]);
+        }
+
+        if issues.name.is_none() && issues.scopes.is_none() && issues.expires_at.is_none() {
+            None
+        } else {
+            Some(issues)
+        }
+    }
+}
+
+// Expired keys included, most recent first
+pub async fn list(user_id: i32, pool: &db::DbPool) -> Result<Vec<ApiKey>, Error> {
+    dl::find_api_keys_of_user(user_id, pool)
+        .await
+        .map(|api_keys| api_keys.into_iter().map(ApiKey::from).collect())
+        .map_err(|_| Error::Failure(Failure::Database))
+}
+
+// Keys of others are reported as not found
+pub async fn revoke(user_id: i32, id: i32, pool: &db::DbPool) -> Result<(), Error> {
+    match dl::delete_api_key(id, user_id, pool).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(Error::NotFound),
+        Err(_) => Err(Error::Failure(Failure::Database)),
+    }
+}
+
+// Id of the key when it may be used, without marking it as used, e.g. to
+// tell keys apart before knowing whether they are allowed in
+pub async fn find_usable_id(key: &str, pool: &db::DbPool) -> Result<Option<i32>, Failure> {
+    dl::find_usable_api_key_id(&security::token_hash(key), pool)
+        .await
+        .map_err(|_| Failure::Database)
+}
+
+pub async fn mark_used(key: &str, pool: &db::DbPool) -> Result<(), Failure> {
+    dl::mark_api_key_used(&security::token_hash(key), pool)
+        .await
+        .map_err(|_| Failure::Database)
+}
+
+// Claims of the key's owner, with the current permissions of their role, or
+// None when the key is unknown, revoked or expired. The key is only marked as
+// used once let in, see mark_used.
+pub async fn authenticate(key: &str, pool: &db::DbPool) -> Result<Option<Claims>, Failure> {
+    let owner = match dl::find_api_key_owner(&security::token_hash(key), pool).await {
+        Ok(Some(owner)) => owner,
+        Ok(None) => return Ok(None),
+        Err(_) => return Err(Failure::Database),
+    };
+
+    let role = Role::try_from(owner.role).map_err(|_| Failure::Database)?;
+    let permissions = permissions_of(role, pool)
+        .await
+        .map_err(|_| Failure::Database)?;
+    Ok(Some(Claims {
+        id: owner.user_id,
+        role,
+        permissions,
+        session_id: 0,
+        version: owner.token_version,
+        two_factor: owner.two_factor,
+        scopes: Some(Scope::from_names(owner.scopes)),
+    }))
+}
@@ -0,0 +1,67 @@
+use crate::core::{db, security};
+use serde::{Deserialize, Serialize};
+
+use super::{dl, find_by_id, session, token::Claims};
+
+#[derive(Deserialize)]
+pub struct Data {
+    // Left out by users without one, see session::is_fresh
+    #[serde(default)]
+    pub password: String,
+    pub jokes: JokesFate,
+}
+
+// What becomes of the user's jokes and comments
+#[derive(Deserialize, Clone, Copy)]
+pub enum JokesFate {
+    // Comments answered by others are handed over like with Reassign, the
+    // jokes going with everything under them
+    Delete,
+    // Handed over to the "[deleted]" placeholder user
+    Reassign,
+}
+
+#[derive(Serialize)]
+pub enum Error {
+    Denied(DeniedReasons),
+    Failure(Failure),
+}
+
+#[derive(Serialize)]
+pub enum DeniedReasons {
+    InvalidPassword,
+    // The session was not opened recently enough
+    ReauthenticationRequired,
+}
+
+#[derive(Serialize)]
+pub enum Failure {
+    Database,
+}
+
+impl Data {
+    // Reactions, sessions and pending tokens are deleted along with the user
+    pub async fn delete_account(&self, claims: &Claims, cache: &session::Cache, pool: &db::DbPool) -> Result<(), Error> {
+        let user = find_by_id(claims.id, pool)
+            .await
+            .map_err(|_| Error::Failure(Failure::Database))?;
+
+        if user.password.is_empty() {
+            // Only their provider lets them in, they just went through it
+            if !session::is_fresh(claims, pool)
+                .await
+                .map_err(|_| Error::Failure(Failure::Database))?
+            {
+                return Err(Error::Denied(DeniedReasons::ReauthenticationRequired));
+            }
+        } else if !security::password_verify(&self.password, &user.password) {
+            return Err(Error::Denied(DeniedReasons::InvalidPassword));
+        }
+
+        dl::delete_user(user.id, matches!(self.jokes, JokesFate::Reassign), pool)
+            .await
+            .map_err(|_| Error::Failure(Failure::Database))?;
+        cache.forget_user(user.id);
+        Ok(())
+    }
+}
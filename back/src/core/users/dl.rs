@@ -1,5 +1,8 @@
-use super::{User, Role};
-use crate::core::db;
+use super::{token::Claims, User, Role};
+use crate::core::{db, jokes::Reaction};
+use chrono::{NaiveDateTime, Utc};
+use serde::Serialize;
+use std::convert::TryFrom;
 
 pub struct UserPostgres {
     pub id: i32,
@@ -7,17 +10,36 @@ pub struct UserPostgres {
     pub role: i32,
     pub email: String,
     pub password: String,
+    pub token_version: i32,
+    pub email_verified_at: Option<NaiveDateTime>,
+    pub email_verification_sent_at: Option<NaiveDateTime>,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub website: Option<String>,
+    pub totp_secret: Option<String>,
+    pub totp_enabled_at: Option<NaiveDateTime>,
 }
 
-impl Into<User> for UserPostgres {
-    fn into(self) -> User {
-        User {
-            id: self.id,
-            username: self.username,
-            password: self.password,
-            email: self.email,
-            role: Role::from(self.role)
-        }
+// Fails on roles this version does not know of
+impl TryFrom<UserPostgres> for User {
+    type Error = sqlx::Error;
+
+    fn try_from(user: UserPostgres) -> Result<Self, Self::Error> {
+        Ok(User {
+            id: user.id,
+            username: user.username,
+            password: user.password,
+            email: user.email,
+            role: Role::try_from(user.role).map_err(|error| sqlx::Error::Decode(Box::new(error)))?,
+            token_version: user.token_version,
+            email_verified_at: user.email_verified_at,
+            email_verification_sent_at: user.email_verification_sent_at,
+            display_name: user.display_name,
+            bio: user.bio,
+            website: user.website,
+            totp_secret: user.totp_secret,
+            totp_enabled_at: user.totp_enabled_at,
+        })
     }
 }
 
@@ -26,19 +48,22 @@ macro_rules! find_by_x {
         sqlx::query_as!(
             dl::UserPostgres,
             r#"
-            SELECT id, username, email, password, role
+            SELECT id, username, email, password, role, token_version,
+                email_verified_at, email_verification_sent_at, display_name, bio, website,
+                totp_secret, totp_enabled_at
             FROM users WHERE "# + $field + r#" = $1 
             "#,
             $value
         )
         .fetch_one($pool)
         .await
-        .map(|user| user.into())
+        .and_then(std::convert::TryInto::try_into)
     };
 }
 pub(crate) use find_by_x;
 
-pub async fn update_role_returning_id(
+// Outdates the user's access tokens along the way, see bump_token_version
+pub async fn update_role_returning_token_version(
     id: i32,
     new_role: Role,
     pool: &db::DbPool,
@@ -46,9 +71,9 @@ pub async fn update_role_returning_id(
     let record = sqlx::query!(
         r#"
     UPDATE users
-    SET role = $1
+    SET role = $1, token_version = token_version + 1
     WHERE id = $2
-    RETURNING *
+    RETURNING token_version
     "#,
         new_role as i32,
         id
@@ -56,29 +81,70 @@ pub async fn update_role_returning_id(
     .fetch_one(pool)
     .await?;
 
-    Ok(record.id)
+    Ok(record.token_version)
+}
+
+pub async fn bump_token_version(id: i32, pool: &db::DbPool) -> Result<i32, sqlx::Error> {
+    sqlx::query!(
+        "UPDATE users SET token_version = token_version + 1 WHERE id = $1 RETURNING token_version",
+        id
+    )
+    .fetch_one(pool)
+    .await
+    .map(|record| record.token_version)
+}
+
+// Wildcards typed by users are matched literally
+fn escape_like_pattern(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
 }
 
 pub async fn search_by_username(
-    query: &String,
+    query: &str,
+    searcher_id: Option<i32>,
+    sees_everyone: bool,
+    limit: i64,
+    offset: i64,
     pool: &db::DbPool,
 ) -> Result<Vec<User>, sqlx::Error> {
+    let query = query.to_lowercase();
+    let pattern = escape_like_pattern(&query);
+
     sqlx::query_as!(
         UserPostgres,
         r#"
-        SELECT id, username, email, password, role
-        FROM users WHERE username ~* $1
+        SELECT id, username, email, password, role, token_version,
+            email_verified_at, email_verification_sent_at, display_name, bio, website,
+            totp_secret, totp_enabled_at
+        FROM users
+        WHERE ( lower(username) LIKE '%' || $1 || '%' OR lower(username) % $2 )
+        AND ( role != $3 OR $4 OR id = $5 )
+        ORDER BY
+            CASE
+                WHEN lower(username) = $2 THEN 0
+                WHEN lower(username) LIKE $1 || '%' THEN 1
+                WHEN lower(username) LIKE '%' || $1 || '%' THEN 2
+                ELSE 3
+            END,
+            similarity(lower(username), $2) DESC,
+            username
+        LIMIT $6 OFFSET $7
         "#,
-        query
+        pattern,
+        query,
+        Role::None as i32,
+        sees_everyone,
+        searcher_id,
+        limit,
+        offset
     )
     .fetch_all(pool)
-    .await
-    .map(|records| {
-        records
-            .into_iter()
-            .map(|user| user.into())
-            .collect()
-    })
+    .await?
+    .into_iter()
+    .map(User::try_from)
+    .collect()
 }
 
 pub async fn insert_returning_id(
@@ -104,3 +170,926 @@ pub async fn insert_returning_id(
 
     Ok(record.id)
 }
+
+pub struct SessionPostgres {
+    pub id: i32,
+    pub user_id: i32,
+    pub expires_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub two_factor: bool,
+}
+
+pub async fn insert_session_returning_id(
+    user_id: i32,
+    refresh_token_hash: &str,
+    expires_at: NaiveDateTime,
+    two_factor: bool,
+    pool: &db::DbPool,
+) -> Result<i32, sqlx::Error> {
+    let now = Utc::now().naive_utc();
+
+    let record = sqlx::query!(
+        r#"
+        INSERT INTO sessions ( user_id, refresh_token_hash, created_at, refreshed_at, expires_at, two_factor )
+        VALUES ( $1, $2, $3, $4, $5, $6 )
+        RETURNING id
+        "#,
+        user_id,
+        refresh_token_hash,
+        now,
+        now,
+        expires_at,
+        two_factor
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(record.id)
+}
+
+pub async fn find_session_by_refresh_token_hash(
+    refresh_token_hash: &str,
+    pool: &db::DbPool,
+) -> Result<SessionPostgres, sqlx::Error> {
+    sqlx::query_as!(
+        SessionPostgres,
+        r#"
+        SELECT id, user_id, expires_at, revoked_at, two_factor
+        FROM sessions WHERE refresh_token_hash = $1
+        "#,
+        refresh_token_hash
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn find_session_id_by_retired_refresh_token_hash(
+    refresh_token_hash: &str,
+    pool: &db::DbPool,
+) -> Result<i32, sqlx::Error> {
+    sqlx::query!(
+        "SELECT session_id FROM retired_refresh_tokens WHERE token_hash = $1",
+        refresh_token_hash
+    )
+    .fetch_one(pool)
+    .await
+    .map(|record| record.session_id)
+}
+
+// Fails with RowNotFound if the old token was rotated concurrently
+pub async fn rotate_refresh_token(
+    session_id: i32,
+    old_refresh_token_hash: &str,
+    new_refresh_token_hash: &str,
+    expires_at: NaiveDateTime,
+    pool: &db::DbPool,
+) -> Result<(), sqlx::Error> {
+    let now = Utc::now().naive_utc();
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE sessions
+        SET refresh_token_hash = $1, refreshed_at = $2, expires_at = $3
+        WHERE id = $4 AND refresh_token_hash = $5 AND revoked_at IS NULL
+        RETURNING id
+        "#,
+        new_refresh_token_hash,
+        now,
+        expires_at,
+        session_id,
+        old_refresh_token_hash
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO retired_refresh_tokens ( token_hash, session_id, retired_at )
+        VALUES ( $1, $2, $3 )
+        "#,
+        old_refresh_token_hash,
+        session_id,
+        now
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await
+}
+
+pub async fn revoke_session(id: i32, pool: &db::DbPool) -> Result<(), sqlx::Error> {
+    let now = Utc::now().naive_utc();
+
+    sqlx::query!(
+        r#"
+        UPDATE sessions SET revoked_at = COALESCE(revoked_at, $1)
+        WHERE id = $2
+        RETURNING id
+        "#,
+        now,
+        id
+    )
+    .fetch_one(pool)
+    .await
+    .map(|_| ())
+}
+
+// The current token version of the session's user, if the session is active
+pub async fn find_active_session_token_version(
+    id: i32,
+    user_id: i32,
+    pool: &db::DbPool,
+) -> Result<Option<i32>, sqlx::Error> {
+    let now = Utc::now().naive_utc();
+
+    sqlx::query!(
+        r#"
+        SELECT u.token_version
+        FROM sessions s INNER JOIN users u ON u.id = s.user_id
+        WHERE s.id = $1 AND s.user_id = $2 AND s.revoked_at IS NULL AND s.expires_at > $3
+        "#,
+        id,
+        user_id,
+        now
+    )
+    .fetch_optional(pool)
+    .await
+    .map(|record| record.map(|record| record.token_version))
+}
+
+// When the session was opened, refreshing it changing nothing, if it is active
+pub async fn find_active_session_created_at(
+    id: i32,
+    user_id: i32,
+    pool: &db::DbPool,
+) -> Result<Option<NaiveDateTime>, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT created_at FROM sessions
+        WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL AND expires_at > $3
+        "#,
+        id,
+        user_id,
+        Utc::now().naive_utc()
+    )
+    .fetch_optional(pool)
+    .await
+    .map(|record| record.map(|record| record.created_at))
+}
+
+// Outdates the user's access tokens and revokes their other sessions, the
+// password may have leaked
+pub async fn update_password_keeping_session(
+    id: i32,
+    password_hash: &str,
+    kept_session_id: i32,
+    pool: &db::DbPool,
+) -> Result<(), sqlx::Error> {
+    let now = Utc::now().naive_utc();
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE users SET password = $1, token_version = token_version + 1
+        WHERE id = $2
+        RETURNING id
+        "#,
+        password_hash,
+        id
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE sessions SET revoked_at = $1
+        WHERE user_id = $2 AND id <> $3 AND revoked_at IS NULL
+        "#,
+        now,
+        id,
+        kept_session_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await
+}
+
+pub async fn insert_password_reset_token(
+    user_id: i32,
+    token_hash: &str,
+    expires_at: NaiveDateTime,
+    pool: &db::DbPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO password_reset_tokens ( token_hash, user_id, created_at, expires_at )
+        VALUES ( $1, $2, $3, $4 )
+        "#,
+        token_hash,
+        user_id,
+        Utc::now().naive_utc(),
+        expires_at
+    )
+    .execute(pool)
+    .await
+    .map(|_| ())
+}
+
+// Fails with RowNotFound if the token is unknown, used or expired. All of the
+// user's reset tokens and sessions are done with afterwards.
+pub async fn reset_password_returning_user_id(
+    token_hash: &str,
+    password_hash: &str,
+    pool: &db::DbPool,
+) -> Result<i32, sqlx::Error> {
+    let now = Utc::now().naive_utc();
+    let mut tx = pool.begin().await?;
+
+    let user_id = sqlx::query!(
+        r#"
+        UPDATE password_reset_tokens SET used_at = $1
+        WHERE token_hash = $2 AND used_at IS NULL AND expires_at > $1
+        RETURNING user_id
+        "#,
+        now,
+        token_hash
+    )
+    .fetch_one(&mut tx)
+    .await?
+    .user_id;
+
+    sqlx::query!(
+        "UPDATE password_reset_tokens SET used_at = $1 WHERE user_id = $2 AND used_at IS NULL",
+        now,
+        user_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE users SET password = $1, token_version = token_version + 1 WHERE id = $2",
+        password_hash,
+        user_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE sessions SET revoked_at = $1 WHERE user_id = $2 AND revoked_at IS NULL",
+        now,
+        user_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(user_id)
+}
+
+// Fails with RowNotFound if a link was sent after not_sent_since
+pub async fn mark_email_verification_sent(
+    id: i32,
+    not_sent_since: NaiveDateTime,
+    pool: &db::DbPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE users SET email_verification_sent_at = $1
+        WHERE id = $2
+        AND ( email_verification_sent_at IS NULL OR email_verification_sent_at <= $3 )
+        RETURNING id
+        "#,
+        Utc::now().naive_utc(),
+        id,
+        not_sent_since
+    )
+    .fetch_one(pool)
+    .await
+    .map(|_| ())
+}
+
+// Fails with RowNotFound if the user's email is no longer the verified one
+pub async fn mark_email_verified(id: i32, email: &str, pool: &db::DbPool) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE users SET email_verified_at = COALESCE(email_verified_at, $1)
+        WHERE id = $2 AND email = $3
+        RETURNING id
+        "#,
+        Utc::now().naive_utc(),
+        id,
+        email
+    )
+    .fetch_one(pool)
+    .await
+    .map(|_| ())
+}
+
+// A new email is unverified, and may be sent a verification link at once
+pub async fn update_profile(user: &User, pool: &db::DbPool) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET username = $1, email = $2::VARCHAR, display_name = $3, bio = $4, website = $5,
+            email_verified_at = CASE WHEN email = $2 THEN email_verified_at ELSE NULL END,
+            email_verification_sent_at = CASE WHEN email = $2 THEN email_verification_sent_at ELSE NULL END
+        WHERE id = $6
+        RETURNING id
+        "#,
+        user.username,
+        user.email,
+        user.display_name,
+        user.bio,
+        user.website,
+        user.id
+    )
+    .fetch_one(pool)
+    .await
+    .map(|_| ())
+}
+
+// Reassigned jokes and comments go to the placeholder user of the settings,
+// everything else of the user goes away with them
+pub async fn delete_user(id: i32, reassign: bool, pool: &db::DbPool) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    if reassign {
+        sqlx::query!(
+            "UPDATE jokes SET author_id = ( SELECT deleted_user_id FROM settings ) WHERE author_id = $1",
+            id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE joke_comments SET author_id = ( SELECT deleted_user_id FROM settings ) WHERE author_id = $1",
+            id
+        )
+        .execute(&mut tx)
+        .await?;
+    } else {
+        // Replies would go along with the comments they answer
+        sqlx::query!(
+            r#"
+            UPDATE joke_comments SET author_id = ( SELECT deleted_user_id FROM settings )
+            WHERE author_id = $1
+                AND EXISTS ( SELECT 1 FROM joke_comments AS reply WHERE reply.parent_id = joke_comments.id )
+            "#,
+            id
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+
+    sqlx::query!("DELETE FROM users WHERE id = $1 RETURNING id", id)
+        .fetch_one(&mut tx)
+        .await?;
+
+    tx.commit().await
+}
+
+#[derive(Serialize)]
+pub struct CommentRecord {
+    pub id: i32,
+    pub joke_id: i32,
+    pub parent_id: Option<i32>,
+    pub content: String,
+    pub hidden: bool,
+    pub created_at: NaiveDateTime,
+    pub modified_at: NaiveDateTime,
+}
+
+pub async fn find_comments_of_user(id: i32, pool: &db::DbPool) -> Result<Vec<CommentRecord>, sqlx::Error> {
+    sqlx::query_as!(
+        CommentRecord,
+        r#"
+        SELECT id, joke_id, parent_id, content, hidden, created_at, modified_at
+        FROM joke_comments WHERE author_id = $1
+        ORDER BY created_at, id
+        "#,
+        id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[derive(Serialize)]
+pub struct ReactionRecord {
+    pub joke_id: i32,
+    pub reaction: Reaction,
+    pub reacted_at: NaiveDateTime,
+}
+
+pub async fn find_reactions_of_user(id: i32, pool: &db::DbPool) -> Result<Vec<ReactionRecord>, sqlx::Error> {
+    sqlx::query_as!(
+        ReactionRecord,
+        r#"
+        SELECT joke_id, reaction AS "reaction: Reaction", reacted_at
+        FROM joke_reactions WHERE user_id = $1
+        ORDER BY reacted_at, joke_id
+        "#,
+        id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Starts over any enrolment in progress. Fails with RowNotFound if two factor
+// authentication is already enabled.
+pub async fn set_totp_secret(id: i32, secret: &str, pool: &db::DbPool) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE users SET totp_secret = $1, totp_last_used_step = NULL
+        WHERE id = $2 AND totp_enabled_at IS NULL
+        RETURNING id
+        "#,
+        secret,
+        id
+    )
+    .fetch_one(pool)
+    .await
+    .map(|_| ())
+}
+
+// Replaces the recovery codes, and counts the enabling session as opened with
+// two factors. Fails with RowNotFound if no enrolment is in progress.
+pub async fn enable_totp(
+    id: i32,
+    used_step: i64,
+    recovery_code_hashes: &[String],
+    session_id: i32,
+    pool: &db::DbPool,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE users SET totp_enabled_at = $1, totp_last_used_step = $2
+        WHERE id = $3 AND totp_secret IS NOT NULL AND totp_enabled_at IS NULL
+        RETURNING id
+        "#,
+        Utc::now().naive_utc(),
+        used_step,
+        id
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    sqlx::query!("DELETE FROM recovery_codes WHERE user_id = $1", id)
+        .execute(&mut tx)
+        .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO recovery_codes ( user_id, code_hash )
+        SELECT $1, code_hash FROM UNNEST($2::VARCHAR[]) AS code ( code_hash )
+        "#,
+        id,
+        recovery_code_hashes
+    )
+    .execute(&mut tx)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE sessions SET two_factor = TRUE WHERE id = $1 AND user_id = $2",
+        session_id,
+        id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await
+}
+
+pub async fn disable_totp(id: i32, pool: &db::DbPool) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE users SET totp_secret = NULL, totp_enabled_at = NULL, totp_last_used_step = NULL
+        WHERE id = $1
+        RETURNING id
+        "#,
+        id
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    sqlx::query!("DELETE FROM recovery_codes WHERE user_id = $1", id)
+        .execute(&mut tx)
+        .await?;
+
+    sqlx::query!("UPDATE sessions SET two_factor = FALSE WHERE user_id = $1", id)
+        .execute(&mut tx)
+        .await?;
+
+    tx.commit().await
+}
+
+// Whether the step was not used already, codes being single use
+pub async fn use_totp_step(id: i32, step: i64, pool: &db::DbPool) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE users SET totp_last_used_step = $1
+        WHERE id = $2 AND ( totp_last_used_step IS NULL OR totp_last_used_step < $1 )
+        RETURNING id
+        "#,
+        step,
+        id
+    )
+    .fetch_optional(pool)
+    .await
+    .map(|record| record.is_some())
+}
+
+pub struct RecoveryCodePostgres {
+    pub id: i32,
+    pub code_hash: String,
+}
+
+pub async fn find_unused_recovery_codes(user_id: i32, pool: &db::DbPool) -> Result<Vec<RecoveryCodePostgres>, sqlx::Error> {
+    sqlx::query_as!(
+        RecoveryCodePostgres,
+        "SELECT id, code_hash FROM recovery_codes WHERE user_id = $1 AND used_at IS NULL",
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Whether the code was not used already
+pub async fn use_recovery_code(id: i32, pool: &db::DbPool) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        "UPDATE recovery_codes SET used_at = $1 WHERE id = $2 AND used_at IS NULL RETURNING id",
+        Utc::now().naive_utc(),
+        id
+    )
+    .fetch_optional(pool)
+    .await
+    .map(|record| record.is_some())
+}
+
+// Latest end of the ongoing locks of the subjects, if any
+pub async fn find_login_lock_end(subjects: &[String], pool: &db::DbPool) -> Result<Option<NaiveDateTime>, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT MAX(locked_until) AS "locked_until"
+        FROM login_failures
+        WHERE subject = ANY($1) AND locked_until > $2
+        "#,
+        subjects,
+        Utc::now().naive_utc()
+    )
+    .fetch_one(pool)
+    .await
+    .map(|record| record.locked_until)
+}
+
+// Counts from scratch again when the subject failed and was locked only before
+// forget_before. Returns the failures counted.
+pub async fn record_login_failure(subject: &str, forget_before: NaiveDateTime, pool: &db::DbPool) -> Result<i32, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO login_failures ( subject, failures, last_failure_at )
+        VALUES ( $1, 1, $2 )
+        ON CONFLICT ( subject ) DO UPDATE
+        SET failures = CASE
+                WHEN login_failures.last_failure_at < $3
+                    AND ( login_failures.locked_until IS NULL OR login_failures.locked_until < $3 )
+                THEN 1
+                ELSE login_failures.failures + 1
+            END,
+            last_failure_at = $2
+        RETURNING failures
+        "#,
+        subject,
+        Utc::now().naive_utc(),
+        forget_before
+    )
+    .fetch_one(pool)
+    .await
+    .map(|record| record.failures)
+}
+
+pub async fn lock_login_subject(subject: &str, until: NaiveDateTime, pool: &db::DbPool) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE login_failures SET locked_until = $1 WHERE subject = $2",
+        until,
+        subject
+    )
+    .execute(pool)
+    .await
+    .map(|_| ())
+}
+
+// Subjects which would count from scratch anyway, see record_login_failure
+pub async fn forget_old_login_failures(forget_before: NaiveDateTime, pool: &db::DbPool) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM login_failures
+        WHERE last_failure_at < $1 AND ( locked_until IS NULL OR locked_until < $1 )
+        "#,
+        forget_before
+    )
+    .execute(pool)
+    .await
+    .map(|_| ())
+}
+
+// Whether there was anything to forget
+pub async fn forget_login_failures(subject: &str, pool: &db::DbPool) -> Result<bool, sqlx::Error> {
+    sqlx::query!("DELETE FROM login_failures WHERE subject = $1", subject)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected() > 0)
+}
+
+pub async fn find_permissions_of_role(role: Role, pool: &db::DbPool) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query!(
+        "SELECT permission FROM role_permissions WHERE role = $1 ORDER BY permission",
+        role as i32
+    )
+    .fetch_all(pool)
+    .await
+    .map(|records| records.into_iter().map(|record| record.permission).collect())
+}
+
+pub struct RolePermissionsPostgres {
+    pub id: i32,
+    pub permissions: Vec<String>,
+}
+
+pub async fn find_roles_with_permissions(pool: &db::DbPool) -> Result<Vec<RolePermissionsPostgres>, sqlx::Error> {
+    sqlx::query_as!(
+        RolePermissionsPostgres,
+        r#"
+        SELECT roles.id,
+            ARRAY_REMOVE(ARRAY_AGG(role_permissions.permission ORDER BY role_permissions.permission), NULL)
+                AS "permissions!"
+        FROM roles
+        LEFT JOIN role_permissions ON role_permissions.role = roles.id
+        GROUP BY roles.id
+        ORDER BY roles.id
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub struct ApiKeyPostgres {
+    pub id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+}
+
+// Keys act for their owner, with a second factor if their session had one
+pub async fn insert_api_key(
+    owner: &Claims,
+    name: &str,
+    key_hash: &str,
+    prefix: &str,
+    scopes: &[String],
+    expires_at: Option<NaiveDateTime>,
+    pool: &db::DbPool,
+) -> Result<ApiKeyPostgres, sqlx::Error> {
+    sqlx::query_as!(
+        ApiKeyPostgres,
+        r#"
+        INSERT INTO api_keys ( user_id, name, key_hash, prefix, scopes, created_at, expires_at, two_factor )
+        VALUES ( $1, $2, $3, $4, $5, $6, $7, $8 )
+        RETURNING id, name, prefix, scopes, created_at, expires_at, last_used_at
+        "#,
+        owner.id,
+        name,
+        key_hash,
+        prefix,
+        scopes,
+        Utc::now().naive_utc(),
+        expires_at,
+        owner.two_factor
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn find_api_keys_of_user(user_id: i32, pool: &db::DbPool) -> Result<Vec<ApiKeyPostgres>, sqlx::Error> {
+    sqlx::query_as!(
+        ApiKeyPostgres,
+        r#"
+        SELECT id, name, prefix, scopes, created_at, expires_at, last_used_at
+        FROM api_keys WHERE user_id = $1
+        ORDER BY created_at DESC, id DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Whether the user had such a key
+pub async fn delete_api_key(id: i32, user_id: i32, pool: &db::DbPool) -> Result<bool, sqlx::Error> {
+    sqlx::query!("DELETE FROM api_keys WHERE id = $1 AND user_id = $2", id, user_id)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected() > 0)
+}
+
+pub struct ApiKeyOwnerPostgres {
+    pub user_id: i32,
+    pub role: i32,
+    pub token_version: i32,
+    pub scopes: Vec<String>,
+    pub two_factor: bool,
+}
+
+// Who the key belongs to, unless it is unknown or expired
+pub async fn find_api_key_owner(key_hash: &str, pool: &db::DbPool) -> Result<Option<ApiKeyOwnerPostgres>, sqlx::Error> {
+    sqlx::query_as!(
+        ApiKeyOwnerPostgres,
+        r#"
+        SELECT users.id AS user_id, users.role, users.token_version, api_keys.scopes, api_keys.two_factor
+        FROM api_keys INNER JOIN users ON users.id = api_keys.user_id
+        WHERE api_keys.key_hash = $1
+            AND ( api_keys.expires_at IS NULL OR api_keys.expires_at > $2 )
+        "#,
+        key_hash,
+        Utc::now().naive_utc()
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn mark_api_key_used(key_hash: &str, pool: &db::DbPool) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE api_keys SET last_used_at = $2 WHERE key_hash = $1",
+        key_hash,
+        Utc::now().naive_utc()
+    )
+    .execute(pool)
+    .await
+    .map(|_| ())
+}
+
+// Id of the key, unless it is unknown or expired, leaving it as it is
+pub async fn find_usable_api_key_id(key_hash: &str, pool: &db::DbPool) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT id FROM api_keys
+        WHERE key_hash = $1 AND ( expires_at IS NULL OR expires_at > $2 )
+        "#,
+        key_hash,
+        Utc::now().naive_utc()
+    )
+    .fetch_optional(pool)
+    .await
+    .map(|row| row.map(|row| row.id))
+}
+
+pub async fn insert_oidc_login_attempt(
+    state_hash: &str,
+    provider: &str,
+    nonce: &str,
+    code_verifier: &str,
+    user_id: Option<i32>,
+    expires_at: NaiveDateTime,
+    pool: &db::DbPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO oidc_login_attempts ( state_hash, provider, nonce, code_verifier, user_id, expires_at )
+        VALUES ( $1, $2, $3, $4, $5, $6 )
+        "#,
+        state_hash,
+        provider,
+        nonce,
+        code_verifier,
+        user_id,
+        expires_at
+    )
+    .execute(pool)
+    .await
+    .map(|_| ())
+}
+
+pub struct OidcLoginAttemptPostgres {
+    pub nonce: String,
+    pub code_verifier: String,
+    pub user_id: Option<i32>,
+}
+
+// Attempts can only be taken once, None when the state is unknown, expired or
+// meant for another provider
+pub async fn take_oidc_login_attempt(
+    state_hash: &str,
+    provider: &str,
+    pool: &db::DbPool,
+) -> Result<Option<OidcLoginAttemptPostgres>, sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM oidc_login_attempts WHERE expires_at <= $1",
+        Utc::now().naive_utc()
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query_as!(
+        OidcLoginAttemptPostgres,
+        r#"
+        DELETE FROM oidc_login_attempts
+        WHERE state_hash = $1 AND provider = $2
+        RETURNING nonce, code_verifier, user_id
+        "#,
+        state_hash,
+        provider
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn find_user_id_by_external_identity(
+    provider: &str,
+    subject: &str,
+    pool: &db::DbPool,
+) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query!(
+        "SELECT user_id FROM external_identities WHERE provider = $1 AND subject = $2",
+        provider,
+        subject
+    )
+    .fetch_optional(pool)
+    .await
+    .map(|record| record.map(|record| record.user_id))
+}
+
+// A user without a password, linked to their identity at the provider, the
+// email verified when the provider says it is. All of it or nothing.
+pub async fn insert_provisioned_user(
+    username: &str,
+    email: &str,
+    email_verified: bool,
+    role: Role,
+    provider: &str,
+    subject: &str,
+    pool: &db::DbPool,
+) -> Result<i32, sqlx::Error> {
+    let now = Utc::now().naive_utc();
+    let mut tx = pool.begin().await?;
+
+    let id = sqlx::query!(
+        r#"
+        INSERT INTO users ( username, email, password, role, email_verified_at )
+        VALUES ( $1, $2, '', $3, $4 )
+        RETURNING id
+        "#,
+        username,
+        email,
+        role as i32,
+        if email_verified { Some(now) } else { None }
+    )
+    .fetch_one(&mut tx)
+    .await?
+    .id;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO external_identities ( provider, subject, user_id, created_at )
+        VALUES ( $1, $2, $3, $4 )
+        "#,
+        provider,
+        subject,
+        id,
+        now
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(id)
+}
+
+// Fails with a unique violation if the identity is linked already
+pub async fn insert_external_identity(
+    provider: &str,
+    subject: &str,
+    user_id: i32,
+    pool: &db::DbPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO external_identities ( provider, subject, user_id, created_at )
+        VALUES ( $1, $2, $3, $4 )
+        "#,
+        provider,
+        subject,
+        user_id,
+        Utc::now().naive_utc()
+    )
+    .execute(pool)
+    .await
+    .map(|_| ())
+}
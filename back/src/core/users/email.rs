@@ -1,5 +1,6 @@
 use serde::Serialize;
 use regex::Regex;
+use std::sync::OnceLock;
 use crate::core::db;
 
 #[derive(Serialize)]
@@ -9,21 +10,27 @@ pub enum Issues {
     NotUnique,
 }
 
-pub fn string_is_email(email: &String) -> bool {
-    let email_regex = Regex::new(
-        r#"^(?:[a-z0-9!#$%&'*+/=?^_`{|}~-]+(?:\.[a-z0-9!#$%&'*+/=?^_`{|}~-]+)*|"(?:[\x01-\x08\x0b\x0c\x0e-\x1f\x21\x23-\x5b\x5d-\x7f]|\\[\x01-\x09\x0b\x0c\x0e-\x7f])*")@(?:(?:[a-z0-9](?:[a-z0-9-]*[a-z0-9])?\.)+[a-z0-9](?:[a-z0-9-]*[a-z0-9])?|\[(?:(?:(2(5[0-5]|[0-4][0-9])|1[0-9][0-9]|[1-9]?[0-9]))\.){3}(?:(2(5[0-5]|[0-4][0-9])|1[0-9][0-9]|[1-9]?[0-9])|[a-z0-9-]*[a-z0-9]:(?:[\x01-\x08\x0b\x0c\x0e-\x1f\x21-\x5a\x53-\x7f]|\\[\x01-\x09\x0b\x0c\x0e-\x7f])+)\])"#,
-    )
-    .unwrap();
-    email_regex.is_match(email)
+static EMAIL_REGEX: OnceLock<Regex> = OnceLock::new();
+
+pub fn string_is_email(email: &str) -> bool {
+    EMAIL_REGEX
+        .get_or_init(|| {
+            Regex::new(
+                r#"^(?:[a-z0-9!#$%&'*+/=?^_`{|}~-]+(?:\.[a-z0-9!#$%&'*+/=?^_`{|}~-]+)*|"(?:[\x01-\x08\x0b\x0c\x0e-\x1f\x21\x23-\x5b\x5d-\x7f]|\\[\x01-\x09\x0b\x0c\x0e-\x7f])*")@(?:(?:[a-z0-9](?:[a-z0-9-]*[a-z0-9])?\.)+[a-z0-9](?:[a-z0-9-]*[a-z0-9])?|\[(?:(?:(2(5[0-5]|[0-4][0-9])|1[0-9][0-9]|[1-9]?[0-9]))\.){3}(?:(2(5[0-5]|[0-4][0-9])|1[0-9][0-9]|[1-9]?[0-9])|[a-z0-9-]*[a-z0-9]:(?:[\x01-\x08\x0b\x0c\x0e-\x1f\x21-\x5a\x53-\x7f]|\\[\x01-\x09\x0b\x0c\x0e-\x7f])+)\])"#,
+            )
+            .unwrap()
+        })
+        .is_match(email)
 }
 
-pub async fn find_issues(email: &String, pool: &db::DbPool) -> Option<Vec<Issues>> {
+// The user of id except_user_id, if any, is not taken into account for uniqueness
+pub async fn find_issues(email: &String, except_user_id: Option<i32>, pool: &db::DbPool) -> Option<Vec<Issues>> {
     let mut issues = vec![];
 
     if !string_is_email(email) {
         issues.push(Issues::Malformed);
     } else {
-        match email_is_unique(email, pool).await {
+        match email_is_unique(email, except_user_id, pool).await {
             Ok(false) => issues.push(Issues::NotUnique),
             Err(_) => issues.push(Issues::CouldNotBeProcessed),
             _ => (),
@@ -37,6 +44,6 @@ pub async fn find_issues(email: &String, pool: &db::DbPool) -> Option<Vec<Issues
     }
 }
 
-async fn email_is_unique(email: &String, pool: &db::DbPool) -> Result<bool, sqlx::Error> {
-    db::check_field_is_unique!("users", "email", email, pool)
+async fn email_is_unique(email: &String, except_user_id: Option<i32>, pool: &db::DbPool) -> Result<bool, sqlx::Error> {
+    db::check_field_is_unique!("users", "email", email, except_id: except_user_id, pool)
 }
\ No newline at end of file
@@ -0,0 +1,121 @@
+use crate::core::{db, mail, security};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::env;
+
+use super::{dl, find_by_id, User};
+
+pub const LINK_LIFETIME_HOURS: i64 = 48;
+pub const RESEND_INTERVAL_SEC: i64 = 60;
+
+const DEFAULT_PUBLIC_URL: &str = "http://127.0.0.1:8080";
+
+// Signed rather than stored, the link only holds for the email it was sent to
+#[derive(Serialize, Deserialize)]
+struct JwtClaims {
+    uid: i32,
+    email: String,
+    exp: usize,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmData {
+    pub token: String,
+}
+
+#[derive(Serialize)]
+pub enum Error {
+    Denied(DeniedReasons),
+    Throttled { retry_after_sec: i64 },
+    Failure(Failure),
+}
+
+#[derive(Serialize)]
+pub enum DeniedReasons {
+    // Badly signed, expired or sent to an email the user no longer has
+    InvalidVerificationToken,
+    AlreadyVerified,
+}
+
+#[derive(Serialize)]
+pub enum Failure {
+    Database,
+    TokenCreation,
+    Mail,
+}
+
+// Mails a verification link to the user's current email, unless one was
+// sent less than RESEND_INTERVAL_SEC ago
+pub async fn send(user: &User, mailer: &dyn mail::Mailer, pool: &db::DbPool) -> Result<(), Error> {
+    let now = Utc::now().naive_utc();
+    match dl::mark_email_verification_sent(user.id, now - Duration::seconds(RESEND_INTERVAL_SEC), pool).await {
+        Ok(_) => (),
+        Err(sqlx::Error::RowNotFound) => {
+            let retry_after_sec = user
+                .email_verification_sent_at
+                .map(|sent_at| (sent_at + Duration::seconds(RESEND_INTERVAL_SEC) - now).num_seconds())
+                .unwrap_or(RESEND_INTERVAL_SEC);
+            return Err(Error::Throttled { retry_after_sec: retry_after_sec.max(1) });
+        }
+        Err(_) => return Err(Error::Failure(Failure::Database)),
+    };
+
+    let token = security::jwt_create(
+        &mut JwtClaims {
+            uid: user.id,
+            email: user.email.clone(),
+            exp: 0,
+        },
+        LINK_LIFETIME_HOURS * 60 * 60,
+    )
+    .map_err(|_| Error::Failure(Failure::TokenCreation))?;
+
+    let link = format!(
+        "{}/api/auth/email-verification/confirm?token={}",
+        env::var("PUBLIC_URL").unwrap_or_else(|_| DEFAULT_PUBLIC_URL.to_owned()),
+        token
+    );
+    let lifetime_hours = LINK_LIFETIME_HOURS.to_string();
+    let message = mail::templates::EMAIL_VERIFICATION.render(
+        &user.email,
+        &[
+            ("username", &user.username),
+            ("lifetime_hours", &lifetime_hours),
+            ("link", &link),
+        ],
+    );
+    mailer
+        .send(message)
+        .await
+        .map_err(|_| Error::Failure(Failure::Mail))
+}
+
+pub async fn resend(user_id: i32, mailer: &dyn mail::Mailer, pool: &db::DbPool) -> Result<(), Error> {
+    let user = find_by_id(user_id, pool)
+        .await
+        .map_err(|_| Error::Failure(Failure::Database))?;
+
+    if user.email_verified_at.is_some() {
+        return Err(Error::Denied(DeniedReasons::AlreadyVerified));
+    }
+    send(&user, mailer, pool).await
+}
+
+impl ConfirmData {
+    pub async fn confirm(&self, pool: &db::DbPool) -> Result<(), Error> {
+        let claims = security::jwt_decode::<JwtClaims>(&self.token)
+            .map_err(|_| Error::Denied(DeniedReasons::InvalidVerificationToken))?;
+
+        match dl::mark_email_verified(claims.uid, &claims.email, pool).await {
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::RowNotFound) => Err(Error::Denied(DeniedReasons::InvalidVerificationToken)),
+            Err(_) => Err(Error::Failure(Failure::Database)),
+        }
+    }
+}
+
+impl security::JwtClaims for JwtClaims {
+    fn set_expiration(&mut self, exp: usize) {
+        self.exp = exp;
+    }
+}
@@ -0,0 +1,46 @@
+use crate::core::{
+    db,
+    jokes::{self, Joke},
+};
+use chrono::{NaiveDateTime, Utc};
+use serde::Serialize;
+
+use super::{dl, find_by_id, token::Claims};
+
+// Everything the user wrote or did, in a single document
+#[derive(Serialize)]
+pub struct Archive {
+    pub exported_at: NaiveDateTime,
+    pub profile: serde_json::Value,
+    pub jokes: Vec<Joke>,
+    pub comments: Vec<dl::CommentRecord>,
+    pub reactions: Vec<dl::ReactionRecord>,
+}
+
+#[derive(Serialize)]
+pub enum Error {
+    DataLayerFailure,
+}
+
+pub async fn export(claims: Claims, pool: &db::DbPool) -> Result<Archive, Error> {
+    let user = find_by_id(claims.id, pool)
+        .await
+        .map_err(|_| Error::DataLayerFailure)?;
+    let jokes = jokes::list_by_author(user.id, pool)
+        .await
+        .map_err(|_| Error::DataLayerFailure)?;
+    let comments = dl::find_comments_of_user(user.id, pool)
+        .await
+        .map_err(|_| Error::DataLayerFailure)?;
+    let reactions = dl::find_reactions_of_user(user.id, pool)
+        .await
+        .map_err(|_| Error::DataLayerFailure)?;
+
+    Ok(Archive {
+        exported_at: Utc::now().naive_utc(),
+        profile: user.to_json_as_seen_from(&Some(claims)),
+        jokes,
+        comments,
+        reactions,
+    })
+}
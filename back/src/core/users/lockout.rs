@@ -0,0 +1,107 @@
+use crate::core::db;
+use chrono::{Duration, Utc};
+use serde::Serialize;
+use std::net::IpAddr;
+
+use super::{dl, find_by_username, two_factor, Error as UserError};
+
+// Failures allowed before locking, more for addresses many users may share
+pub const ACCOUNT_FREE_FAILURES: i32 = 5;
+pub const IP_FREE_FAILURES: i32 = 20;
+// Doubled by each failure past the free ones
+pub const BASE_LOCK_SEC: i64 = 30;
+pub const MAX_LOCK_SEC: i64 = 60 * 60;
+// Failures are forgotten that long after the last one and the end of the lock
+pub const FAILURE_MEMORY_MIN: i64 = 15;
+// Wrong codes a single two factor token may take before being spent
+pub const PENDING_LOGIN_MAX_ATTEMPTS: i32 = 5;
+
+pub enum Subject<'a> {
+    User(i32),
+    // Logins matching no user get locked too, so locks tell nothing about
+    // which users exist
+    UnknownLogin(&'a str),
+    Ip(IpAddr),
+    // A two factor token, by its id, locked for good once out of attempts
+    PendingLogin(&'a str),
+}
+
+#[derive(Serialize)]
+pub enum Error {
+    NotFound,
+    DataAccessLayerFailure,
+}
+
+impl Subject<'_> {
+    fn key(&self) -> String {
+        match self {
+            Subject::User(id) => format!("user:{}", id),
+            Subject::UnknownLogin(login) => format!("login:{}", login.to_lowercase()),
+            Subject::Ip(ip) => format!("ip:{}", ip),
+            Subject::PendingLogin(id) => format!("pending:{}", id),
+        }
+    }
+
+    fn free_failures(&self) -> i32 {
+        match self {
+            Subject::Ip(_) => IP_FREE_FAILURES,
+            Subject::PendingLogin(_) => PENDING_LOGIN_MAX_ATTEMPTS - 1,
+            _ => ACCOUNT_FREE_FAILURES,
+        }
+    }
+
+    fn lock_sec(&self, failures_past_free: i32) -> i64 {
+        match self {
+            // Outlives the token, which cannot be used anymore
+            Subject::PendingLogin(_) => two_factor::PENDING_LOGIN_LIFETIME_SEC,
+            _ => lock_duration_sec(failures_past_free),
+        }
+    }
+}
+
+pub fn lock_duration_sec(failures_past_free: i32) -> i64 {
+    let doublings = (failures_past_free - 1).clamp(0, 16) as u32;
+    (BASE_LOCK_SEC * 2i64.pow(doublings)).min(MAX_LOCK_SEC)
+}
+
+// Seconds until none of the subjects is locked anymore, if any is
+pub(super) async fn retry_after_sec(subjects: &[Subject<'_>], pool: &db::DbPool) -> Result<Option<i64>, sqlx::Error> {
+    let keys: Vec<String> = subjects.iter().map(Subject::key).collect();
+    Ok(dl::find_login_lock_end(&keys, pool)
+        .await?
+        .map(|end| (end - Utc::now().naive_utc()).num_seconds() + 1))
+}
+
+pub(super) async fn record_failure(subjects: &[Subject<'_>], pool: &db::DbPool) -> Result<(), sqlx::Error> {
+    let now = Utc::now().naive_utc();
+    let forget_before = now - Duration::minutes(FAILURE_MEMORY_MIN);
+    dl::forget_old_login_failures(forget_before, pool).await?;
+    for subject in subjects {
+        let key = subject.key();
+        let failures = dl::record_login_failure(&key, forget_before, pool).await?;
+        if failures > subject.free_failures() {
+            let lock_sec = subject.lock_sec(failures - subject.free_failures());
+            dl::lock_login_subject(&key, now + Duration::seconds(lock_sec), pool).await?;
+        }
+    }
+    Ok(())
+}
+
+// After a successful login, the failures of others from the same address
+// still count
+pub(super) async fn forget(subject: &Subject<'_>, pool: &db::DbPool) -> Result<(), sqlx::Error> {
+    dl::forget_login_failures(&subject.key(), pool).await.map(|_| ())
+}
+
+// Lets an admin give a locked out user their attempts back. The addresses they
+// failed from stay locked: which ones they were is not kept, and others behind
+// them may be the ones who failed.
+pub async fn unlock(username: &String, pool: &db::DbPool) -> Result<(), Error> {
+    let user = find_by_username(username, pool).await.map_err(|error| match error {
+        UserError::NotFound => Error::NotFound,
+        _ => Error::DataAccessLayerFailure,
+    })?;
+    forget(&Subject::User(user.id), pool)
+        .await
+        .map_err(|_| Error::DataAccessLayerFailure)
+}
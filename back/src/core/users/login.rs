@@ -1,12 +1,15 @@
-use crate::core::{db, security};
+use crate::core::{db, security, settings};
 use super::{
-    token,
+    lockout,
+    session,
+    two_factor,
     email::string_is_email,
     find_by_username,
     find_by_email,
     Error as UserError
 };
 use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
 
 #[derive(Deserialize)]
 pub struct Data {
@@ -14,6 +17,12 @@ pub struct Data {
     pub password: String,
 }
 
+pub enum Outcome {
+    LoggedIn(session::Tokens),
+    // The password was right but a second factor is needed, see two_factor
+    TwoFactorPending(two_factor::PendingLogin),
+}
+
 #[derive(Serialize)]
 pub enum Error {
     Denied(DeniedReasons),
@@ -24,7 +33,8 @@ pub enum Error {
 pub enum DeniedReasons {
     UnknownLogin, // Username wrong
     InvalidPassword, // Username good but password wrong
-    InvalidCredentials // Email or password wrong (avoiding guessing attacks on emails)
+    InvalidCredentials, // Email or password wrong (avoiding guessing attacks on emails)
+    Locked { retry_after_sec: i64 } // Too many failures lately, from the account or the address
 }
 
 #[derive(Serialize)]
@@ -33,41 +43,80 @@ pub enum Failure {
     TokenCreation
 }
 
+impl From<session::Failure> for Failure {
+    fn from(failure: session::Failure) -> Self {
+        match failure {
+            session::Failure::Database => Failure::Database,
+            session::Failure::TokenCreation => Failure::TokenCreation,
+        }
+    }
+}
+
 impl Data {
-    pub async fn login(&self, pool: &db::DbPool) -> Result<String, Error> {
+    // ip is the address of the client, failures are tracked per account and
+    // per address
+    pub async fn login(&self, ip: Option<IpAddr>, pool: &db::DbPool) -> Result<Outcome, Error> {
+        let generic_denials = settings::get(pool)
+            .await
+            .map_err(|_| Error::Failure(Failure::Database))?
+            .generic_login_denials;
+
         let login_is_email = string_is_email(&self.login);
         let maybe_user = if login_is_email {
             find_by_email(&self.login, pool).await
         } else {
             find_by_username(&self.login, pool).await
         };
+        let maybe_user = match maybe_user {
+            Ok(user) => Some(user),
+            Err(UserError::NotFound) => None,
+            Err(_) => return Err(Error::Failure(Failure::Database)),
+        };
 
-        match (login_is_email, maybe_user) {
-            (_, Ok(user)) => {
-                if security::password_verify(&self.password, &user.password) {
-                    token::from_claims(token::Claims {
-                        id: user.id,
-                        role: user.role
-                    }, 120)
-                        .map_err(|_| Error::Failure(Failure::TokenCreation))
-                } else {
-                    if login_is_email {
-                        Err(Error::Denied(DeniedReasons::InvalidCredentials))
-                    } else {
-                        Err(Error::Denied(DeniedReasons::InvalidPassword))
-                    }
-                }
-            },
-            (false, Err(UserError::NotFound)) => {
-                Err(Error::Denied(DeniedReasons::UnknownLogin))
-            },
-            (true, Err(UserError::NotFound)) => {
+        let mut subjects = vec![match &maybe_user {
+            Some(user) => lockout::Subject::User(user.id),
+            None => lockout::Subject::UnknownLogin(&self.login),
+        }];
+        subjects.extend(ip.map(lockout::Subject::Ip));
+        if let Some(retry_after_sec) = lockout::retry_after_sec(&subjects, pool)
+            .await
+            .map_err(|_| Error::Failure(Failure::Database))?
+        {
+            return Err(Error::Denied(DeniedReasons::Locked { retry_after_sec }));
+        }
+
+        let verified = match maybe_user {
+            Some(user) if security::password_verify(&self.password, &user.password) => Ok(user),
+            Some(_) if login_is_email || generic_denials => Err(DeniedReasons::InvalidCredentials),
+            Some(_) => Err(DeniedReasons::InvalidPassword),
+            None if login_is_email || generic_denials => {
                 security::fake_password_verify(); // Avoiding guessing attacks on response time
-                Err(Error::Denied(DeniedReasons::InvalidCredentials))
+                Err(DeniedReasons::InvalidCredentials)
             },
-            (_, Err(_)) => {
-                Err(Error::Failure(Failure::Database))
+            None => Err(DeniedReasons::UnknownLogin),
+        };
+        let user = match verified {
+            Ok(user) => user,
+            Err(reason) => {
+                lockout::record_failure(&subjects, pool)
+                    .await
+                    .map_err(|_| Error::Failure(Failure::Database))?;
+                return Err(Error::Denied(reason));
             }
+        };
+        lockout::forget(&subjects[0], pool)
+            .await
+            .map_err(|_| Error::Failure(Failure::Database))?;
+
+        if user.totp_enabled_at.is_some() {
+            two_factor::pending_login(&user)
+                .map(Outcome::TwoFactorPending)
+                .map_err(|_| Error::Failure(Failure::TokenCreation))
+        } else {
+            session::open(&user, false, pool)
+                .await
+                .map(Outcome::LoggedIn)
+                .map_err(|failure| Error::Failure(failure.into()))
         }
     }
-}
\ No newline at end of file
+}
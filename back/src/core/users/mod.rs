@@ -1,18 +1,33 @@
 use self::token::Claims;
+use chrono::NaiveDateTime;
 use serde::{Serialize};
 
-use super::db;
+use super::{db, settings};
 
 mod dl;
 mod email;
 mod password;
 mod username;
 mod role;
+mod permission;
 
+pub mod api_key;
+pub mod deletion;
+pub mod email_verification;
+pub mod export;
+pub mod lockout;
 pub mod login;
+pub mod oidc_login;
+pub mod profile;
+pub mod password_change;
+pub mod password_reset;
 pub mod registration;
+pub mod search;
+pub mod session;
 pub mod token;
+pub mod two_factor;
 pub use role::*;
+pub use permission::*;
 
 #[derive(Serialize)]
 pub struct User {
@@ -21,51 +36,94 @@ pub struct User {
     pub role: Role,
     pub email: String,
     pub password: String,
+    #[serde(skip_serializing)]
+    pub token_version: i32,
+    pub email_verified_at: Option<NaiveDateTime>,
+    #[serde(skip_serializing)]
+    pub email_verification_sent_at: Option<NaiveDateTime>,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub website: Option<String>,
+    #[serde(skip_serializing)]
+    pub totp_secret: Option<String>,
+    #[serde(skip_serializing)]
+    pub totp_enabled_at: Option<NaiveDateTime>,
 }
 
 #[derive(Serialize, Debug)]
 pub enum Error {
     NotFound,
+    // Authors must have verified their email, see settings
+    UnverifiedEmail,
     DataAccessLayerFailure,
 }
 
 impl User {
-    pub async fn set_role(&mut self, new_role: Role, pool: &db::DbPool) -> Result<(), Error> {
+    // The user will need to refresh their access tokens to get the new role
+    pub async fn set_role(
+        &mut self,
+        new_role: Role,
+        cache: &session::Cache,
+        pool: &db::DbPool,
+    ) -> Result<(), Error> {
+        let promoted = matches!(new_role, Role::Author | Role::Moderator);
+        if self.role == Role::None && promoted && self.email_verified_at.is_none() {
+            let settings = settings::get(pool)
+                .await
+                .map_err(|_| Error::DataAccessLayerFailure)?;
+            if settings.authors_need_verified_email {
+                return Err(Error::UnverifiedEmail);
+            }
+        }
+
+        self.token_version = dl::update_role_returning_token_version(self.id, new_role, pool)
+            .await
+            .map_err(|_| Error::DataAccessLayerFailure)?;
         self.role = new_role;
-        dl::update_role_returning_id(self.id, new_role, pool)
+        cache.forget_user(self.id);
+        Ok(())
+    }
+
+    // Outstanding access tokens stop working, sessions can still be refreshed
+    pub async fn outdate_tokens(&mut self, cache: &session::Cache, pool: &db::DbPool) -> Result<(), Error> {
+        self.token_version = dl::bump_token_version(self.id, pool)
             .await
-            .map(|_| ())
-            .map_err(|_| Error::DataAccessLayerFailure)
+            .map_err(|_| Error::DataAccessLayerFailure)?;
+        cache.forget_user(self.id);
+        Ok(())
     }
 
     pub fn is_searchable_by(&self, claims: &Option<Claims>) -> bool {
-        let is_searcher = match claims {
-            Some(Claims { id, .. }) => *id == self.id,
-            None => false,
-        };
         match claims {
-            Some(Claims { role, .. }) => {
-                self.role != Role::None || *role == Role::Admin || is_searcher
+            Some(claims) => {
+                self.role != Role::None || claims.can(Permission::ManageUsers) || claims.id == self.id
             }
             None => self.role != Role::None,
         }
     }
 
     pub fn to_json_as_seen_from(&self, claims: &Option<Claims>) -> serde_json::Value {
-        if let Some(Claims { id, role }) = claims {
-            if *id == self.id || *role == Role::Admin {
+        if let Some(claims) = claims {
+            if claims.id == self.id || claims.can(Permission::ManageUsers) {
                 return serde_json::json!({
                     "id": self.id,
                     "username": self.username,
                     "role": self.role,
-                    "email": self.email
+                    "email": self.email,
+                    "email_verified": self.email_verified_at.is_some(),
+                    "display_name": self.display_name,
+                    "bio": self.bio,
+                    "website": self.website
                 });
             }
         }
         serde_json::json!({
             "id": self.id,
             "username": self.username,
-            "role": self.role
+            "role": self.role,
+            "display_name": self.display_name,
+            "bio": self.bio,
+            "website": self.website
         })
     }
 }
@@ -82,25 +140,6 @@ pub async fn find_by_username(username: &String, pool: &db::DbPool) -> Result<Us
     find_by_x!("username", username, pool)
 }
 
-pub async fn search_by_username_to_json_as_seen_from(
-    query: &String,
-    claims: &Option<Claims>,
-    pool: &db::DbPool,
-) -> Result<Vec<serde_json::Value>, Error> {
-    let result = dl::search_by_username(query, pool).await;
-    match result {
-        Ok(users) => Ok(users
-            .into_iter()
-            .filter(|user| user.is_searchable_by(claims))
-            .map(|user| user.to_json_as_seen_from(claims))
-            .collect()),
-        Err(error) => {
-            println!("{}", error);
-            Err(Error::DataAccessLayerFailure)
-        },
-    }
-}
-
 macro_rules! find_by_x {
     ($field:literal, $value:ident, $pool:ident) => {
         dl::find_by_x!($field, $value, $pool).map_err(|error| match error {
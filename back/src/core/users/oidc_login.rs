@@ -0,0 +1,255 @@
+use crate::core::{db, oidc, security};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    dl, email::string_is_email, find_by_email, find_by_id, login::Outcome, session, two_factor,
+    username, Error as UserError, Role, User,
+};
+
+pub const ATTEMPT_LIFETIME_MIN: i64 = 10;
+// Leaving room for a number making it unique
+const USERNAME_BASE_MAX_LENGTH: usize = 24;
+const IDENTITY_CONSTRAINT: &str = "external_identities_pkey";
+const USERNAME_CONSTRAINT: &str = "users_username_key";
+const USERNAME_MAX_ATTEMPTS: u32 = 3;
+
+#[derive(Serialize)]
+pub struct Authorization {
+    pub authorization_url: String,
+}
+
+// What the provider sent the user back with
+#[derive(Deserialize)]
+pub struct CallbackData {
+    pub code: String,
+    pub state: String,
+}
+
+#[derive(Serialize)]
+pub enum Error {
+    Denied(DeniedReasons),
+    Failure(Failure),
+}
+
+#[derive(Serialize)]
+pub enum DeniedReasons {
+    UnknownProvider,
+    // Unknown, already used, expired or meant for another provider
+    InvalidState,
+    // Refused by the provider
+    InvalidCode,
+    // Wrongly signed, expired, for another client or another login
+    InvalidIdToken,
+    // Nobody is linked to the identity and the provider does not provision
+    // users
+    UnknownIdentity,
+    // Linked to another user
+    IdentityTaken,
+    // Linking attempts are only finished by the user who started them, lest
+    // they be sent to someone else to link their identity to the account
+    NotTheLinkingUser,
+    // Someone already registered with the email of the identity, they have to
+    // link it from their account
+    EmailTaken,
+}
+
+#[derive(Serialize)]
+pub enum Failure {
+    Database,
+    Provider,
+    TokenCreation,
+}
+
+impl From<oidc::Error> for Error {
+    fn from(error: oidc::Error) -> Self {
+        match error {
+            oidc::Error::Unavailable => Error::Failure(Failure::Provider),
+            oidc::Error::CodeRejected => Error::Denied(DeniedReasons::InvalidCode),
+            oidc::Error::InvalidIdToken => Error::Denied(DeniedReasons::InvalidIdToken),
+        }
+    }
+}
+
+// Starts the login of a user with the provider, or the linking of their
+// identity there to the account of linking_user_id
+pub async fn start(
+    providers: &oidc::Providers,
+    provider_name: &str,
+    linking_user_id: Option<i32>,
+    pool: &db::DbPool,
+) -> Result<Authorization, Error> {
+    let provider = providers
+        .get(provider_name)
+        .ok_or(Error::Denied(DeniedReasons::UnknownProvider))?;
+
+    let state = security::random_token();
+    let nonce = security::random_token();
+    let code_verifier = security::random_token();
+    let authorization_url = providers
+        .authorization_url(provider, &state, &nonce, &security::pkce_challenge(&code_verifier))
+        .await?;
+
+    dl::insert_oidc_login_attempt(
+        &security::token_hash(&state),
+        &provider.name,
+        &nonce,
+        &code_verifier,
+        linking_user_id,
+        Utc::now().naive_utc() + Duration::minutes(ATTEMPT_LIFETIME_MIN),
+        pool,
+    )
+    .await
+    .map_err(|_| Error::Failure(Failure::Database))?;
+
+    Ok(Authorization { authorization_url })
+}
+
+impl CallbackData {
+    // Logs in the user linked to the identity, linking or provisioning one
+    // first when there is none. Users with two factor authentication enabled
+    // still have to give a code.
+    pub async fn finish(
+        &self,
+        providers: &oidc::Providers,
+        provider_name: &str,
+        requester_id: Option<i32>,
+        pool: &db::DbPool,
+    ) -> Result<Outcome, Error> {
+        let provider = providers
+            .get(provider_name)
+            .ok_or(Error::Denied(DeniedReasons::UnknownProvider))?;
+
+        let attempt = dl::take_oidc_login_attempt(&security::token_hash(&self.state), &provider.name, pool)
+            .await
+            .map_err(|_| Error::Failure(Failure::Database))?
+            .ok_or(Error::Denied(DeniedReasons::InvalidState))?;
+        if attempt.user_id.is_some() && attempt.user_id != requester_id {
+            return Err(Error::Denied(DeniedReasons::NotTheLinkingUser));
+        }
+
+        let claims = providers
+            .exchange_code(provider, &self.code, &attempt.code_verifier)
+            .await?;
+        if claims.nonce.as_deref() != Some(attempt.nonce.as_str()) {
+            return Err(Error::Denied(DeniedReasons::InvalidIdToken));
+        }
+
+        let linked_user_id = dl::find_user_id_by_external_identity(&provider.name, &claims.sub, pool)
+            .await
+            .map_err(|_| Error::Failure(Failure::Database))?;
+        let user = match (linked_user_id, attempt.user_id) {
+            (Some(linked), Some(linking)) if linked != linking => {
+                return Err(Error::Denied(DeniedReasons::IdentityTaken))
+            }
+            (Some(user_id), _) => find_user(user_id, pool).await?,
+            (None, Some(linking)) => {
+                link(provider, &claims, linking, pool).await?;
+                find_user(linking, pool).await?
+            }
+            (None, None) => match provider.default_role {
+                Some(role) => provision(provider, &claims, role, pool).await?,
+                None => return Err(Error::Denied(DeniedReasons::UnknownIdentity)),
+            },
+        };
+
+        if user.totp_enabled_at.is_some() {
+            two_factor::pending_login(&user)
+                .map(Outcome::TwoFactorPending)
+                .map_err(|_| Error::Failure(Failure::TokenCreation))
+        } else {
+            session::open(&user, false, pool)
+                .await
+                .map(Outcome::LoggedIn)
+                .map_err(|failure| match failure {
+                    session::Failure::Database => Error::Failure(Failure::Database),
+                    session::Failure::TokenCreation => Error::Failure(Failure::TokenCreation),
+                })
+        }
+    }
+}
+
+async fn find_user(id: i32, pool: &db::DbPool) -> Result<User, Error> {
+    find_by_id(id, pool)
+        .await
+        .map_err(|_| Error::Failure(Failure::Database))
+}
+
+async fn link(provider: &oidc::Provider, claims: &oidc::IdClaims, user_id: i32, pool: &db::DbPool) -> Result<(), Error> {
+    match dl::insert_external_identity(&provider.name, &claims.sub, user_id, pool).await {
+        Ok(_) => Ok(()),
+        // Linked by a concurrent login
+        Err(sqlx::Error::Database(error)) if error.code().as_deref() == Some("23505") => {
+            Err(Error::Denied(DeniedReasons::IdentityTaken))
+        }
+        Err(_) => Err(Error::Failure(Failure::Database)),
+    }
+}
+
+// The new user has no password, only the provider lets them in until they
+// reset one
+async fn provision(provider: &oidc::Provider, claims: &oidc::IdClaims, role: Role, pool: &db::DbPool) -> Result<User, Error> {
+    // Unverified addresses could be someone else's
+    let email = claims
+        .email
+        .clone()
+        .filter(|email| claims.email_verified && string_is_email(email))
+        .unwrap_or_default();
+    if !email.is_empty() {
+        match find_by_email(&email, pool).await {
+            Ok(_) => return Err(Error::Denied(DeniedReasons::EmailTaken)),
+            Err(UserError::NotFound) => (),
+            Err(_) => return Err(Error::Failure(Failure::Database)),
+        }
+    }
+
+    let mut attempts = 0;
+    let id = loop {
+        let username = available_username(claims, pool).await?;
+        match dl::insert_provisioned_user(&username, &email, !email.is_empty(), role, &provider.name, &claims.sub, pool).await {
+            Ok(id) => break id,
+            // Provisioned by a concurrent login
+            Err(sqlx::Error::Database(error)) if error.constraint() == Some(IDENTITY_CONSTRAINT) => {
+                return Err(Error::Denied(DeniedReasons::IdentityTaken));
+            }
+            // Taken by a concurrent signup since it was found available
+            Err(sqlx::Error::Database(error))
+                if error.constraint() == Some(USERNAME_CONSTRAINT) && attempts < USERNAME_MAX_ATTEMPTS =>
+            {
+                attempts += 1;
+            }
+            Err(_) => return Err(Error::Failure(Failure::Database)),
+        }
+    };
+
+    find_user(id, pool).await
+}
+
+// From what the provider tells of the user, numbered when taken already
+async fn available_username(claims: &oidc::IdClaims, pool: &db::DbPool) -> Result<String, Error> {
+    let wanted = claims
+        .preferred_username
+        .as_deref()
+        .or(claims.name.as_deref())
+        .or_else(|| claims.email.as_deref().and_then(|email| email.split('@').next()))
+        .unwrap_or_default();
+    let mut base = String::new();
+    for c in wanted.trim().replace('@', "_").chars() {
+        if base.len() + c.len_utf8() > USERNAME_BASE_MAX_LENGTH {
+            break;
+        }
+        base.push(c);
+    }
+    if base.len() < 3 {
+        base = "user".to_owned();
+    }
+
+    let numbered = (2..10).map(|n| format!("{}{}", base, n));
+    let random = format!("{}{}", base, &security::random_token()[..6]);
+    for candidate in std::iter::once(base.clone()).chain(numbered).chain(std::iter::once(random)) {
+        if username::find_issues(&candidate, None, pool).await.is_none() {
+            return Ok(candidate);
+        }
+    }
+    Err(Error::Failure(Failure::Database))
+}
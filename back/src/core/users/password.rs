@@ -11,14 +11,14 @@ pub enum Weakness {
     NoAlphabetic,
 }
 
-pub fn find_weaknesses(password: &String) -> Option<Vec<Weakness>> {
+pub fn find_weaknesses(password: &str) -> Option<Vec<Weakness>> {
     let mut weaknesses = Vec::<Weakness>::new();
 
     if password.len() < 8 {
         weaknesses.push(Weakness::NotLongEnough);
     }
 
-    if !password.chars().any(|c| c.is_digit(10)) {
+    if !password.chars().any(|c| c.is_ascii_digit()) {
         weaknesses.push(Weakness::NoNumeric);
     }
     if !password.chars().any(char::is_alphabetic) {
@@ -35,7 +35,7 @@ pub fn find_weaknesses(password: &String) -> Option<Vec<Weakness>> {
         weaknesses.push(Weakness::NoLowerCase);
     }
 
-    if weaknesses.len() > 0 {
+    if !weaknesses.is_empty() {
         Some(weaknesses)
     } else {
         None
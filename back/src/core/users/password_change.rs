@@ -0,0 +1,70 @@
+use crate::core::{db, security};
+use serde::{Deserialize, Serialize};
+
+use super::{dl, find_by_id, password, session, token::Claims};
+
+#[derive(Deserialize)]
+pub struct Data {
+    // Left out by users without one, see session::is_fresh
+    #[serde(default)]
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Serialize)]
+pub enum Error {
+    Denied(DeniedReasons),
+    Data(DataIssues),
+    Failure(Failure),
+}
+
+#[derive(Serialize)]
+pub enum DeniedReasons {
+    InvalidPassword,
+    // The session was not opened recently enough
+    ReauthenticationRequired,
+}
+
+#[derive(Serialize)]
+pub struct DataIssues {
+    pub password: Vec<password::Weakness>,
+}
+
+#[derive(Serialize)]
+pub enum Failure {
+    Database,
+    PasswordHashing,
+}
+
+impl Data {
+    // Only the session making the change survives it, and its access token
+    // has to be refreshed
+    pub async fn change(&self, claims: &Claims, cache: &session::Cache, pool: &db::DbPool) -> Result<(), Error> {
+        let user = find_by_id(claims.id, pool)
+            .await
+            .map_err(|_| Error::Failure(Failure::Database))?;
+
+        if user.password.is_empty() {
+            // Only their provider lets them in, they just went through it
+            if !session::is_fresh(claims, pool)
+                .await
+                .map_err(|_| Error::Failure(Failure::Database))?
+            {
+                return Err(Error::Denied(DeniedReasons::ReauthenticationRequired));
+            }
+        } else if !security::password_verify(&self.current_password, &user.password) {
+            return Err(Error::Denied(DeniedReasons::InvalidPassword));
+        }
+        if let Some(weaknesses) = password::find_weaknesses(&self.new_password) {
+            return Err(Error::Data(DataIssues { password: weaknesses }));
+        }
+
+        let hashed_password = password::hash(&self.new_password)
+            .map_err(|_| Error::Failure(Failure::PasswordHashing))?;
+        dl::update_password_keeping_session(user.id, &hashed_password, claims.session_id, pool)
+            .await
+            .map_err(|_| Error::Failure(Failure::Database))?;
+        cache.forget_user(user.id);
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+use crate::core::{db, mail, security};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use super::{dl, find_by_email, password, session, Error as UserError};
+
+pub const TOKEN_LIFETIME_MIN: i64 = 30;
+
+#[derive(Deserialize)]
+pub struct RequestData {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetData {
+    pub token: String,
+    pub new_password: String,
+}
+
+#[derive(Serialize)]
+pub enum Error {
+    Denied(DeniedReasons),
+    Data(DataIssues),
+    Failure(Failure),
+}
+
+#[derive(Serialize)]
+pub enum DeniedReasons {
+    // Unknown, already used or expired
+    InvalidResetToken,
+}
+
+#[derive(Serialize)]
+pub struct DataIssues {
+    pub password: Vec<password::Weakness>,
+}
+
+#[derive(Serialize)]
+pub enum Failure {
+    Database,
+    PasswordHashing,
+    Mail,
+}
+
+impl RequestData {
+    // Answers right away, the email being looked up and mailed to in the
+    // background, so that neither the answer nor its timing tells who is
+    // registered. Failures cannot be reported, the requester asks again.
+    pub fn request(&self, mailer: Arc<dyn mail::Mailer>, pool: &db::DbPool) {
+        let email = self.email.clone();
+        let pool = pool.clone();
+        actix_web::rt::spawn(async move {
+            let _ = send_reset_token(&email, mailer.as_ref(), &pool).await;
+        });
+    }
+}
+
+async fn send_reset_token(email: &String, mailer: &dyn mail::Mailer, pool: &db::DbPool) -> Result<(), Failure> {
+    let user = match find_by_email(email, pool).await {
+        Ok(user) => user,
+        Err(UserError::NotFound) => return Ok(()),
+        Err(_) => return Err(Failure::Database),
+    };
+
+    let token = security::random_token();
+    dl::insert_password_reset_token(
+        user.id,
+        &security::token_hash(&token),
+        Utc::now().naive_utc() + Duration::minutes(TOKEN_LIFETIME_MIN),
+        pool,
+    )
+    .await
+    .map_err(|_| Failure::Database)?;
+
+    let lifetime_min = TOKEN_LIFETIME_MIN.to_string();
+    let message = mail::templates::PASSWORD_RESET.render(
+        &user.email,
+        &[
+            ("username", &user.username),
+            ("lifetime_min", &lifetime_min),
+            ("token", &token),
+        ],
+    );
+    mailer
+        .send(message)
+        .await
+        .map_err(|_| Failure::Mail)
+}
+
+impl ResetData {
+    // Every session of the user gets revoked, whoever knew the old password
+    // is logged out
+    pub async fn reset(&self, cache: &session::Cache, pool: &db::DbPool) -> Result<(), Error> {
+        if let Some(weaknesses) = password::find_weaknesses(&self.new_password) {
+            return Err(Error::Data(DataIssues { password: weaknesses }));
+        }
+
+        let hashed_password = password::hash(&self.new_password)
+            .map_err(|_| Error::Failure(Failure::PasswordHashing))?;
+        match dl::reset_password_returning_user_id(&security::token_hash(&self.token), &hashed_password, pool).await {
+            Ok(user_id) => {
+                cache.forget_user(user_id);
+                Ok(())
+            }
+            Err(sqlx::Error::RowNotFound) => Err(Error::Denied(DeniedReasons::InvalidResetToken)),
+            Err(_) => Err(Error::Failure(Failure::Database)),
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use crate::core::db;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+use super::{dl, Error, Role};
+
+// Granted to roles by the role_permissions table, under their snake_case name
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    CreateJoke,
+    // Updating, publishing, archiving and deleting the jokes of others
+    EditAnyJoke,
+    // Drafts and archived jokes of others included
+    ViewAnyJoke,
+    // Seeing, hiding and deleting any comment
+    Moderate,
+    // Seeing private user data, unlocking accounts
+    ManageUsers,
+    ManageRoles,
+    ManageSettings,
+}
+
+#[derive(Serialize)]
+pub struct RoleDescription {
+    pub role: Role,
+    pub permissions: Vec<Permission>,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 7] = [
+        Permission::CreateJoke,
+        Permission::EditAnyJoke,
+        Permission::ViewAnyJoke,
+        Permission::Moderate,
+        Permission::ManageUsers,
+        Permission::ManageRoles,
+        Permission::ManageSettings,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Permission::CreateJoke => "create_joke",
+            Permission::EditAnyJoke => "edit_any_joke",
+            Permission::ViewAnyJoke => "view_any_joke",
+            Permission::Moderate => "moderate",
+            Permission::ManageUsers => "manage_users",
+            Permission::ManageRoles => "manage_roles",
+            Permission::ManageSettings => "manage_settings",
+        }
+    }
+
+    // Names this version does not know of are ignored
+    fn from_names(names: Vec<String>) -> Vec<Permission> {
+        names
+            .iter()
+            .filter_map(|name| Permission::ALL.iter().find(|p| p.name() == name).copied())
+            .collect()
+    }
+}
+
+pub async fn permissions_of(role: Role, pool: &db::DbPool) -> Result<Vec<Permission>, Error> {
+    dl::find_permissions_of_role(role, pool)
+        .await
+        .map(Permission::from_names)
+        .map_err(|_| Error::DataAccessLayerFailure)
+}
+
+pub async fn list_roles(pool: &db::DbPool) -> Result<Vec<RoleDescription>, Error> {
+    let records = dl::find_roles_with_permissions(pool)
+        .await
+        .map_err(|_| Error::DataAccessLayerFailure)?;
+
+    records
+        .into_iter()
+        .map(|record| {
+            Ok(RoleDescription {
+                role: Role::try_from(record.id).map_err(|_| Error::DataAccessLayerFailure)?,
+                permissions: Permission::from_names(record.permissions),
+            })
+        })
+        .collect()
+}
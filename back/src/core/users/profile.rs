@@ -0,0 +1,167 @@
+use crate::core::{db, mail};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::sync::OnceLock;
+
+use super::{dl, email, email_verification, find_by_id, username, User};
+
+pub const DISPLAY_NAME_MAX_LENGTH: usize = 50;
+pub const BIO_MAX_LENGTH: usize = 1000;
+pub const WEBSITE_MAX_LENGTH: usize = 200;
+
+// Missing fields are left as they are, null clears the optional ones
+#[derive(Deserialize)]
+pub struct Update {
+    pub username: Option<String>,
+    pub email: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub display_name: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub bio: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub website: Option<Option<String>>,
+}
+
+#[derive(Serialize)]
+pub enum Error {
+    Data(DataIssues),
+    Failure(Failure),
+}
+
+#[derive(Serialize)]
+pub enum Failure {
+    Database,
+}
+
+#[derive(Serialize)]
+pub enum Issues {
+    Blank,
+    TooLong,
+    Malformed,
+}
+
+#[derive(Serialize, Default)]
+pub struct DataIssues {
+    pub username: Option<Vec<username::Issues>>,
+    pub email: Option<Vec<email::Issues>>,
+    pub display_name: Option<Vec<Issues>>,
+    pub bio: Option<Vec<Issues>>,
+    pub website: Option<Vec<Issues>>,
+}
+
+impl Update {
+    // A new email has to be verified again, a link is mailed to it
+    pub async fn apply(&self, user_id: i32, mailer: &dyn mail::Mailer, pool: &db::DbPool) -> Result<User, Error> {
+        let mut user = find_by_id(user_id, pool)
+            .await
+            .map_err(|_| Error::Failure(Failure::Database))?;
+
+        if let Some(issues) = self.find_issues(user.id, pool).await {
+            return Err(Error::Data(issues));
+        }
+
+        let email_changed = matches!(&self.email, Some(email) if *email != user.email);
+        if let Some(username) = &self.username {
+            user.username = username.clone();
+        }
+        if let Some(email) = &self.email {
+            user.email = email.clone();
+        }
+        if let Some(display_name) = &self.display_name {
+            user.display_name = display_name.as_ref().map(|display_name| display_name.trim().to_owned());
+        }
+        if let Some(bio) = &self.bio {
+            user.bio = bio.clone();
+        }
+        if let Some(website) = &self.website {
+            user.website = website.clone();
+        }
+
+        dl::update_profile(&user, pool)
+            .await
+            .map_err(|_| Error::Failure(Failure::Database))?;
+
+        if email_changed {
+            user.email_verified_at = None;
+            user.email_verification_sent_at = None;
+            // Another link can be asked for if this one does not make it
+            let _ = email_verification::send(&user, mailer, pool).await;
+        }
+        Ok(user)
+    }
+
+    async fn find_issues(&self, user_id: i32, pool: &db::DbPool) -> Option<DataIssues> {
+        let mut issues = DataIssues::default();
+
+        if let Some(username) = &self.username {
+            issues.username = username::find_issues(username, Some(user_id), pool).await;
+        }
+        if let Some(email) = &self.email {
+            issues.email = email::find_issues(email, Some(user_id), pool).await;
+        }
+        if let Some(Some(display_name)) = &self.display_name {
+            issues.display_name = find_display_name_issues(display_name);
+        }
+        if let Some(Some(bio)) = &self.bio {
+            issues.bio = find_bio_issues(bio);
+        }
+        if let Some(Some(website)) = &self.website {
+            issues.website = find_website_issues(website);
+        }
+
+        match issues {
+            DataIssues {
+                username: None,
+                email: None,
+                display_name: None,
+                bio: None,
+                website: None,
+            } => None,
+            issues => Some(issues),
+        }
+    }
+}
+
+fn find_display_name_issues(display_name: &str) -> Option<Vec<Issues>> {
+    let display_name = display_name.trim();
+    if display_name.is_empty() {
+        Some(vec![Issues::Blank])
+    } else if display_name.chars().count() > DISPLAY_NAME_MAX_LENGTH {
+        Some(vec![Issues::TooLong])
+    } else {
+        None
+    }
+}
+
+fn find_bio_issues(bio: &str) -> Option<Vec<Issues>> {
+    if bio.chars().count() > BIO_MAX_LENGTH {
+        Some(vec![Issues::TooLong])
+    } else {
+        None
+    }
+}
+
+static URL_REGEX: OnceLock<Regex> = OnceLock::new();
+
+fn find_website_issues(website: &str) -> Option<Vec<Issues>> {
+    let url_regex = URL_REGEX.get_or_init(|| Regex::new(r"^https?://[^\s/?#]+\.[^\s/?#]+([/?#]\S*)?$").unwrap());
+
+    let mut issues = vec![];
+    if website.len() > WEBSITE_MAX_LENGTH {
+        issues.push(Issues::TooLong);
+    }
+    if !url_regex.is_match(website) {
+        issues.push(Issues::Malformed);
+    }
+
+    if !issues.is_empty() {
+        Some(issues)
+    } else {
+        None
+    }
+}
+
+// Tells a field set to null from a missing one
+fn nullable<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Option<String>>, D::Error> {
+    Option::<String>::deserialize(deserializer).map(Some)
+}
@@ -1,4 +1,4 @@
-use crate::core::db;
+use crate::core::{db, mail};
 use serde::{Deserialize, Serialize};
 
 use super::*;
@@ -30,28 +30,35 @@ pub struct DataIssues {
 }
 
 impl Data {
-    pub async fn register(&self, pool: &db::DbPool) -> Result<i32, Error> {
-        if let Some(issues) = self.find_issues(&pool).await {
-            Err(Error::Data(issues))
-        } else if let Ok(hashed_password) = password::hash(&self.password) {
-            dl::insert_returning_id(
-                &self.username,
-                &self.email,
-                &hashed_password,
-                Role::None,
-                pool,
-            )
-            .await
-            .map_err(|_| Error::Failure(Failures::DatabaseInsertion))
-        } else {
-            Err(Error::Failure(Failures::PasswordHashing))
+    // A verification link is mailed along the way, failing to send it does
+    // not fail the registration since another one can be asked for
+    pub async fn register(&self, mailer: &dyn mail::Mailer, pool: &db::DbPool) -> Result<i32, Error> {
+        if let Some(issues) = self.find_issues(pool).await {
+            return Err(Error::Data(issues));
         }
+
+        let hashed_password = password::hash(&self.password)
+            .map_err(|_| Error::Failure(Failures::PasswordHashing))?;
+        let id = dl::insert_returning_id(
+            &self.username,
+            &self.email,
+            &hashed_password,
+            Role::None,
+            pool,
+        )
+        .await
+        .map_err(|_| Error::Failure(Failures::DatabaseInsertion))?;
+
+        if let Ok(user) = find_by_id(id, pool).await {
+            let _ = email_verification::send(&user, mailer, pool).await;
+        }
+        Ok(id)
     }
 
     async fn find_issues(&self, pool: &db::DbPool) -> Option<DataIssues> {
         match (
-            username::find_issues(&self.username, pool).await,
-            email::find_issues(&self.email, pool).await,
+            username::find_issues(&self.username, None, pool).await,
+            email::find_issues(&self.email, None, pool).await,
             password::find_weaknesses(&self.password),
         ) {
             (None, None, None) => None,
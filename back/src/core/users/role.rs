@@ -1,18 +1,51 @@
 use serde::{Serialize, Deserialize};
+use std::{convert::TryFrom, fmt, str::FromStr};
 
-#[derive(sqlx::Type, Serialize, Deserialize, PartialEq, Clone, Copy)]
+// Discriminants are the ids of the roles table
+#[derive(sqlx::Type, Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
 pub enum Role {
-    Admin,
-    Author,
-    None,
+    Admin = 0,
+    Author = 1,
+    None = 2,
+    Moderator = 3,
 }
 
-impl From<i32> for Role {
-    fn from(i: i32) -> Self {
+#[derive(Debug)]
+pub struct UnknownRole(pub i32);
+
+impl TryFrom<i32> for Role {
+    type Error = UnknownRole;
+
+    fn try_from(i: i32) -> Result<Self, Self::Error> {
         match i {
-            0 => Role::Admin,
-            1 => Role::Author,
-            _ => Role::None
+            0 => Ok(Role::Admin),
+            1 => Ok(Role::Author),
+            2 => Ok(Role::None),
+            3 => Ok(Role::Moderator),
+            _ => Err(UnknownRole(i)),
+        }
+    }
+}
+
+// By name, as in configuration files
+impl FromStr for Role {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "Admin" => Ok(Role::Admin),
+            "Author" => Ok(Role::Author),
+            "None" => Ok(Role::None),
+            "Moderator" => Ok(Role::Moderator),
+            _ => Err(()),
         }
     }
-}
\ No newline at end of file
+}
+
+impl fmt::Display for UnknownRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown role {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownRole {}
@@ -0,0 +1,53 @@
+use crate::core::db;
+use serde::Deserialize;
+
+use super::{dl, token::Claims, Error, Permission};
+
+pub const DEFAULT_LIMIT: i64 = 20;
+pub const MAX_LIMIT: i64 = 100;
+
+#[derive(Deserialize)]
+pub struct Query {
+    // Plain text, matched case-insensitively anywhere in usernames and
+    // tolerant to small typos
+    pub query: String,
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+impl Query {
+    // Pages start at 1, out of range values are clamped rather than rejected
+    pub fn page(&self) -> i64 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+
+    // Exact matches come first, then prefixes, substrings and lookalikes.
+    // Only users searchable by the claims' owner are returned, see
+    // User::is_searchable_by
+    pub async fn search_to_json_as_seen_from(
+        &self,
+        claims: &Option<Claims>,
+        pool: &db::DbPool,
+    ) -> Result<Vec<serde_json::Value>, Error> {
+        let (searcher_id, sees_everyone) = match claims {
+            Some(claims) => (Some(claims.id), claims.can(Permission::ManageUsers)),
+            None => (None, false),
+        };
+        let limit = self.limit();
+        let offset = (self.page() - 1) * limit;
+
+        dl::search_by_username(&self.query, searcher_id, sees_everyone, limit, offset, pool)
+            .await
+            .map(|users| {
+                users
+                    .into_iter()
+                    .map(|user| user.to_json_as_seen_from(claims))
+                    .collect()
+            })
+            .map_err(|_| Error::DataAccessLayerFailure)
+    }
+}
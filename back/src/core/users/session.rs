@@ -0,0 +1,274 @@
+use crate::core::{db, security};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use super::{dl, find_by_id, permissions_of, token, Error as UserError, User};
+
+pub const ACCESS_TOKEN_LIFETIME_SEC: i64 = 15 * 60;
+// Sliding: every refresh pushes the expiration back
+pub const SESSION_LIFETIME_DAYS: i64 = 30;
+// How long a confirmed token version is trusted without asking the database
+pub const CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(5);
+// How recently users without a password must have logged in with their
+// provider to do what others need their password for
+pub const REAUTHENTICATION_MAX_AGE_MIN: i64 = 10;
+const CACHE_CAPACITY: usize = 10_000;
+
+#[derive(Serialize)]
+pub struct Tokens {
+    pub token: String,
+    pub refresh_token: String,
+}
+
+#[derive(Deserialize)]
+pub struct RefreshData {
+    pub refresh_token: String,
+}
+
+#[derive(Serialize)]
+pub enum Error {
+    Denied(DeniedReasons),
+    Failure(Failure),
+}
+
+#[derive(Serialize)]
+pub enum DeniedReasons {
+    InvalidRefreshToken,
+    ExpiredSession,
+    RevokedSession,
+    // The refresh token was already exchanged, it probably leaked so its
+    // session got revoked
+    ReusedRefreshToken,
+}
+
+#[derive(Serialize)]
+pub enum Failure {
+    Database,
+    TokenCreation,
+}
+
+#[derive(PartialEq, Debug)]
+pub enum ClaimsStatus {
+    Valid,
+    RevokedSession,
+    // Issued before the user's role or credentials changed
+    OutdatedToken,
+}
+
+// Recently confirmed token versions of active sessions, along with whether
+// admins need two factors, sparing database round trips on most authenticated
+// requests. Changes made through the same cache are seen at once, changes made
+// elsewhere after at most CACHE_TTL.
+#[derive(Clone, Default)]
+pub struct Cache {
+    confirmations: Arc<Mutex<HashMap<i32, Confirmation>>>,
+    admins_need_two_factor: Arc<Mutex<Option<(bool, Instant)>>>,
+}
+
+struct Confirmation {
+    user_id: i32,
+    token_version: i32,
+    confirmed_at: Instant,
+}
+
+impl Cache {
+    fn confirmed_token_version(&self, claims: &token::Claims) -> Option<i32> {
+        let confirmations = self.confirmations.lock().unwrap();
+        confirmations
+            .get(&claims.session_id)
+            .filter(|confirmation| {
+                confirmation.user_id == claims.id && confirmation.confirmed_at.elapsed() < CACHE_TTL
+            })
+            .map(|confirmation| confirmation.token_version)
+    }
+
+    fn confirm(&self, claims: &token::Claims, token_version: i32) {
+        let mut confirmations = self.confirmations.lock().unwrap();
+        if confirmations.len() >= CACHE_CAPACITY {
+            confirmations.retain(|_, confirmation| confirmation.confirmed_at.elapsed() < CACHE_TTL);
+            if confirmations.len() >= CACHE_CAPACITY {
+                confirmations.clear();
+            }
+        }
+        confirmations.insert(
+            claims.session_id,
+            Confirmation {
+                user_id: claims.id,
+                token_version,
+                confirmed_at: Instant::now(),
+            },
+        );
+    }
+
+    fn forget_session(&self, session_id: i32) {
+        self.confirmations.lock().unwrap().remove(&session_id);
+    }
+
+    pub fn forget_user(&self, user_id: i32) {
+        self.confirmations
+            .lock()
+            .unwrap()
+            .retain(|_, confirmation| confirmation.user_id != user_id);
+    }
+
+    pub fn admins_need_two_factor(&self) -> Option<bool> {
+        self.admins_need_two_factor
+            .lock()
+            .unwrap()
+            .filter(|(_, confirmed_at)| confirmed_at.elapsed() < CACHE_TTL)
+            .map(|(required, _)| required)
+    }
+
+    pub fn confirm_admins_need_two_factor(&self, required: bool) {
+        *self.admins_need_two_factor.lock().unwrap() = Some((required, Instant::now()));
+    }
+}
+
+// Meant to be called once the user proved who they are, two_factor telling
+// whether they did so with a second factor
+pub async fn open(user: &User, two_factor: bool, pool: &db::DbPool) -> Result<Tokens, Failure> {
+    let refresh_token = security::random_token();
+    let session_id = dl::insert_session_returning_id(
+        user.id,
+        &security::token_hash(&refresh_token),
+        Utc::now().naive_utc() + Duration::days(SESSION_LIFETIME_DAYS),
+        two_factor,
+        pool,
+    )
+    .await
+    .map_err(|_| Failure::Database)?;
+
+    Ok(Tokens {
+        token: access_token(user, session_id, two_factor, pool).await?,
+        refresh_token,
+    })
+}
+
+pub async fn revoke(session_id: i32, cache: &Cache, pool: &db::DbPool) -> Result<(), Failure> {
+    dl::revoke_session(session_id, pool)
+        .await
+        .map_err(|_| Failure::Database)?;
+    cache.forget_session(session_id);
+    Ok(())
+}
+
+// Whether the claims of a well signed access token still hold. Only fails
+// when the database cannot tell, unknown sessions being revoked ones.
+pub async fn check(claims: &token::Claims, cache: &Cache, pool: &db::DbPool) -> Result<ClaimsStatus, sqlx::Error> {
+    if cache.confirmed_token_version(claims) == Some(claims.version) {
+        return Ok(ClaimsStatus::Valid);
+    }
+
+    match dl::find_active_session_token_version(claims.session_id, claims.id, pool).await {
+        Ok(Some(token_version)) => {
+            cache.confirm(claims, token_version);
+            if token_version == claims.version {
+                Ok(ClaimsStatus::Valid)
+            } else {
+                Ok(ClaimsStatus::OutdatedToken)
+            }
+        }
+        Ok(None) => {
+            cache.forget_session(claims.session_id);
+            Ok(ClaimsStatus::RevokedSession)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+// Whether the session of the claims was opened lately, refreshing it not
+// counting as logging in again
+pub async fn is_fresh(claims: &token::Claims, pool: &db::DbPool) -> Result<bool, sqlx::Error> {
+    let fresh_since = Utc::now().naive_utc() - Duration::minutes(REAUTHENTICATION_MAX_AGE_MIN);
+    dl::find_active_session_created_at(claims.session_id, claims.id, pool)
+        .await
+        .map(|opened_at| matches!(opened_at, Some(opened_at) if opened_at > fresh_since))
+}
+
+async fn access_token(user: &User, session_id: i32, two_factor: bool, pool: &db::DbPool) -> Result<String, Failure> {
+    let permissions = permissions_of(user.role, pool)
+        .await
+        .map_err(|_| Failure::Database)?;
+    token::from_claims(
+        token::Claims {
+            id: user.id,
+            role: user.role,
+            permissions,
+            session_id,
+            version: user.token_version,
+            two_factor,
+            scopes: None,
+        },
+        ACCESS_TOKEN_LIFETIME_SEC,
+    )
+    .map_err(|_| Failure::TokenCreation)
+}
+
+impl RefreshData {
+    // Refresh tokens are single use, each refresh hands out a new one along
+    // with an access token reflecting the user's current role and its
+    // permissions
+    pub async fn refresh(&self, cache: &Cache, pool: &db::DbPool) -> Result<Tokens, Error> {
+        let refresh_token_hash = security::token_hash(&self.refresh_token);
+
+        let session = match dl::find_session_by_refresh_token_hash(&refresh_token_hash, pool).await {
+            Ok(session) => session,
+            Err(sqlx::Error::RowNotFound) => {
+                return Err(self.deny_unknown(&refresh_token_hash, cache, pool).await)
+            }
+            Err(_) => return Err(Error::Failure(Failure::Database)),
+        };
+
+        if session.revoked_at.is_some() {
+            return Err(Error::Denied(DeniedReasons::RevokedSession));
+        }
+        if session.expires_at <= Utc::now().naive_utc() {
+            return Err(Error::Denied(DeniedReasons::ExpiredSession));
+        }
+
+        // Deleted since the session was found
+        let user = match find_by_id(session.user_id, pool).await {
+            Ok(user) => user,
+            Err(UserError::NotFound) => return Err(Error::Denied(DeniedReasons::InvalidRefreshToken)),
+            Err(_) => return Err(Error::Failure(Failure::Database)),
+        };
+
+        let refresh_token = security::random_token();
+        match dl::rotate_refresh_token(
+            session.id,
+            &refresh_token_hash,
+            &security::token_hash(&refresh_token),
+            Utc::now().naive_utc() + Duration::days(SESSION_LIFETIME_DAYS),
+            pool,
+        )
+        .await
+        {
+            Ok(_) => (),
+            Err(sqlx::Error::RowNotFound) => return Err(Error::Denied(DeniedReasons::InvalidRefreshToken)),
+            Err(_) => return Err(Error::Failure(Failure::Database)),
+        };
+
+        Ok(Tokens {
+            token: access_token(&user, session.id, session.two_factor, pool)
+                .await
+                .map_err(Error::Failure)?,
+            refresh_token,
+        })
+    }
+
+    async fn deny_unknown(&self, refresh_token_hash: &str, cache: &Cache, pool: &db::DbPool) -> Error {
+        match dl::find_session_id_by_retired_refresh_token_hash(refresh_token_hash, pool).await {
+            Ok(session_id) => match revoke(session_id, cache, pool).await {
+                Ok(_) => Error::Denied(DeniedReasons::ReusedRefreshToken),
+                Err(failure) => Error::Failure(failure),
+            },
+            Err(sqlx::Error::RowNotFound) => Error::Denied(DeniedReasons::InvalidRefreshToken),
+            Err(_) => Error::Failure(Failure::Database),
+        }
+    }
+}
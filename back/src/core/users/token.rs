@@ -1,26 +1,67 @@
 use serde::{Serialize, Deserialize};
 use crate::core::security;
-use super::Role;
+use super::{api_key::Scope, Permission, Role};
 
+#[derive(Clone)]
 pub struct Claims {
     pub id: i32,
-    pub role: Role
+    pub role: Role,
+    // Those of the role when the token was issued
+    pub permissions: Vec<Permission>,
+    // Tokens die with the session they were issued for, see session::revoke.
+    // 0 for API keys, which have none.
+    pub session_id: i32,
+    // Must match the user's current token version, see User::outdate_tokens
+    pub version: i32,
+    // Whether the session was opened with a second factor, see two_factor
+    pub two_factor: bool,
+    // Only set for API keys, limited to the routes of their scopes
+    pub scopes: Option<Vec<Scope>>,
 }
 
 #[derive(Serialize, Deserialize)]
 struct JwtClaims {
     id: i32,
     role: Role,
+    prm: Vec<Permission>,
+    sid: i32,
+    ver: i32,
+    tfa: bool,
     exp: usize,
 }
 
-pub fn from_claims(claims: Claims, expiration_sec: i64) -> Result<String, ()> {
-    security::jwt_create(&mut JwtClaims { id: claims.id, role: claims.role, exp: 0 }, expiration_sec)
+pub fn from_claims(claims: Claims, expiration_sec: i64) -> Result<String, security::JwtError> {
+    security::jwt_create(
+        &mut JwtClaims {
+            id: claims.id,
+            role: claims.role,
+            prm: claims.permissions,
+            sid: claims.session_id,
+            ver: claims.version,
+            tfa: claims.two_factor,
+            exp: 0
+        },
+        expiration_sec
+    )
 }
 
-pub fn to_claims(token: &String) -> Result<Claims, ()> {
+pub fn to_claims(token: &str) -> Result<Claims, security::JwtError> {
     security::jwt_decode::<JwtClaims>(token)
-        .map(|claims| Claims { id: claims.id, role: claims.role })
+        .map(|claims| Claims {
+            id: claims.id,
+            role: claims.role,
+            permissions: claims.prm,
+            session_id: claims.sid,
+            version: claims.ver,
+            two_factor: claims.tfa,
+            scopes: None,
+        })
+}
+
+impl Claims {
+    pub fn can(&self, permission: Permission) -> bool {
+        self.permissions.contains(&permission)
+    }
 }
 
 impl security::JwtClaims for JwtClaims {
@@ -0,0 +1,357 @@
+use crate::core::{db, security, settings};
+use chrono::Utc;
+use data_encoding::BASE32_NOPAD;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+use super::{dl, find_by_id, lockout, session, token::Claims, Role, User};
+
+pub const PENDING_LOGIN_LIFETIME_SEC: i64 = 5 * 60;
+pub const RECOVERY_CODE_COUNT: usize = 10;
+const SECRET_LENGTH: usize = 20;
+const ISSUER: &str = "Camion";
+// Codes of the steps around the current one are accepted, clocks drift
+const ALLOWED_STEP_DRIFT: i64 = 1;
+const PENDING_LOGIN_PURPOSE: &str = "two_factor_login";
+
+// Proves the password was right, nothing more
+#[derive(Serialize, Deserialize)]
+struct PendingLoginClaims {
+    uid: i32,
+    // Counts the wrong codes given with this very token
+    jti: String,
+    pur: String,
+    exp: usize,
+}
+
+#[derive(Serialize)]
+pub struct PendingLogin {
+    pub two_factor_token: String,
+}
+
+#[derive(Serialize)]
+pub struct Enrolment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+// Shown once, only their hashes are kept
+#[derive(Serialize)]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmData {
+    pub code: String,
+}
+
+#[derive(Deserialize)]
+pub struct LoginData {
+    pub two_factor_token: String,
+    // Either a TOTP code or a recovery code
+    pub code: String,
+}
+
+#[derive(Deserialize)]
+pub struct DisableData {
+    pub password: String,
+    // Either a TOTP code or a recovery code
+    pub code: String,
+}
+
+#[derive(Serialize)]
+pub enum Error {
+    Denied(DeniedReasons),
+    Failure(Failure),
+}
+
+#[derive(Serialize)]
+pub enum DeniedReasons {
+    InvalidTwoFactorToken,
+    InvalidCode,
+    InvalidPassword,
+    // Too many wrong codes lately, from the account or the address
+    Locked { retry_after_sec: i64 },
+    AlreadyEnabled,
+    NotEnrolling,
+    NotEnabled,
+    // Admins cannot go without it, see settings
+    MandatoryForRole,
+}
+
+#[derive(Serialize)]
+pub enum Failure {
+    Database,
+    TokenCreation,
+    PasswordHashing,
+}
+
+impl From<session::Failure> for Failure {
+    fn from(failure: session::Failure) -> Self {
+        match failure {
+            session::Failure::Database => Failure::Database,
+            session::Failure::TokenCreation => Failure::TokenCreation,
+        }
+    }
+}
+
+// Whether users of that role must use two factor authentication
+pub async fn is_mandatory_for(role: Role, pool: &db::DbPool) -> Result<bool, settings::Error> {
+    if role != Role::Admin {
+        return Ok(false);
+    }
+    settings::get(pool)
+        .await
+        .map(|settings| settings.admins_need_two_factor)
+}
+
+// Same, trusting the setting as cached with the sessions, for every request
+pub async fn is_mandatory_for_cached(role: Role, cache: &session::Cache, pool: &db::DbPool) -> Result<bool, settings::Error> {
+    if role != Role::Admin {
+        return Ok(false);
+    }
+    if let Some(required) = cache.admins_need_two_factor() {
+        return Ok(required);
+    }
+    let required = is_mandatory_for(role, pool).await?;
+    cache.confirm_admins_need_two_factor(required);
+    Ok(required)
+}
+
+// Handed out by login::Data::login in place of the session tokens
+pub(super) fn pending_login(user: &User) -> Result<PendingLogin, Failure> {
+    security::jwt_create(
+        &mut PendingLoginClaims {
+            uid: user.id,
+            jti: security::random_token(),
+            pur: PENDING_LOGIN_PURPOSE.to_owned(),
+            exp: 0,
+        },
+        PENDING_LOGIN_LIFETIME_SEC,
+    )
+    .map(|two_factor_token| PendingLogin { two_factor_token })
+    .map_err(|_| Failure::TokenCreation)
+}
+
+// Two factor authentication is only enabled once a first code got confirmed
+pub async fn enrol(user_id: i32, pool: &db::DbPool) -> Result<Enrolment, Error> {
+    let user = find_by_id(user_id, pool)
+        .await
+        .map_err(|_| Error::Failure(Failure::Database))?;
+
+    let secret = BASE32_NOPAD.encode(&security::random_bytes(SECRET_LENGTH));
+    match dl::set_totp_secret(user.id, &secret, pool).await {
+        Ok(_) => (),
+        Err(sqlx::Error::RowNotFound) => return Err(Error::Denied(DeniedReasons::AlreadyEnabled)),
+        Err(_) => return Err(Error::Failure(Failure::Database)),
+    };
+
+    Ok(Enrolment {
+        otpauth_uri: format!(
+            "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={digits}&period={period}",
+            issuer = ISSUER,
+            account = percent_encode(&user.username),
+            secret = secret,
+            digits = security::TOTP_DIGITS,
+            period = security::TOTP_STEP_SEC
+        ),
+        secret,
+    })
+}
+
+impl ConfirmData {
+    // The session confirming counts as opened with two factors from then on
+    pub async fn confirm(&self, claims: &Claims, pool: &db::DbPool) -> Result<RecoveryCodes, Error> {
+        let user = find_by_id(claims.id, pool)
+            .await
+            .map_err(|_| Error::Failure(Failure::Database))?;
+
+        let secret = match (&user.totp_secret, user.totp_enabled_at) {
+            (_, Some(_)) => return Err(Error::Denied(DeniedReasons::AlreadyEnabled)),
+            (None, None) => return Err(Error::Denied(DeniedReasons::NotEnrolling)),
+            (Some(secret), None) => secret,
+        };
+        let step = match matching_totp_step(secret, &self.code) {
+            Some(step) => step,
+            None => return Err(Error::Denied(DeniedReasons::InvalidCode)),
+        };
+
+        let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT).map(|_| recovery_code()).collect();
+        let mut recovery_code_hashes = Vec::with_capacity(RECOVERY_CODE_COUNT);
+        for code in &recovery_codes {
+            recovery_code_hashes.push(
+                security::password_salt_and_hash(&normalize_recovery_code(code))
+                    .map_err(|_| Error::Failure(Failure::PasswordHashing))?,
+            );
+        }
+
+        match dl::enable_totp(user.id, step, &recovery_code_hashes, claims.session_id, pool).await {
+            Ok(_) => Ok(RecoveryCodes { recovery_codes }),
+            Err(sqlx::Error::RowNotFound) => Err(Error::Denied(DeniedReasons::NotEnrolling)),
+            Err(_) => Err(Error::Failure(Failure::Database)),
+        }
+    }
+}
+
+impl LoginData {
+    // Wrong codes count as failed logins, see lockout, and a token taking
+    // too many of them cannot be used anymore
+    pub async fn login(&self, ip: Option<IpAddr>, pool: &db::DbPool) -> Result<session::Tokens, Error> {
+        let claims = security::jwt_decode::<PendingLoginClaims>(&self.two_factor_token)
+            .ok()
+            .filter(|claims| claims.pur == PENDING_LOGIN_PURPOSE)
+            .ok_or(Error::Denied(DeniedReasons::InvalidTwoFactorToken))?;
+
+        let token_subject = [lockout::Subject::PendingLogin(&claims.jti)];
+        if lockout::retry_after_sec(&token_subject, pool)
+            .await
+            .map_err(|_| Error::Failure(Failure::Database))?
+            .is_some()
+        {
+            return Err(Error::Denied(DeniedReasons::InvalidTwoFactorToken));
+        }
+
+        let user = find_by_id(claims.uid, pool).await.map_err(|error| match error {
+            super::Error::NotFound => Error::Denied(DeniedReasons::InvalidTwoFactorToken),
+            _ => Error::Failure(Failure::Database),
+        })?;
+
+        let [token_subject] = token_subject;
+        let mut subjects = vec![lockout::Subject::User(user.id)];
+        subjects.extend(ip.map(lockout::Subject::Ip));
+        if let Some(retry_after_sec) = lockout::retry_after_sec(&subjects, pool)
+            .await
+            .map_err(|_| Error::Failure(Failure::Database))?
+        {
+            return Err(Error::Denied(DeniedReasons::Locked { retry_after_sec }));
+        }
+
+        if !verify_code(&user, &self.code, pool).await? {
+            subjects.push(token_subject);
+            lockout::record_failure(&subjects, pool)
+                .await
+                .map_err(|_| Error::Failure(Failure::Database))?;
+            return Err(Error::Denied(DeniedReasons::InvalidCode));
+        }
+        lockout::forget(&subjects[0], pool)
+            .await
+            .map_err(|_| Error::Failure(Failure::Database))?;
+
+        session::open(&user, true, pool)
+            .await
+            .map_err(|failure| Error::Failure(failure.into()))
+    }
+}
+
+impl DisableData {
+    pub async fn disable(&self, claims: &Claims, pool: &db::DbPool) -> Result<(), Error> {
+        let user = find_by_id(claims.id, pool)
+            .await
+            .map_err(|_| Error::Failure(Failure::Database))?;
+
+        if !security::password_verify(&self.password, &user.password) {
+            return Err(Error::Denied(DeniedReasons::InvalidPassword));
+        }
+        if user.totp_enabled_at.is_none() {
+            return Err(Error::Denied(DeniedReasons::NotEnabled));
+        }
+        if is_mandatory_for(user.role, pool)
+            .await
+            .map_err(|_| Error::Failure(Failure::Database))?
+        {
+            return Err(Error::Denied(DeniedReasons::MandatoryForRole));
+        }
+
+        // The password alone, from a stolen session, does not take the second
+        // factor away. Wrong codes count as failed logins here too.
+        let subjects = [lockout::Subject::User(user.id)];
+        if let Some(retry_after_sec) = lockout::retry_after_sec(&subjects, pool)
+            .await
+            .map_err(|_| Error::Failure(Failure::Database))?
+        {
+            return Err(Error::Denied(DeniedReasons::Locked { retry_after_sec }));
+        }
+        if !verify_code(&user, &self.code, pool).await? {
+            lockout::record_failure(&subjects, pool)
+                .await
+                .map_err(|_| Error::Failure(Failure::Database))?;
+            return Err(Error::Denied(DeniedReasons::InvalidCode));
+        }
+
+        dl::disable_totp(user.id, pool)
+            .await
+            .map_err(|_| Error::Failure(Failure::Database))
+    }
+}
+
+// Either a TOTP code or one of the recovery codes, each usable once
+async fn verify_code(user: &User, code: &str, pool: &db::DbPool) -> Result<bool, Error> {
+    let secret = match (&user.totp_secret, user.totp_enabled_at) {
+        (Some(secret), Some(_)) => secret,
+        _ => return Ok(false),
+    };
+
+    if let Some(step) = matching_totp_step(secret, code) {
+        return dl::use_totp_step(user.id, step, pool)
+            .await
+            .map_err(|_| Error::Failure(Failure::Database));
+    }
+
+    let code = normalize_recovery_code(code);
+    let recovery_codes = dl::find_unused_recovery_codes(user.id, pool)
+        .await
+        .map_err(|_| Error::Failure(Failure::Database))?;
+    for recovery_code in recovery_codes {
+        if security::password_verify(&code, &recovery_code.code_hash) {
+            return dl::use_recovery_code(recovery_code.id, pool)
+                .await
+                .map_err(|_| Error::Failure(Failure::Database));
+        }
+    }
+    Ok(false)
+}
+
+fn matching_totp_step(secret: &str, code: &str) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != security::TOTP_DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+    let secret = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+
+    let current_step = security::totp_step(Utc::now().timestamp() as u64) as i64;
+    (current_step - ALLOWED_STEP_DRIFT..=current_step + ALLOWED_STEP_DRIFT)
+        .find(|step| security::totp(&secret, *step as u64) == code)
+}
+
+// Looks like "abcde-fghij"
+fn recovery_code() -> String {
+    let code = BASE32_NOPAD
+        .encode(&security::random_bytes(8))
+        .to_lowercase();
+    format!("{}-{}", &code[0..5], &code[5..10])
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect::<String>()
+        .to_lowercase()
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+impl security::JwtClaims for PendingLoginClaims {
+    fn set_expiration(&mut self, exp: usize) {
+        self.exp = exp;
+    }
+}
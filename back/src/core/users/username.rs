@@ -13,7 +13,8 @@ pub enum Issues {
     NotUnique,
 }
 
-pub async fn find_issues(username: &String, pool: &db::DbPool) -> Option<Vec<Issues>> {
+// The user of id except_user_id, if any, is not taken into account for uniqueness
+pub async fn find_issues(username: &String, except_user_id: Option<i32>, pool: &db::DbPool) -> Option<Vec<Issues>> {
     let mut issues = vec![];
 
     if string_is_email(username) {
@@ -25,7 +26,7 @@ pub async fn find_issues(username: &String, pool: &db::DbPool) -> Option<Vec<Iss
     } else if username.len() > 32 {
         issues.push(Issues::TooLong);
     } else {
-        match username_is_unique(username, pool).await {
+        match username_is_unique(username, except_user_id, pool).await {
             Ok(false) => issues.push(Issues::NotUnique),
             Err(_) => issues.push(Issues::CouldNotBeProcessed),
             _ => (),
@@ -39,6 +40,6 @@ pub async fn find_issues(username: &String, pool: &db::DbPool) -> Option<Vec<Iss
     }
 }
 
-async fn username_is_unique(username: &String, pool: &db::DbPool) -> Result<bool, sqlx::Error> {
-    db::check_field_is_unique!("users", "username", username, pool)
+async fn username_is_unique(username: &String, except_user_id: Option<i32>, pool: &db::DbPool) -> Result<bool, sqlx::Error> {
+    db::check_field_is_unique!("users", "username", username, except_id: except_user_id, pool)
 }
\ No newline at end of file
@@ -0,0 +1,58 @@
+use crate::core::users::api_key;
+use actix_web::{delete, get, http::StatusCode, post, web, HttpResponse};
+use serde_json::json;
+
+use super::{users::extractors::AuthUser, ApiState};
+
+// The key is in the response only this once
+#[post("/users/me/api-keys")]
+async fn create_api_key(
+    AuthUser(claims): AuthUser,
+    api_state: web::Data<ApiState>,
+    create_data: web::Json<api_key::CreateData>,
+) -> HttpResponse {
+    let (status, body) = match create_data.create(&claims, &api_state.db_conn_pool).await {
+        Ok(api_key) => (
+            StatusCode::OK,
+            json!({ "success": true, "created_api_key": api_key }),
+        ),
+        Err(api_key::Error::Data(issues)) => (
+            StatusCode::OK,
+            json!({ "success": false, "issues": issues }),
+        ),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[get("/users/me/api-keys")]
+async fn list_api_keys(AuthUser(claims): AuthUser, api_state: web::Data<ApiState>) -> HttpResponse {
+    let (status, body) = match api_key::list(claims.id, &api_state.db_conn_pool).await {
+        Ok(api_keys) => (StatusCode::OK, json!({ "api_keys": api_keys })),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[delete("/users/me/api-keys/{id}")]
+async fn revoke_api_key(
+    AuthUser(claims): AuthUser,
+    api_state: web::Data<ApiState>,
+    path: web::Path<(i32,)>,
+) -> HttpResponse {
+    let (status, body) = match api_key::revoke(claims.id, path.0, &api_state.db_conn_pool).await {
+        Ok(_) => (StatusCode::OK, json!({})),
+        Err(api_key::Error::NotFound) => (StatusCode::NOT_FOUND, json!({})),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
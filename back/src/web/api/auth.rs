@@ -1,15 +1,16 @@
-use crate::core::{users};
-use actix_web::{http::StatusCode, post, web, HttpResponse};
+use crate::core::{users::{self, session}};
+use crate::web::rate_limit::RateLimit;
+use actix_web::{delete, get, http::StatusCode, post, web, HttpRequest, HttpResponse};
 use serde_json::json;
 
-use super::{ApiState};
+use super::{users::extractors::{AuthUser, AuthUserWithoutTwoFactor, OptionalAuthUser}, ApiState};
 
-#[post("/auth/register")]
+#[post("/auth/register", wrap = "RateLimit::Registration")]
 async fn register(
     api_state: web::Data<ApiState>,
     register_data: web::Json<users::registration::Data>,
 ) -> HttpResponse {
-    let (status, body) = match register_data.register(&&api_state.db_conn_pool).await {
+    let (status, body) = match register_data.register(api_state.mailer.as_ref(), &api_state.db_conn_pool).await {
         Ok(id) => (
             StatusCode::OK,
             json!({
@@ -34,13 +35,25 @@ async fn register(
         .body(body.to_string())
 }
 
-#[post("/auth/login")]
+#[post("/auth/login", wrap = "RateLimit::Login")]
 async fn login(
+    req: HttpRequest,
     api_state: web::Data<ApiState>,
     login_data: web::Json<users::login::Data>,
 ) -> HttpResponse {
-    let (status, body) = match login_data.login(&&api_state.db_conn_pool).await {
-        Ok(jwt) => (StatusCode::OK, json!({ "token": jwt })),
+    let ip = api_state.trusted_proxies.client_ip(&req);
+    let (status, body) = match login_data.login(ip, &api_state.db_conn_pool).await {
+        Ok(users::login::Outcome::LoggedIn(tokens)) => (StatusCode::OK, json!(tokens)),
+        Ok(users::login::Outcome::TwoFactorPending(pending)) => (
+            StatusCode::OK,
+            json!({
+                "two_factor_required": true,
+                "two_factor_token": pending.two_factor_token
+            }),
+        ),
+        Err(users::login::Error::Denied(reason @ users::login::DeniedReasons::Locked { retry_after_sec })) => {
+            return locked_response(retry_after_sec, json!({ "reason": reason }));
+        },
         Err(users::login::Error::Denied(reason)) => {
             (StatusCode::UNAUTHORIZED, json!({ "reason": reason }))
         },
@@ -52,4 +65,334 @@ async fn login(
     HttpResponse::build(status)
         .content_type("application/json")
         .body(body.to_string())
-}
\ No newline at end of file
+}
+
+#[post("/auth/refresh")]
+async fn refresh(
+    api_state: web::Data<ApiState>,
+    refresh_data: web::Json<session::RefreshData>,
+) -> HttpResponse {
+    let (status, body) = match refresh_data.refresh(&api_state.session_cache, &api_state.db_conn_pool).await {
+        Ok(tokens) => (StatusCode::OK, json!(tokens)),
+        Err(session::Error::Denied(reason)) => {
+            (StatusCode::UNAUTHORIZED, json!({ "reason": reason }))
+        },
+        Err(session::Error::Failure(failure)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            json!({ "error": failure }),
+        ),
+    };
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+// Revokes the session of the access token used, along with its refresh token
+#[post("/auth/logout")]
+async fn logout(AuthUserWithoutTwoFactor(claims): AuthUserWithoutTwoFactor, api_state: web::Data<ApiState>) -> HttpResponse {
+    let (status, body) = match session::revoke(claims.session_id, &api_state.session_cache, &api_state.db_conn_pool).await {
+        Ok(_) => (StatusCode::OK, json!({})),
+        Err(failure) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            json!({ "error": failure }),
+        ),
+    };
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[post("/auth/password")]
+async fn change_password(
+    AuthUser(claims): AuthUser,
+    api_state: web::Data<ApiState>,
+    change_data: web::Json<users::password_change::Data>,
+) -> HttpResponse {
+    let (status, body) = match change_data.change(&claims, &api_state.session_cache, &api_state.db_conn_pool).await {
+        Ok(_) => (StatusCode::OK, json!({ "success": true })),
+        Err(users::password_change::Error::Denied(reason)) => {
+            (StatusCode::UNAUTHORIZED, json!({ "reason": reason }))
+        },
+        Err(users::password_change::Error::Data(issues)) => (
+            StatusCode::OK,
+            json!({
+                "success": false,
+                "issues": issues
+            }),
+        ),
+        Err(users::password_change::Error::Failure(failure)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            json!({ "error": failure }),
+        ),
+    };
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+// Answers the same whether the email is known or not
+#[post("/auth/password-reset", wrap = "RateLimit::Login")]
+async fn request_password_reset(
+    api_state: web::Data<ApiState>,
+    request_data: web::Json<users::password_reset::RequestData>,
+) -> HttpResponse {
+    request_data.request(api_state.mailer.clone(), &api_state.db_conn_pool);
+    HttpResponse::build(StatusCode::OK)
+        .content_type("application/json")
+        .body(json!({}).to_string())
+}
+
+#[post("/auth/password-reset/confirm")]
+async fn reset_password(
+    api_state: web::Data<ApiState>,
+    reset_data: web::Json<users::password_reset::ResetData>,
+) -> HttpResponse {
+    let (status, body) = match reset_data.reset(&api_state.session_cache, &api_state.db_conn_pool).await {
+        Ok(_) => (StatusCode::OK, json!({ "success": true })),
+        Err(users::password_reset::Error::Denied(reason)) => {
+            (StatusCode::UNAUTHORIZED, json!({ "reason": reason }))
+        },
+        Err(users::password_reset::Error::Data(issues)) => (
+            StatusCode::OK,
+            json!({
+                "success": false,
+                "issues": issues
+            }),
+        ),
+        Err(users::password_reset::Error::Failure(failure)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            json!({ "error": failure }),
+        ),
+    };
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[get("/auth/email-verification/confirm")]
+async fn confirm_email(
+    api_state: web::Data<ApiState>,
+    confirm_data: web::Query<users::email_verification::ConfirmData>,
+) -> HttpResponse {
+    let (status, body) = match confirm_data.confirm(&api_state.db_conn_pool).await {
+        Ok(_) => (StatusCode::OK, json!({ "verified": true })),
+        Err(error) => return email_verification_error_response(error),
+    };
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[post("/auth/email-verification")]
+async fn resend_email_verification(AuthUser(claims): AuthUser, api_state: web::Data<ApiState>) -> HttpResponse {
+    let (status, body) = match users::email_verification::resend(claims.id, api_state.mailer.as_ref(), &api_state.db_conn_pool).await {
+        Ok(_) => (StatusCode::OK, json!({})),
+        Err(error) => return email_verification_error_response(error),
+    };
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+fn email_verification_error_response(error: users::email_verification::Error) -> HttpResponse {
+    use users::email_verification::{DeniedReasons, Error};
+
+    let (status, body) = match error {
+        Error::Denied(reason @ DeniedReasons::InvalidVerificationToken) => {
+            (StatusCode::UNAUTHORIZED, json!({ "reason": reason }))
+        },
+        Error::Denied(reason @ DeniedReasons::AlreadyVerified) => {
+            (StatusCode::CONFLICT, json!({ "reason": reason }))
+        },
+        Error::Throttled { retry_after_sec } => {
+            return HttpResponse::build(StatusCode::TOO_MANY_REQUESTS)
+                .insert_header(("Retry-After", retry_after_sec.to_string()))
+                .content_type("application/json")
+                .body(json!({ "retry_after": retry_after_sec }).to_string());
+        },
+        Error::Failure(failure) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            json!({ "error": failure }),
+        ),
+    };
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+// Second step of the login of users with two factor authentication enabled
+#[post("/auth/login/two-factor", wrap = "RateLimit::Login")]
+async fn login_two_factor(
+    req: HttpRequest,
+    api_state: web::Data<ApiState>,
+    login_data: web::Json<users::two_factor::LoginData>,
+) -> HttpResponse {
+    let ip = api_state.trusted_proxies.client_ip(&req);
+    let (status, body) = match login_data.login(ip, &api_state.db_conn_pool).await {
+        Ok(tokens) => (StatusCode::OK, json!(tokens)),
+        Err(error) => return two_factor_error_response(error),
+    };
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[post("/auth/two-factor/enrol")]
+async fn enrol_two_factor(AuthUserWithoutTwoFactor(claims): AuthUserWithoutTwoFactor, api_state: web::Data<ApiState>) -> HttpResponse {
+    let (status, body) = match users::two_factor::enrol(claims.id, &api_state.db_conn_pool).await {
+        Ok(enrolment) => (StatusCode::OK, json!(enrolment)),
+        Err(error) => return two_factor_error_response(error),
+    };
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[post("/auth/two-factor/confirm")]
+async fn confirm_two_factor(
+    AuthUserWithoutTwoFactor(claims): AuthUserWithoutTwoFactor,
+    api_state: web::Data<ApiState>,
+    confirm_data: web::Json<users::two_factor::ConfirmData>,
+) -> HttpResponse {
+    let (status, body) = match confirm_data.confirm(&claims, &api_state.db_conn_pool).await {
+        Ok(recovery_codes) => (StatusCode::OK, json!(recovery_codes)),
+        Err(error) => return two_factor_error_response(error),
+    };
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[delete("/auth/two-factor")]
+async fn disable_two_factor(
+    AuthUser(claims): AuthUser,
+    api_state: web::Data<ApiState>,
+    disable_data: web::Json<users::two_factor::DisableData>,
+) -> HttpResponse {
+    let (status, body) = match disable_data.disable(&claims, &api_state.db_conn_pool).await {
+        Ok(_) => (StatusCode::OK, json!({})),
+        Err(error) => return two_factor_error_response(error),
+    };
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+fn two_factor_error_response(error: users::two_factor::Error) -> HttpResponse {
+    use users::two_factor::{DeniedReasons, Error};
+
+    let (status, body) = match error {
+        Error::Denied(reason @ DeniedReasons::Locked { retry_after_sec }) => {
+            return locked_response(retry_after_sec, json!({ "reason": reason }));
+        },
+        Error::Denied(
+            reason @ (DeniedReasons::InvalidTwoFactorToken
+            | DeniedReasons::InvalidCode
+            | DeniedReasons::InvalidPassword),
+        ) => (StatusCode::UNAUTHORIZED, json!({ "reason": reason })),
+        Error::Denied(reason) => (StatusCode::CONFLICT, json!({ "reason": reason })),
+        Error::Failure(failure) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            json!({ "error": failure }),
+        ),
+    };
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[get("/auth/oidc/providers")]
+async fn list_oidc_providers(api_state: web::Data<ApiState>) -> HttpResponse {
+    let body = json!({ "providers": api_state.oidc_providers.names() });
+    HttpResponse::build(StatusCode::OK)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+// Where to send the user to log in with the provider. Logged in users are sent
+// there to link their identity at the provider to their account instead.
+#[post("/auth/oidc/{provider}/authorize", wrap = "RateLimit::Login")]
+async fn authorize_oidc(
+    OptionalAuthUser(claims): OptionalAuthUser,
+    api_state: web::Data<ApiState>,
+    provider: web::Path<String>,
+) -> HttpResponse {
+    let linking_user_id = claims.map(|claims| claims.id);
+    let (status, body) = match users::oidc_login::start(
+        &api_state.oidc_providers,
+        &provider,
+        linking_user_id,
+        &api_state.db_conn_pool,
+    )
+    .await
+    {
+        Ok(authorization) => (StatusCode::OK, json!(authorization)),
+        Err(error) => return oidc_error_response(error),
+    };
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+// Answers like a login, whether the identity got linked, provisioned or was
+// known already. Linking ones are finished by the user who started them.
+#[post("/auth/oidc/{provider}/callback", wrap = "RateLimit::Login")]
+async fn oidc_callback(
+    OptionalAuthUser(claims): OptionalAuthUser,
+    api_state: web::Data<ApiState>,
+    provider: web::Path<String>,
+    callback_data: web::Json<users::oidc_login::CallbackData>,
+) -> HttpResponse {
+    let (status, body) = match callback_data
+        .finish(&api_state.oidc_providers, &provider, claims.map(|claims| claims.id), &api_state.db_conn_pool)
+        .await
+    {
+        Ok(users::login::Outcome::LoggedIn(tokens)) => (StatusCode::OK, json!(tokens)),
+        Ok(users::login::Outcome::TwoFactorPending(pending)) => (
+            StatusCode::OK,
+            json!({
+                "two_factor_required": true,
+                "two_factor_token": pending.two_factor_token
+            }),
+        ),
+        Err(error) => return oidc_error_response(error),
+    };
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+fn oidc_error_response(error: users::oidc_login::Error) -> HttpResponse {
+    use users::oidc_login::{DeniedReasons, Error, Failure};
+
+    let (status, body) = match error {
+        Error::Denied(reason @ DeniedReasons::UnknownProvider) => {
+            (StatusCode::NOT_FOUND, json!({ "reason": reason }))
+        },
+        Error::Denied(reason @ (DeniedReasons::IdentityTaken | DeniedReasons::EmailTaken)) => {
+            (StatusCode::CONFLICT, json!({ "reason": reason }))
+        },
+        Error::Denied(reason @ DeniedReasons::NotTheLinkingUser) => {
+            (StatusCode::FORBIDDEN, json!({ "reason": reason }))
+        },
+        Error::Denied(reason) => (StatusCode::UNAUTHORIZED, json!({ "reason": reason })),
+        Error::Failure(failure @ Failure::Provider) => (
+            StatusCode::BAD_GATEWAY,
+            json!({ "error": failure }),
+        ),
+        Error::Failure(failure) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            json!({ "error": failure }),
+        ),
+    };
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+fn locked_response(retry_after_sec: i64, body: serde_json::Value) -> HttpResponse {
+    HttpResponse::build(StatusCode::TOO_MANY_REQUESTS)
+        .insert_header(("Retry-After", retry_after_sec.to_string()))
+        .content_type("application/json")
+        .body(body.to_string())
+}
@@ -0,0 +1,219 @@
+use super::super::users::{
+    extractors::{AuthUser, OptionalAuthUser},
+    utils_auth::{enforce_id, enforce_id_or_permission},
+};
+use super::find_joke_visible_by_requester;
+use crate::core::{
+    comments::{self, listing, Comment, CommentTemplate},
+    db,
+    jokes::Joke,
+    users::{api_key::Scope, token, Permission},
+};
+use actix_web::{delete, get, http::StatusCode, post, put, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::super::ApiState;
+
+#[get("/jokes/{id}/comments", wrap = "Scope::Read")]
+async fn list_comments(
+    OptionalAuthUser(claims): OptionalAuthUser,
+    api_state: web::Data<ApiState>,
+    query: web::Query<listing::Query>,
+    path: web::Path<(i32,)>,
+) -> HttpResponse {
+    let joke = match find_joke_visible_by_requester(&claims, path.0, &api_state.db_conn_pool).await {
+        Ok(joke) => joke,
+        Err(response) => return response,
+    };
+
+    let (status, body) = match query.list(&joke, &claims, &api_state.db_conn_pool).await {
+        Ok(comments) => (
+            StatusCode::OK,
+            json!({
+                "comments": comments,
+                "page": query.page(),
+                "limit": query.limit()
+            }),
+        ),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[derive(Deserialize)]
+struct CreateCommentBody {
+    pub comment: CommentTemplate,
+    pub reply_to: Option<i32>,
+}
+
+#[post("/jokes/{id}/comments", wrap = "Scope::Interact")]
+async fn create_comment(
+    AuthUser(claims): AuthUser,
+    api_state: web::Data<ApiState>,
+    body: web::Json<CreateCommentBody>,
+    path: web::Path<(i32,)>,
+) -> HttpResponse {
+    let joke = match find_joke_visible_by_requester(&Some(claims.clone()), path.0, &api_state.db_conn_pool).await {
+        Ok(joke) => joke,
+        Err(response) => return response,
+    };
+
+    let (status, body) = match body
+        .comment
+        .insert_on_joke(&joke, claims.id, body.reply_to, &api_state.db_conn_pool)
+        .await
+    {
+        Ok(comment) => (StatusCode::OK, json!({ "success": true, "created_comment": comment })),
+        Err(error) => comment_error_to_status_and_body(error),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[derive(Deserialize)]
+struct UpdateCommentBody {
+    pub comment: CommentTemplate,
+}
+
+#[put("/jokes/{id}/comments/{comment_id}", wrap = "Scope::Interact")]
+async fn update_comment(
+    AuthUser(claims): AuthUser,
+    api_state: web::Data<ApiState>,
+    body: web::Json<UpdateCommentBody>,
+    path: web::Path<(i32, i32)>,
+) -> HttpResponse {
+    let (_, mut comment) =
+        match find_comment_visible_by_requester(&claims, path.0, path.1, &api_state.db_conn_pool).await {
+            Ok(found) => found,
+            Err(response) => return response,
+        };
+
+    // Only the comment's author may reword it, admins can only delete it
+    if let Err(error) = enforce_id(&claims, comment.author_id) {
+        return error.to_http_response();
+    }
+
+    let (status, body) = match comment.update(&body.comment, &api_state.db_conn_pool).await {
+        Ok(_) => (StatusCode::OK, json!({ "success": true, "updated_comment": comment })),
+        Err(error) => comment_error_to_status_and_body(error),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+async fn set_comment_hidden(
+    claims: token::Claims,
+    api_state: web::Data<ApiState>,
+    joke_id: i32,
+    comment_id: i32,
+    hidden: bool,
+) -> HttpResponse {
+    let (joke, mut comment) =
+        match find_comment_visible_by_requester(&claims, joke_id, comment_id, &api_state.db_conn_pool).await {
+            Ok(found) => found,
+            Err(response) => return response,
+        };
+
+    // Moderating the discussion under a joke is up to its author, and moderators
+    if let Err(error) = enforce_id_or_permission(&claims, joke.author_id, Permission::Moderate) {
+        return error.to_http_response();
+    }
+
+    let (status, body) = match comment.set_hidden(hidden, &api_state.db_conn_pool).await {
+        Ok(_) => (StatusCode::OK, json!({ "success": true, "updated_comment": comment })),
+        Err(error) => comment_error_to_status_and_body(error),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[post("/jokes/{id}/comments/{comment_id}/hide", wrap = "Scope::Interact")]
+async fn hide_comment(
+    AuthUser(claims): AuthUser,
+    api_state: web::Data<ApiState>,
+    path: web::Path<(i32, i32)>,
+) -> HttpResponse {
+    set_comment_hidden(claims, api_state, path.0, path.1, true).await
+}
+
+#[post("/jokes/{id}/comments/{comment_id}/unhide", wrap = "Scope::Interact")]
+async fn unhide_comment(
+    AuthUser(claims): AuthUser,
+    api_state: web::Data<ApiState>,
+    path: web::Path<(i32, i32)>,
+) -> HttpResponse {
+    set_comment_hidden(claims, api_state, path.0, path.1, false).await
+}
+
+#[delete("/jokes/{id}/comments/{comment_id}", wrap = "Scope::Interact")]
+async fn delete_comment(
+    AuthUser(claims): AuthUser,
+    api_state: web::Data<ApiState>,
+    path: web::Path<(i32, i32)>,
+) -> HttpResponse {
+    let (_, comment) =
+        match find_comment_visible_by_requester(&claims, path.0, path.1, &api_state.db_conn_pool).await {
+            Ok(found) => found,
+            Err(response) => return response,
+        };
+
+    if let Err(error) = enforce_id_or_permission(&claims, comment.author_id, Permission::Moderate) {
+        return error.to_http_response();
+    }
+
+    let (status, body) = match comment.delete(&api_state.db_conn_pool).await {
+        Ok(_) => (StatusCode::OK, json!({})),
+        Err(error) => comment_error_to_status_and_body(error),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+// Comments under jokes the requester cannot see, or hidden from them, are
+// reported as not found
+async fn find_comment_visible_by_requester(
+    claims: &token::Claims,
+    joke_id: i32,
+    comment_id: i32,
+    pool: &db::DbPool,
+) -> Result<(Joke, Comment), HttpResponse> {
+    let claims = Some(claims.clone());
+    let joke = find_joke_visible_by_requester(&claims, joke_id, pool).await?;
+
+    let (status, body) = match comments::find_by_id(joke.id, comment_id, pool).await {
+        Ok(comment) if comment.is_visible_by(&joke, &claims) => return Ok((joke, comment)),
+        Ok(_) | Err(comments::Error::NotFound) => (StatusCode::NOT_FOUND, json!({})),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    Err(HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string()))
+}
+
+fn comment_error_to_status_and_body(error: comments::Error) -> (StatusCode, serde_json::Value) {
+    match error {
+        comments::Error::InvalidContent(issues) => {
+            (StatusCode::OK, json!({ "success": false, "issues": issues }))
+        }
+        comments::Error::NotFound => (StatusCode::NOT_FOUND, json!({})),
+        comments::Error::ParentNotFound | comments::Error::NestedReply => {
+            (StatusCode::BAD_REQUEST, json!({ "error": error }))
+        }
+        comments::Error::DataLayerFailure => {
+            (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error }))
+        }
+    }
+}
@@ -1,37 +1,43 @@
-use super::users::utils_auth::disallow_anonymous_and_role;
+use super::users::{
+    extractors::{permissions, AuthUser, OptionalAuthUser, RequirePermission},
+    utils_auth::enforce_id_or_permission,
+};
 use crate::core::{
-    jokes::JokeTemplate,
-    users::{self},
+    db,
+    jokes::{self, listing, search, Joke, JokeLineTemplate, JokeTemplate, Reaction, Status},
+    users::{self, api_key::Scope, token, Permission},
 };
-use actix_web::{http::StatusCode, post, web, HttpRequest, HttpResponse};
+use actix_web::{delete, get, http::StatusCode, post, put, web, HttpResponse};
 use serde::Deserialize;
 use serde_json::json;
 
 use super::ApiState;
+use crate::web::rate_limit::RateLimit;
+
+pub mod comments;
 
 #[derive(Deserialize)]
 struct CreateJokeBody {
     pub joke: JokeTemplate,
 }
 
-#[post("/jokes/create")]
+#[post("/jokes/create", wrap = "RateLimit::JokeCreation", wrap = "Scope::WriteJokes")]
 async fn create_joke(
-    req: HttpRequest,
+    RequirePermission(claims, _): RequirePermission<permissions::CreateJoke>,
     api_state: web::Data<ApiState>,
     body: web::Json<CreateJokeBody>,
 ) -> HttpResponse {
-    let claims = match disallow_anonymous_and_role(&req, users::Role::None) {
-        Err(error) => return error.to_http_response(),
-        Ok(claims) => claims,
-    };
-
-    let (status, body) = match users::find_by_id(claims.id, &&api_state.db_conn_pool).await {
+    let (status, body) = match users::find_by_id(claims.id, &api_state.db_conn_pool).await {
         Ok(user) => match body
             .joke
-            .insert_and_set_author(&user, &&api_state.db_conn_pool)
+            .insert_and_set_author(&user, &api_state.db_conn_pool)
             .await
         {
             Ok(joke) => (StatusCode::OK, json!({ "success": true, "created_joke": joke })),
+            Err(jokes::Error::Data(issues)) => (
+                StatusCode::OK,
+                json!({ "success": false, "issues": issues }),
+            ),
             Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
         },
         Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
@@ -40,4 +46,391 @@ async fn create_joke(
     HttpResponse::build(status)
         .content_type("application/json")
         .body(body.to_string())
-}
\ No newline at end of file
+}
+
+#[get("/jokes/{id}", wrap = "Scope::Read")]
+async fn get_joke(
+    OptionalAuthUser(claims): OptionalAuthUser,
+    api_state: web::Data<ApiState>,
+    path: web::Path<(i32,)>,
+) -> HttpResponse {
+    let joke = match find_joke_visible_by_requester(&claims, path.0, &api_state.db_conn_pool).await {
+        Ok(joke) => joke,
+        Err(response) => return response,
+    };
+
+    HttpResponse::build(StatusCode::OK)
+        .content_type("application/json")
+        .body(json!(joke).to_string())
+}
+
+#[get("/jokes", wrap = "Scope::Read")]
+async fn list_jokes(
+    OptionalAuthUser(claims): OptionalAuthUser,
+    api_state: web::Data<ApiState>,
+    query: web::Query<listing::Query>,
+) -> HttpResponse {
+    let (status, body) = match query.list(&claims, &api_state.db_conn_pool).await {
+        Ok(jokes) => (
+            StatusCode::OK,
+            json!({
+                "jokes": jokes,
+                "page": query.page(),
+                "limit": query.limit()
+            }),
+        ),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[get("/jokes/search", wrap = "RateLimit::Search", wrap = "Scope::Read")]
+async fn search_jokes(
+    OptionalAuthUser(claims): OptionalAuthUser,
+    api_state: web::Data<ApiState>,
+    query: web::Query<search::Query>,
+) -> HttpResponse {
+    let (status, body) = match query.search(&claims, &api_state.db_conn_pool).await {
+        Ok(hits) => (
+            StatusCode::OK,
+            json!({
+                "hits": hits,
+                "page": query.page(),
+                "limit": query.limit()
+            }),
+        ),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+// Jokes the requester is not allowed to see are reported as not found
+async fn find_joke_visible_by_requester(
+    claims: &Option<token::Claims>,
+    id: i32,
+    pool: &db::DbPool,
+) -> Result<Joke, HttpResponse> {
+    let (status, body) = match jokes::find_by_id(id, pool).await {
+        Ok(joke) if joke.is_visible_by(claims) => return Ok(joke),
+        Ok(_) | Err(jokes::Error::NotFound) => (StatusCode::NOT_FOUND, json!({})),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    Err(HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string()))
+}
+
+// Only the author of a joke or those who can edit any may modify it. Jokes
+// the requester cannot see are reported as not found, as when reading them.
+async fn find_joke_editable_by_requester(
+    claims: &token::Claims,
+    id: i32,
+    pool: &db::DbPool,
+) -> Result<Joke, HttpResponse> {
+    let (status, body) = match jokes::find_by_id(id, pool).await {
+        Ok(joke) if !joke.is_visible_by(&Some(claims.clone())) => (StatusCode::NOT_FOUND, json!({})),
+        Ok(joke) => match enforce_id_or_permission(claims, joke.author_id, Permission::EditAnyJoke) {
+            Ok(_) => return Ok(joke),
+            Err(error) => return Err(error.to_http_response()),
+        },
+        Err(jokes::Error::NotFound) => (StatusCode::NOT_FOUND, json!({})),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    Err(HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string()))
+}
+
+#[derive(Deserialize)]
+struct UpdateJokeBody {
+    pub joke: JokeTemplate,
+}
+
+#[put("/jokes/{id}", wrap = "Scope::WriteJokes")]
+async fn update_joke(
+    AuthUser(claims): AuthUser,
+    api_state: web::Data<ApiState>,
+    body: web::Json<UpdateJokeBody>,
+    path: web::Path<(i32,)>,
+) -> HttpResponse {
+    let mut joke =
+        match find_joke_editable_by_requester(&claims, path.0, &api_state.db_conn_pool).await {
+            Ok(found) => found,
+            Err(response) => return response,
+        };
+
+    let (status, body) = match joke
+        .update(&body.joke, claims.id, &api_state.db_conn_pool)
+        .await
+    {
+        Ok(_) => (StatusCode::OK, json!({ "success": true, "updated_joke": joke })),
+        Err(jokes::Error::Data(issues)) => (
+            StatusCode::OK,
+            json!({ "success": false, "issues": issues }),
+        ),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[derive(Deserialize)]
+struct UpdateJokeLineBody {
+    pub line: JokeLineTemplate,
+}
+
+#[put("/jokes/{id}/lines/{index_within_joke}", wrap = "Scope::WriteJokes")]
+async fn update_joke_line(
+    AuthUser(claims): AuthUser,
+    api_state: web::Data<ApiState>,
+    body: web::Json<UpdateJokeLineBody>,
+    path: web::Path<(i32, i32)>,
+) -> HttpResponse {
+    let mut joke =
+        match find_joke_editable_by_requester(&claims, path.0, &api_state.db_conn_pool).await {
+            Ok(found) => found,
+            Err(response) => return response,
+        };
+
+    let (status, body) = match joke
+        .update_line(path.1, &body.line, claims.id, &api_state.db_conn_pool)
+        .await
+    {
+        Ok(_) => (StatusCode::OK, json!({ "success": true, "updated_joke": joke })),
+        Err(jokes::Error::Data(issues)) => (
+            StatusCode::OK,
+            json!({ "success": false, "issues": issues }),
+        ),
+        Err(jokes::Error::NotFound) => (StatusCode::NOT_FOUND, json!({})),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+async fn set_joke_status(
+    claims: token::Claims,
+    api_state: web::Data<ApiState>,
+    id: i32,
+    status: Status,
+) -> HttpResponse {
+    let mut joke =
+        match find_joke_editable_by_requester(&claims, id, &api_state.db_conn_pool).await {
+            Ok(found) => found,
+            Err(response) => return response,
+        };
+
+    let (status, body) = match joke.set_status(status, &api_state.db_conn_pool).await {
+        Ok(_) => (StatusCode::OK, json!({ "success": true, "updated_joke": joke })),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[post("/jokes/{id}/publish", wrap = "Scope::WriteJokes")]
+async fn publish_joke(
+    AuthUser(claims): AuthUser,
+    api_state: web::Data<ApiState>,
+    path: web::Path<(i32,)>,
+) -> HttpResponse {
+    set_joke_status(claims, api_state, path.0, Status::Published).await
+}
+
+#[post("/jokes/{id}/archive", wrap = "Scope::WriteJokes")]
+async fn archive_joke(
+    AuthUser(claims): AuthUser,
+    api_state: web::Data<ApiState>,
+    path: web::Path<(i32,)>,
+) -> HttpResponse {
+    set_joke_status(claims, api_state, path.0, Status::Archived).await
+}
+
+#[derive(Deserialize)]
+struct SetReactionBody {
+    pub reaction: Reaction,
+}
+
+#[put("/jokes/{id}/reaction", wrap = "Scope::Interact")]
+async fn set_joke_reaction(
+    AuthUser(claims): AuthUser,
+    api_state: web::Data<ApiState>,
+    body: web::Json<SetReactionBody>,
+    path: web::Path<(i32,)>,
+) -> HttpResponse {
+    let mut joke = match find_joke_visible_by_requester(&Some(claims.clone()), path.0, &api_state.db_conn_pool).await {
+        Ok(joke) => joke,
+        Err(response) => return response,
+    };
+
+    let (status, body) = match joke
+        .set_reaction(claims.id, body.reaction, &api_state.db_conn_pool)
+        .await
+    {
+        Ok(_) => (StatusCode::OK, json!({ "success": true, "reactions": joke.reactions })),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[delete("/jokes/{id}/reaction", wrap = "Scope::Interact")]
+async fn clear_joke_reaction(
+    AuthUser(claims): AuthUser,
+    api_state: web::Data<ApiState>,
+    path: web::Path<(i32,)>,
+) -> HttpResponse {
+    let mut joke = match find_joke_visible_by_requester(&Some(claims.clone()), path.0, &api_state.db_conn_pool).await {
+        Ok(joke) => joke,
+        Err(response) => return response,
+    };
+
+    let (status, body) = match joke.clear_reaction(claims.id, &api_state.db_conn_pool).await {
+        Ok(_) => (StatusCode::OK, json!({ "success": true, "reactions": joke.reactions })),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[delete("/jokes/{id}", wrap = "Scope::WriteJokes")]
+async fn delete_joke(
+    AuthUser(claims): AuthUser,
+    api_state: web::Data<ApiState>,
+    path: web::Path<(i32,)>,
+) -> HttpResponse {
+    let joke =
+        match find_joke_editable_by_requester(&claims, path.0, &api_state.db_conn_pool).await {
+            Ok(found) => found,
+            Err(response) => return response,
+        };
+
+    let (status, body) = match joke.delete(&api_state.db_conn_pool).await {
+        Ok(_) => (StatusCode::OK, json!({})),
+        Err(jokes::Error::NotFound) => (StatusCode::NOT_FOUND, json!({})),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[get("/jokes/{id}/revisions", wrap = "Scope::Read")]
+async fn list_joke_revisions(
+    OptionalAuthUser(claims): OptionalAuthUser,
+    api_state: web::Data<ApiState>,
+    path: web::Path<(i32,)>,
+) -> HttpResponse {
+    let joke = match find_joke_visible_by_requester(&claims, path.0, &api_state.db_conn_pool).await {
+        Ok(joke) => joke,
+        Err(response) => return response,
+    };
+
+    let (status, body) = match joke.revisions(&api_state.db_conn_pool).await {
+        Ok(revisions) => (StatusCode::OK, json!({ "revisions": revisions })),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[get("/jokes/{id}/revisions/{revision_number}", wrap = "Scope::Read")]
+async fn get_joke_revision(
+    OptionalAuthUser(claims): OptionalAuthUser,
+    api_state: web::Data<ApiState>,
+    path: web::Path<(i32, i32)>,
+) -> HttpResponse {
+    let joke = match find_joke_visible_by_requester(&claims, path.0, &api_state.db_conn_pool).await {
+        Ok(joke) => joke,
+        Err(response) => return response,
+    };
+
+    let (status, body) = match joke.revision(path.1, &api_state.db_conn_pool).await {
+        Ok(revision) => (StatusCode::OK, json!(revision)),
+        Err(jokes::Error::NotFound) => (StatusCode::NOT_FOUND, json!({})),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[get("/jokes/{id}/revisions/{from_revision}/diff/{to_revision}", wrap = "Scope::Read")]
+async fn diff_joke_revisions(
+    OptionalAuthUser(claims): OptionalAuthUser,
+    api_state: web::Data<ApiState>,
+    path: web::Path<(i32, i32, i32)>,
+) -> HttpResponse {
+    let pool = &api_state.db_conn_pool;
+    let joke = match find_joke_visible_by_requester(&claims, path.0, pool).await {
+        Ok(joke) => joke,
+        Err(response) => return response,
+    };
+
+    let (status, body) = match (joke.revision(path.1, pool).await, joke.revision(path.2, pool).await) {
+        (Ok(from), Ok(to)) => (StatusCode::OK, json!(from.diff(&to))),
+        (Err(jokes::Error::NotFound), _) | (_, Err(jokes::Error::NotFound)) => {
+            (StatusCode::NOT_FOUND, json!({}))
+        }
+        (Err(error), _) | (_, Err(error)) => {
+            (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error }))
+        }
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[post("/jokes/{id}/revisions/{revision_number}/restore", wrap = "Scope::WriteJokes")]
+async fn restore_joke_revision(
+    AuthUser(claims): AuthUser,
+    api_state: web::Data<ApiState>,
+    path: web::Path<(i32, i32)>,
+) -> HttpResponse {
+    let mut joke =
+        match find_joke_editable_by_requester(&claims, path.0, &api_state.db_conn_pool).await {
+            Ok(found) => found,
+            Err(response) => return response,
+        };
+
+    let (status, body) = match joke
+        .restore_revision(path.1, claims.id, &api_state.db_conn_pool)
+        .await
+    {
+        Ok(_) => (StatusCode::OK, json!({ "success": true, "updated_joke": joke })),
+        Err(jokes::Error::NotFound) => (StatusCode::NOT_FOUND, json!({})),
+        // Revisions saved before the current rules may not follow them
+        Err(jokes::Error::Data(issues)) => (
+            StatusCode::OK,
+            json!({ "success": false, "issues": issues }),
+        ),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
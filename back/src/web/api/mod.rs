@@ -1,30 +1,102 @@
-use crate::core::{db};
+use crate::core::{db, mail, oidc, users::session};
+use crate::web::{client_ip, rate_limit};
 use actix_web::{web, Scope};
+use std::sync::Arc;
 
 mod auth;
-mod users;
+pub mod users;
 mod misc;
 mod jokes;
+mod tags;
+mod settings;
+mod roles;
+mod api_keys;
 
-pub fn service(db_conn_pool: db::DbPool) -> Scope {
+pub fn service(api_state: ApiState) -> Scope {
     web::scope("/api")
-        .app_data(web::Data::new(ApiState::new(db_conn_pool)))
+        .app_data(web::Data::new(api_state))
         .service(misc::ping)
         .service(auth::register)
         .service(auth::login)
+        .service(auth::refresh)
+        .service(auth::logout)
+        .service(auth::change_password)
+        .service(auth::request_password_reset)
+        .service(auth::reset_password)
+        .service(auth::confirm_email)
+        .service(auth::resend_email_verification)
+        .service(auth::login_two_factor)
+        .service(auth::enrol_two_factor)
+        .service(auth::confirm_two_factor)
+        .service(auth::disable_two_factor)
+        .service(auth::list_oidc_providers)
+        .service(auth::authorize_oidc)
+        .service(auth::oidc_callback)
         .service(users::get_user_data)
+        .service(users::export_own_data)
         .service(users::search_users)
         .service(users::change_user_role)
+        .service(users::unlock_user)
+        .service(users::update_own_profile)
+        .service(users::delete_own_account)
+        .service(api_keys::create_api_key)
+        .service(api_keys::list_api_keys)
+        .service(api_keys::revoke_api_key)
+        .service(settings::get_settings)
+        .service(settings::update_settings)
+        .service(roles::list_roles)
         .service(jokes::create_joke)
+        .service(jokes::list_jokes)
+        // Registered before get_joke, which would otherwise take "search" for an id
+        .service(jokes::search_jokes)
+        .service(jokes::get_joke)
+        .service(jokes::update_joke)
+        .service(jokes::update_joke_line)
+        .service(jokes::publish_joke)
+        .service(jokes::archive_joke)
+        .service(jokes::delete_joke)
+        .service(jokes::set_joke_reaction)
+        .service(jokes::clear_joke_reaction)
+        .service(jokes::comments::list_comments)
+        .service(jokes::comments::create_comment)
+        .service(jokes::comments::update_comment)
+        .service(jokes::comments::hide_comment)
+        .service(jokes::comments::unhide_comment)
+        .service(jokes::comments::delete_comment)
+        .service(jokes::list_joke_revisions)
+        .service(jokes::get_joke_revision)
+        .service(jokes::diff_joke_revisions)
+        .service(jokes::restore_joke_revision)
+        .service(tags::list_tags)
+        .service(tags::list_tag_jokes)
 }
 
 #[derive(Clone)]
 pub struct ApiState {
     pub db_conn_pool: db::DbPool,
+    // Shared by all workers, so that revocations are seen by every one of them
+    pub session_cache: session::Cache,
+    pub mailer: Arc<dyn mail::Mailer>,
+    pub rate_limiter: rate_limit::Limiter,
+    pub oidc_providers: oidc::Providers,
+    pub trusted_proxies: client_ip::TrustedProxies,
 }
 
 impl ApiState {
-    fn new(db_conn_pool: db::DbPool) -> Self {
-        ApiState { db_conn_pool }
+    pub fn new(
+        db_conn_pool: db::DbPool,
+        mailer: Arc<dyn mail::Mailer>,
+        rate_limiter: rate_limit::Limiter,
+        oidc_providers: oidc::Providers,
+        trusted_proxies: client_ip::TrustedProxies,
+    ) -> Self {
+        ApiState {
+            db_conn_pool,
+            session_cache: session::Cache::default(),
+            mailer,
+            rate_limiter,
+            oidc_providers,
+            trusted_proxies,
+        }
     }
 }
\ No newline at end of file
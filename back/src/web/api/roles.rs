@@ -0,0 +1,21 @@
+use crate::core::users;
+use actix_web::{get, http::StatusCode, web, HttpResponse};
+use serde_json::json;
+
+use super::{
+    users::extractors::{permissions::ManageRoles, RequirePermission},
+    ApiState,
+};
+
+// Every role along with the permissions it grants
+#[get("/roles")]
+async fn list_roles(_: RequirePermission<ManageRoles>, api_state: web::Data<ApiState>) -> HttpResponse {
+    let (status, body) = match users::list_roles(&api_state.db_conn_pool).await {
+        Ok(roles) => (StatusCode::OK, json!({ "roles": roles })),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
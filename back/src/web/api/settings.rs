@@ -0,0 +1,41 @@
+use crate::core::settings;
+use actix_web::{get, http::StatusCode, put, web, HttpResponse};
+use serde_json::json;
+
+use super::{
+    users::extractors::{permissions::ManageSettings, RequirePermission},
+    ApiState,
+};
+
+#[get("/settings")]
+async fn get_settings(_: RequirePermission<ManageSettings>, api_state: web::Data<ApiState>) -> HttpResponse {
+    let (status, body) = match settings::get(&api_state.db_conn_pool).await {
+        Ok(settings) => (StatusCode::OK, json!(settings)),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[put("/settings")]
+async fn update_settings(
+    _: RequirePermission<ManageSettings>,
+    api_state: web::Data<ApiState>,
+    update: web::Json<settings::Update>,
+) -> HttpResponse {
+    let (status, body) = match update.apply(&api_state.db_conn_pool).await {
+        Ok(settings) => {
+            api_state
+                .session_cache
+                .confirm_admins_need_two_factor(settings.admins_need_two_factor);
+            (StatusCode::OK, json!(settings))
+        }
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
@@ -0,0 +1,46 @@
+use crate::core::{
+    jokes::{listing, tags},
+    users::api_key::Scope,
+};
+use actix_web::{get, http::StatusCode, web, HttpResponse};
+use serde_json::json;
+
+use super::{users::extractors::OptionalAuthUser, ApiState};
+
+#[get("/tags", wrap = "Scope::Read")]
+async fn list_tags(OptionalAuthUser(claims): OptionalAuthUser, api_state: web::Data<ApiState>) -> HttpResponse {
+    let (status, body) = match tags::list_with_counts(&claims, &api_state.db_conn_pool).await {
+        Ok(tags) => (StatusCode::OK, json!({ "tags": tags })),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[get("/tags/{tag}/jokes", wrap = "Scope::Read")]
+async fn list_tag_jokes(
+    OptionalAuthUser(claims): OptionalAuthUser,
+    api_state: web::Data<ApiState>,
+    mut query: web::Query<listing::Query>,
+    path: web::Path<(String,)>,
+) -> HttpResponse {
+    query.tag = Some(path.into_inner().0);
+
+    let (status, body) = match query.list(&claims, &api_state.db_conn_pool).await {
+        Ok(jokes) => (
+            StatusCode::OK,
+            json!({
+                "jokes": jokes,
+                "page": query.page(),
+                "limit": query.limit()
+            }),
+        ),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
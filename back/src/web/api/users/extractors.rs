@@ -0,0 +1,102 @@
+use crate::core::users::{token::Claims, Permission};
+use actix_web::{dev::Payload, FromRequest, HttpRequest};
+use std::{future::Future, marker::PhantomData, pin::Pin};
+
+use super::utils_auth::{self, Error};
+
+type ClaimsFuture<T> = Pin<Box<dyn Future<Output = Result<T, Error>>>>;
+
+// Refuses requests without a valid access token with 401
+pub struct AuthUser(pub Claims);
+
+// Anonymous without an Authorization header, invalid tokens are still refused
+pub struct OptionalAuthUser(pub Option<Claims>);
+
+// As AuthUser, but lets in admins who still have to set up a second factor
+pub struct AuthUserWithoutTwoFactor(pub Claims);
+
+// Refuses users whose role lacks the permission with 403:
+// RequirePermission<ManageSettings>
+pub struct RequirePermission<P: PermissionMarker>(pub Claims, pub PhantomData<P>);
+
+pub trait PermissionMarker {
+    const PERMISSION: Permission;
+}
+
+macro_rules! markers {
+    ($marker_trait:ident, $const:ident, $type:ident, $($name:ident),*) => {
+        $(
+            pub struct $name;
+
+            impl $marker_trait for $name {
+                const $const: $type = $type::$name;
+            }
+        )*
+    };
+}
+
+pub mod permissions {
+    use super::{Permission, PermissionMarker};
+    markers!(
+        PermissionMarker, PERMISSION, Permission,
+        CreateJoke, EditAnyJoke, ViewAnyJoke, Moderate, ManageUsers, ManageRoles, ManageSettings
+    );
+}
+
+impl FromRequest for AuthUser {
+    type Config = ();
+    type Error = Error;
+    type Future = ClaimsFuture<Self>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move { utils_auth::auth_user(&req).await.map(AuthUser) })
+    }
+}
+
+impl FromRequest for OptionalAuthUser {
+    type Config = ();
+    type Error = Error;
+    type Future = ClaimsFuture<Self>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            match utils_auth::auth_user(&req).await {
+                Ok(claims) => Ok(OptionalAuthUser(Some(claims))),
+                Err(Error::NoAuthorizationHeader) => Ok(OptionalAuthUser(None)),
+                Err(error) => Err(error),
+            }
+        })
+    }
+}
+
+impl FromRequest for AuthUserWithoutTwoFactor {
+    type Config = ();
+    type Error = Error;
+    type Future = ClaimsFuture<Self>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            utils_auth::auth_user_without_two_factor(&req)
+                .await
+                .map(AuthUserWithoutTwoFactor)
+        })
+    }
+}
+
+impl<P: PermissionMarker + 'static> FromRequest for RequirePermission<P> {
+    type Config = ();
+    type Error = Error;
+    type Future = ClaimsFuture<Self>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            utils_auth::require_permission(&req, P::PERMISSION)
+                .await
+                .map(|claims| RequirePermission(claims, PhantomData))
+        })
+    }
+}
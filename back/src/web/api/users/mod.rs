@@ -1,41 +1,35 @@
-use crate::core::users::{self, Role};
-use actix_web::{get, http::StatusCode, post, web, HttpRequest, HttpResponse};
+use crate::core::users::{self, api_key::Scope, Role};
+use crate::web::rate_limit::RateLimit;
+use actix_web::{delete, get, http::StatusCode, patch, post, web, HttpResponse};
 use serde::Deserialize;
 use serde_json::json;
 
-use self::utils_auth::enforce_role;
+use self::extractors::{permissions, AuthUser, OptionalAuthUser, RequirePermission};
 
 use super::ApiState;
 
+pub mod extractors;
+pub mod scope;
 pub mod utils_auth;
 
-#[derive(Deserialize)]
-struct SearchUserQuery {
-    pub query: String,
-}
-
-#[post("/users/search")]
+#[post("/users/search", wrap = "RateLimit::Search", wrap = "Scope::Read")]
 async fn search_users(
-    req: HttpRequest,
+    OptionalAuthUser(claims): OptionalAuthUser,
     api_state: web::Data<ApiState>,
-    query: web::Json<SearchUserQuery>,
+    query: web::Json<users::search::Query>,
 ) -> HttpResponse {
-    let claims = utils_auth::auth_user(&req);
-
-    let claims = match claims {
-        Ok(claims) => Some(claims),
-        Err(utils_auth::Error::NoAuthorizationHeader) => None,
-        Err(error) => return error.to_http_response(),
-    };
-
-    let (status, body) = match users::search_by_username_to_json_as_seen_from(
-        &query.query,
-        &claims,
-        &&api_state.db_conn_pool,
-    )
-    .await
+    let (status, body) = match query
+        .search_to_json_as_seen_from(&claims, &api_state.db_conn_pool)
+        .await
     {
-        Ok(users) => (StatusCode::OK, json!({ "results": users })),
+        Ok(users) => (
+            StatusCode::OK,
+            json!({
+                "results": users,
+                "page": query.page(),
+                "limit": query.limit()
+            }),
+        ),
         Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
     };
 
@@ -44,21 +38,13 @@ async fn search_users(
         .body(body.to_string())
 }
 
-#[get("/users/{username}")]
+#[get("/users/{username}", wrap = "Scope::Read")]
 async fn get_user_data(
-    req: HttpRequest,
+    OptionalAuthUser(claims): OptionalAuthUser,
     api_state: web::Data<ApiState>,
     path: web::Path<(String,)>,
 ) -> HttpResponse {
-    let claims = utils_auth::auth_user(&req);
-
-    let claims = match claims {
-        Ok(claims) => Some(claims),
-        Err(utils_auth::Error::NoAuthorizationHeader) => None,
-        Err(error) => return error.to_http_response(),
-    };
-
-    let (status, body) = match users::find_by_username(&path.0, &&api_state.db_conn_pool).await {
+    let (status, body) = match users::find_by_username(&path.0, &api_state.db_conn_pool).await {
         Ok(user) => (StatusCode::OK, user.to_json_as_seen_from(&claims)),
         Err(users::Error::NotFound) => (StatusCode::NOT_FOUND, json!({})),
         Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
@@ -76,18 +62,16 @@ struct ChangeRoleBody {
 
 #[post("/users/{username}/role")]
 async fn change_user_role(
-    req: HttpRequest,
+    _: RequirePermission<permissions::ManageRoles>,
     api_state: web::Data<ApiState>,
     body: web::Json<ChangeRoleBody>,
     path: web::Path<(String,)>,
 ) -> HttpResponse {
-    if let Err(error) = enforce_role(&req, users::Role::Admin) {
-        return error.to_http_response();
-    }
-    let (status, body) = match users::find_by_username(&path.0, &&api_state.db_conn_pool).await {
+    let (status, body) = match users::find_by_username(&path.0, &api_state.db_conn_pool).await {
         Ok(mut user) => {
-            match user.set_role(body.new_role, &&api_state.db_conn_pool).await {
+            match user.set_role(body.new_role, &api_state.session_cache, &api_state.db_conn_pool).await {
                 Ok(_) => (StatusCode::OK, json!({})),
+                Err(users::Error::UnverifiedEmail) => (StatusCode::CONFLICT, json!({ "error": users::Error::UnverifiedEmail })),
                 Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })) 
             }
         },
@@ -98,4 +82,89 @@ async fn change_user_role(
     HttpResponse::build(status)
         .content_type("application/json")
         .body(body.to_string())
-}
\ No newline at end of file
+}
+
+// Gives a user locked out by failed logins their attempts back
+#[delete("/users/{username}/lock")]
+async fn unlock_user(
+    _: RequirePermission<permissions::ManageUsers>,
+    api_state: web::Data<ApiState>,
+    path: web::Path<(String,)>,
+) -> HttpResponse {
+    let (status, body) = match users::lockout::unlock(&path.0, &api_state.db_conn_pool).await {
+        Ok(_) => (StatusCode::OK, json!({})),
+        Err(users::lockout::Error::NotFound) => (StatusCode::NOT_FOUND, json!({})),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": error })),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[patch("/users/me")]
+async fn update_own_profile(
+    AuthUser(claims): AuthUser,
+    api_state: web::Data<ApiState>,
+    update: web::Json<users::profile::Update>,
+) -> HttpResponse {
+    let (status, body) = match update.apply(claims.id, api_state.mailer.as_ref(), &api_state.db_conn_pool).await {
+        Ok(user) => (
+            StatusCode::OK,
+            json!({
+                "success": true,
+                "user": user.to_json_as_seen_from(&Some(claims))
+            }),
+        ),
+        Err(users::profile::Error::Data(issues)) => (
+            StatusCode::OK,
+            json!({
+                "success": false,
+                "issues": issues
+            }),
+        ),
+        Err(users::profile::Error::Failure(failure)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            json!({ "error": failure }),
+        ),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[delete("/users/me")]
+async fn delete_own_account(
+    AuthUser(claims): AuthUser,
+    api_state: web::Data<ApiState>,
+    deletion_data: web::Json<users::deletion::Data>,
+) -> HttpResponse {
+    let (status, body) = match deletion_data.delete_account(&claims, &api_state.session_cache, &api_state.db_conn_pool).await {
+        Ok(_) => (StatusCode::OK, json!({})),
+        Err(users::deletion::Error::Denied(reason)) => {
+            (StatusCode::UNAUTHORIZED, json!({ "reason": reason }))
+        },
+        Err(users::deletion::Error::Failure(failure)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            json!({ "error": failure }),
+        ),
+    };
+
+    HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_string())
+}
+
+#[get("/users/me/export")]
+async fn export_own_data(AuthUser(claims): AuthUser, api_state: web::Data<ApiState>) -> HttpResponse {
+    match users::export::export(claims, &api_state.db_conn_pool).await {
+        Ok(archive) => HttpResponse::Ok()
+            .content_type("application/json")
+            .insert_header(("Content-Disposition", "attachment; filename=\"camion-export.json\""))
+            .body(json!(archive).to_string()),
+        Err(error) => HttpResponse::InternalServerError()
+            .content_type("application/json")
+            .body(json!({ "error": error }).to_string()),
+    }
+}
@@ -0,0 +1,49 @@
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error as ActixError, HttpMessage,
+};
+use std::future::{ready, Ready};
+
+use crate::core::users::api_key::Scope;
+
+// Routes API keys with the scope may call, used as their middleware:
+// #[post("/jokes/create", wrap = "Scope::WriteJokes")]
+// Keys are refused on routes declaring none, see utils_auth.
+pub struct ScopeMiddleware<S> {
+    service: S,
+    scope: Scope,
+}
+
+impl<S> Transform<S, ServiceRequest> for Scope
+where
+    S: Service<ServiceRequest, Response = ServiceResponse, Error = ActixError>,
+{
+    type Response = ServiceResponse;
+    type Error = ActixError;
+    type Transform = ScopeMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ScopeMiddleware {
+            service,
+            scope: *self,
+        }))
+    }
+}
+
+impl<S> Service<ServiceRequest> for ScopeMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse, Error = ActixError>,
+{
+    type Response = ServiceResponse;
+    type Error = ActixError;
+    type Future = S::Future;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        req.extensions_mut().insert(self.scope);
+        self.service.call(req)
+    }
+}
@@ -1,79 +1,192 @@
+use super::super::ApiState;
 use crate::core::{
-    users::{token, Role},
+    users::{
+        api_key::{self, Scope},
+        session, token, two_factor, Permission,
+    },
+};
+use actix_web::{
+    http::{header::WWW_AUTHENTICATE, StatusCode},
+    web, HttpRequest, HttpResponse, ResponseError,
 };
-use actix_web::{http::StatusCode, HttpRequest, HttpResponse};
 use serde::Serialize;
 use serde_json::json;
+use std::fmt;
 
-#[derive(Serialize)]
+#[derive(Serialize, Debug)]
 pub enum Error {
     NoAuthorizationHeader,
+    // Not of the "Bearer <access token or API key>" form
     AuthorizationParsing,
     InvalidToken,
+    // Unknown, revoked or expired
+    InvalidApiKey,
+    RevokedSession,
+    OutdatedToken,
     UserNotAllowed,
     RoleNotAllowed,
+    // The route does not take API keys, see scope
+    ApiKeyNotAllowed,
+    MissingScope,
+    // The role requires the session to be opened with two factors
+    TwoFactorRequired,
+    // The database could not be asked, the only error answered with a 500
+    SessionCheckFailure,
 }
 
 impl Error {
+    // 401 when the requester is not known, 403 when they are but cannot do it
     pub fn to_http_response(&self) -> HttpResponse {
-        HttpResponse::build(StatusCode::UNAUTHORIZED)
+        let mut response = HttpResponse::build(self.status_code());
+        if self.status_code() == StatusCode::UNAUTHORIZED {
+            response.insert_header((WWW_AUTHENTICATE, "Bearer"));
+        }
+        response
             .content_type("application/json")
             .body(json!({ "error": self }).to_string())
     }
 }
 
-pub fn disallow_anonymous_and_role(
-    req: &HttpRequest,
-    role: Role,
-) -> Result<token::Claims, Error> {
-    match auth_user(req) {
-        Ok(claims) => {
-            if role != claims.role {
-                Ok(claims)
-            } else {
-                Err(Error::RoleNotAllowed)
-            }
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+// Lets the extractors fail with these errors, see extractors
+impl ResponseError for Error {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Error::UserNotAllowed
+            | Error::RoleNotAllowed
+            | Error::ApiKeyNotAllowed
+            | Error::MissingScope
+            | Error::TwoFactorRequired => StatusCode::FORBIDDEN,
+            Error::SessionCheckFailure => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::UNAUTHORIZED,
         }
-        Err(err) => Err(err),
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        self.to_http_response()
     }
 }
 
-pub fn enforce_role(
+// Fails with RoleNotAllowed when the role of the user does not grant the
+// permission
+pub async fn require_permission(
     req: &HttpRequest,
-    role: Role,
+    permission: Permission,
 ) -> Result<token::Claims, Error> {
-    match auth_user(req) {
-        Ok(claims) => {
-            if role == claims.role {
-                Ok(claims)
-            } else {
-                Err(Error::RoleNotAllowed)
-            }
-        }
-        Err(err) => Err(err),
+    let claims = auth_user(req).await?;
+    if claims.can(permission) {
+        Ok(claims)
+    } else {
+        Err(Error::RoleNotAllowed)
     }
 }
 
-pub fn enforce_id(req: &HttpRequest, id: i32) -> Result<(), Error> {
-    match auth_user(req) {
-        Ok(token::Claims{ id: user_id, ..}) => {
-            if user_id == id {
-                Ok(())
-            } else {
-                Err(Error::UserNotAllowed)
-            }
-        }
-        Err(err) => Err(err),
+pub fn enforce_id(claims: &token::Claims, id: i32) -> Result<(), Error> {
+    if claims.id == id {
+        Ok(())
+    } else {
+        Err(Error::UserNotAllowed)
+    }
+}
+
+pub fn enforce_id_or_permission(
+    claims: &token::Claims,
+    id: i32,
+    permission: Permission,
+) -> Result<(), Error> {
+    if claims.id == id || claims.can(permission) {
+        Ok(())
+    } else {
+        Err(Error::UserNotAllowed)
+    }
+}
+
+// Fails with TwoFactorRequired when the role requires a second factor the
+// session was not opened with. API keys count as opened with one when the
+// session creating them was.
+pub async fn auth_user(req: &HttpRequest) -> Result<token::Claims, Error> {
+    let claims = auth_user_without_two_factor(req).await?;
+    if claims.two_factor {
+        return Ok(claims);
+    }
+
+    let api_state = req
+        .app_data::<web::Data<ApiState>>()
+        .expect("ApiState not registered");
+    match two_factor::is_mandatory_for_cached(claims.role, &api_state.session_cache, &api_state.db_conn_pool).await {
+        Ok(false) => Ok(claims),
+        Ok(true) => Err(Error::TwoFactorRequired),
+        Err(_) => Err(Error::SessionCheckFailure),
+    }
+}
+
+// Only for what users need to get a second factor set up
+pub async fn auth_user_without_two_factor(req: &HttpRequest) -> Result<token::Claims, Error> {
+    let api_state = req
+        .app_data::<web::Data<ApiState>>()
+        .expect("ApiState not registered");
+    let claims = match credentials(req)? {
+        Credentials::AccessToken(claims) => claims,
+        Credentials::ApiKey(key) => return auth_api_key(req, &key, api_state).await,
+    };
+
+    match session::check(&claims, &api_state.session_cache, &api_state.db_conn_pool).await {
+        Ok(session::ClaimsStatus::Valid) => Ok(claims),
+        Ok(session::ClaimsStatus::RevokedSession) => Err(Error::RevokedSession),
+        Ok(session::ClaimsStatus::OutdatedToken) => Err(Error::OutdatedToken),
+        Err(_) => Err(Error::SessionCheckFailure),
+    }
+}
+
+// Keys are only let in on the routes declaring a scope they have
+async fn auth_api_key(req: &HttpRequest, key: &str, api_state: &ApiState) -> Result<token::Claims, Error> {
+    let claims = match api_key::authenticate(key, &api_state.db_conn_pool).await {
+        Ok(Some(claims)) => claims,
+        Ok(None) => return Err(Error::InvalidApiKey),
+        Err(_) => return Err(Error::SessionCheckFailure),
+    };
+
+    let required = match req.extensions().get::<Scope>() {
+        Some(scope) => *scope,
+        None => return Err(Error::ApiKeyNotAllowed),
+    };
+    match &claims.scopes {
+        Some(scopes) if scopes.contains(&required) => (),
+        _ => return Err(Error::MissingScope),
     }
+
+    api_key::mark_used(key, &api_state.db_conn_pool)
+        .await
+        .map_err(|_| Error::SessionCheckFailure)?;
+    Ok(claims)
 }
 
-pub fn auth_user(req: &HttpRequest) -> Result<token::Claims, Error> {
-    match req.headers().get("Authorization") {
-        Some(authorization) => match authorization.to_str() {
-            Ok(token) => token::to_claims(&token.to_owned())
-                .map_err(|_| Error::InvalidToken),
-            Err(_) => Err(Error::AuthorizationParsing),
-        },
-        None => Err(Error::NoAuthorizationHeader),
+// What the Authorization header holds, not yet checked against the database
+pub enum Credentials {
+    AccessToken(token::Claims),
+    ApiKey(String),
+}
+
+pub fn credentials(req: &HttpRequest) -> Result<Credentials, Error> {
+    let authorization = match req.headers().get("Authorization") {
+        Some(authorization) => authorization.to_str().map_err(|_| Error::AuthorizationParsing)?,
+        None => return Err(Error::NoAuthorizationHeader),
+    };
+    let token = match authorization.trim().split_once(' ') {
+        Some((scheme, token)) if scheme.eq_ignore_ascii_case("Bearer") => token.trim(),
+        _ => return Err(Error::AuthorizationParsing),
+    };
+
+    if token.starts_with(api_key::KEY_PREFIX) {
+        Ok(Credentials::ApiKey(token.to_owned()))
+    } else {
+        token::to_claims(token)
+            .map(Credentials::AccessToken)
+            .map_err(|_| Error::InvalidToken)
     }
-}
\ No newline at end of file
+}
@@ -1,8 +1,8 @@
 use actix_web::{App, HttpServer, dev::Server};
-use std::net::TcpListener;
+use std::{net::TcpListener, sync::Arc};
 
-use crate::core::{db};
-use crate::web::{api};
+use crate::core::{db, mail, oidc};
+use crate::web::{api, client_ip, rate_limit, well_known};
 
 pub struct Application {
     server: Server,
@@ -13,11 +13,22 @@ pub struct Config {
     pub host: String,
     pub port: u16,
     pub db_url: String,
+    pub mailer: Arc<dyn mail::Mailer>,
+    pub rate_limiter: rate_limit::Limiter,
+    pub oidc_providers: oidc::Providers,
+    pub trusted_proxies: client_ip::TrustedProxies,
 }
 
 impl Application {
     pub async fn create(config: &Config) -> Result<Self, std::io::Error> {
         let pool = db::build_pool(&config.db_url).await;
+        let api_state = api::ApiState::new(
+            pool,
+            config.mailer.clone(),
+            config.rate_limiter.clone(),
+            config.oidc_providers.clone(),
+            config.trusted_proxies.clone(),
+        );
 
         let address = format!("{}:{}", config.host, config.port);
         let listener = TcpListener::bind(&address)?;
@@ -25,7 +36,8 @@ impl Application {
     
         let server = HttpServer::new(move || {
             App::new()
-                .service(api::service(pool.clone()))
+                .service(api::service(api_state.clone()))
+                .service(well_known::jwks)
         })
         .listen(listener)?
         .run();
@@ -0,0 +1,56 @@
+use actix_web::HttpRequest;
+use std::{
+    env,
+    net::{IpAddr, SocketAddr},
+};
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Invalid(&'static str),
+}
+
+// Proxies whose Forwarded and X-Forwarded-For headers are believed. They must
+// set these headers themselves, the first address listed being taken for the
+// client's.
+#[derive(Clone, Debug, Default)]
+pub struct TrustedProxies(Vec<IpAddr>);
+
+impl TrustedProxies {
+    pub fn new(addresses: Vec<IpAddr>) -> Self {
+        TrustedProxies(addresses)
+    }
+
+    // Any client could claim another address, so only requests coming from a
+    // trusted proxy are taken at their word
+    pub fn client_ip(&self, req: &HttpRequest) -> Option<IpAddr> {
+        let peer_ip = req.peer_addr()?.ip();
+        if !self.0.contains(&peer_ip) {
+            return Some(peer_ip);
+        }
+        req.connection_info()
+            .realip_remote_addr()
+            .and_then(parse_address)
+            .or(Some(peer_ip))
+    }
+}
+
+// TRUSTED_PROXIES lists the addresses of the proxies, comma separated. None are
+// trusted without it.
+pub fn from_env() -> Result<TrustedProxies, ConfigError> {
+    env::var("TRUSTED_PROXIES")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .map(|address| address.parse().map_err(|_| ConfigError::Invalid("TRUSTED_PROXIES")))
+        .collect::<Result<Vec<_>, _>>()
+        .map(TrustedProxies)
+}
+
+// Proxies may give the port along with the address
+fn parse_address(address: &str) -> Option<IpAddr> {
+    address
+        .parse::<IpAddr>()
+        .ok()
+        .or_else(|| address.parse::<SocketAddr>().ok().map(|address| address.ip()))
+}
@@ -1,2 +1,5 @@
 pub mod api;
-pub mod application;
\ No newline at end of file
+pub mod application;
+pub mod client_ip;
+pub mod rate_limit;
+pub mod well_known;
@@ -0,0 +1,32 @@
+use std::env;
+
+use super::{Limit, Limits};
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Invalid(String),
+}
+
+// RATE_LIMIT_REGISTRATION, RATE_LIMIT_LOGIN, RATE_LIMIT_SEARCH and
+// RATE_LIMIT_JOKE_CREATION are "<capacity>/minute", "<capacity>/hour" or
+// "off". Those left out keep their default limit.
+pub fn from_env() -> Result<Limits, ConfigError> {
+    let defaults = Limits::default();
+    Ok(Limits {
+        registration: limit_from_env("RATE_LIMIT_REGISTRATION", defaults.registration)?,
+        login: limit_from_env("RATE_LIMIT_LOGIN", defaults.login)?,
+        search: limit_from_env("RATE_LIMIT_SEARCH", defaults.search)?,
+        joke_creation: limit_from_env("RATE_LIMIT_JOKE_CREATION", defaults.joke_creation)?,
+    })
+}
+
+fn limit_from_env(name: &str, default: Option<Limit>) -> Result<Option<Limit>, ConfigError> {
+    match env::var(name).ok().filter(|value| !value.is_empty()).as_deref() {
+        None => Ok(default),
+        Some("off") => Ok(None),
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| ConfigError::Invalid(name.to_owned())),
+    }
+}
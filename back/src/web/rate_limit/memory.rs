@@ -0,0 +1,75 @@
+use async_trait::async_trait;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::Instant,
+};
+
+use super::{Decision, Error, Limit, Store};
+
+// Past that, refilled buckets get dropped, a missing bucket being a full one
+const CAPACITY: usize = 100_000;
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+    limit: Limit,
+}
+
+// Only enforces limits within a single instance
+#[derive(Default)]
+pub struct InMemoryStore {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        let elapsed_sec = now.duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed_sec * refill_per_sec(self.limit)).min(self.limit.capacity as f64);
+        self.updated_at = now;
+    }
+
+    fn is_full(&self, now: Instant) -> bool {
+        self.tokens + now.duration_since(self.updated_at).as_secs_f64() * refill_per_sec(self.limit)
+            >= self.limit.capacity as f64
+    }
+}
+
+fn refill_per_sec(limit: Limit) -> f64 {
+    limit.capacity as f64 / limit.period_sec as f64
+}
+
+fn sec_to_refill(missing_tokens: f64, limit: Limit) -> u64 {
+    (missing_tokens / refill_per_sec(limit)).ceil().max(0.0) as u64
+}
+
+#[async_trait]
+impl Store for InMemoryStore {
+    async fn take(&self, key: &str, limit: Limit) -> Result<Decision, Error> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().map_err(|_| Error::Unavailable)?;
+
+        if buckets.len() >= CAPACITY && !buckets.contains_key(key) {
+            buckets.retain(|_, bucket| !bucket.is_full(now));
+        }
+        let bucket = buckets.entry(key.to_owned()).or_insert(Bucket {
+            tokens: limit.capacity as f64,
+            updated_at: now,
+            limit,
+        });
+        // Limits may have changed since the bucket was made
+        bucket.limit = limit;
+        bucket.refill(now);
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        Ok(Decision {
+            allowed,
+            remaining: bucket.tokens.floor() as u32,
+            reset_sec: sec_to_refill(limit.capacity as f64 - bucket.tokens, limit),
+            retry_after_sec: if allowed { 0 } else { sec_to_refill(1.0 - bucket.tokens, limit) },
+        })
+    }
+}
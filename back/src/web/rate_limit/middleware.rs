@@ -0,0 +1,130 @@
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::{
+        header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+        StatusCode,
+    },
+    web, Error as ActixError, HttpRequest, HttpResponse,
+};
+use serde_json::json;
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+    rc::Rc,
+};
+
+use super::{Decision, Limit, RateLimit};
+use crate::core::users::{api_key, session};
+use crate::web::api::{users::utils_auth, ApiState};
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+    group: RateLimit,
+}
+
+impl<S> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse, Error = ActixError> + 'static,
+{
+    type Response = ServiceResponse;
+    type Error = ActixError;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service: Rc::new(service),
+            group: *self,
+        }))
+    }
+}
+
+impl<S> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse, Error = ActixError> + 'static,
+{
+    type Response = ServiceResponse;
+    type Error = ActixError;
+    type Future = Pin<Box<dyn Future<Output = Result<ServiceResponse, ActixError>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let group = self.group;
+
+        Box::pin(async move {
+            let api_state = req
+                .app_data::<web::Data<ApiState>>()
+                .expect("ApiState not registered")
+                .clone();
+            let limiter = &api_state.rate_limiter;
+            let limit = match limiter.limits.of(group) {
+                Some(limit) => limit,
+                None => return service.call(req).await,
+            };
+
+            let key = format!("{}:{}", group.name(), client_key(req.parts_mut().0, &api_state).await);
+            let decision = match limiter.store.take(&key, limit).await {
+                Ok(decision) => decision,
+                // Better to let requests through than to take the site down
+                // with the store
+                Err(_) => return service.call(req).await,
+            };
+
+            if !decision.allowed {
+                let mut response = HttpResponse::build(StatusCode::TOO_MANY_REQUESTS)
+                    .insert_header((RETRY_AFTER, decision.retry_after_sec.to_string()))
+                    .content_type("application/json")
+                    .body(json!({ "retry_after": decision.retry_after_sec }).to_string());
+                insert_headers(response.headers_mut(), limit, decision);
+                return Ok(req.into_response(response));
+            }
+
+            let mut response = service.call(req).await?;
+            insert_headers(response.headers_mut(), limit, decision);
+            Ok(response)
+        })
+    }
+}
+
+// Authenticated users and API keys get their own buckets, wherever they come
+// from. Others share the ones of their address, as do tokens of revoked
+// sessions and unknown keys, which would otherwise each get a fresh bucket.
+async fn client_key(req: &HttpRequest, api_state: &ApiState) -> String {
+    match utils_auth::credentials(req) {
+        Ok(utils_auth::Credentials::AccessToken(claims)) => {
+            let status = session::check(&claims, &api_state.session_cache, &api_state.db_conn_pool).await;
+            if let Ok(session::ClaimsStatus::Valid) = status {
+                return format!("user:{}", claims.id);
+            }
+        }
+        Ok(utils_auth::Credentials::ApiKey(key)) => {
+            if let Ok(Some(key_id)) = api_key::find_usable_id(&key, &api_state.db_conn_pool).await {
+                return format!("key:{}", key_id);
+            }
+        }
+        Err(_) => (),
+    }
+    match api_state.trusted_proxies.client_ip(req) {
+        Some(ip) => format!("ip:{}", ip),
+        None => "ip:unknown".to_owned(),
+    }
+}
+
+// As drafted by the IETF httpapi working group
+fn insert_headers(headers: &mut HeaderMap, limit: Limit, decision: Decision) {
+    headers.insert(
+        HeaderName::from_static("ratelimit-limit"),
+        HeaderValue::from(limit.capacity),
+    );
+    headers.insert(
+        HeaderName::from_static("ratelimit-remaining"),
+        HeaderValue::from(decision.remaining),
+    );
+    headers.insert(
+        HeaderName::from_static("ratelimit-reset"),
+        HeaderValue::from(decision.reset_sec),
+    );
+}
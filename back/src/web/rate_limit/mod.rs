@@ -0,0 +1,138 @@
+use async_trait::async_trait;
+use std::{str::FromStr, sync::Arc};
+
+mod config;
+mod memory;
+mod middleware;
+
+pub use config::{from_env, ConfigError};
+pub use memory::InMemoryStore;
+
+// Routes sharing a limit, used as their middleware:
+// #[post("/auth/login", wrap = "RateLimit::Login")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RateLimit {
+    Registration,
+    Login,
+    Search,
+    JokeCreation,
+}
+
+// Token bucket holding up to capacity requests, refilled in full every
+// period_sec
+#[derive(Clone, Copy, Debug)]
+pub struct Limit {
+    pub capacity: u32,
+    pub period_sec: u64,
+}
+
+// Groups without a limit are not limited
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    pub registration: Option<Limit>,
+    pub login: Option<Limit>,
+    pub search: Option<Limit>,
+    pub joke_creation: Option<Limit>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decision {
+    pub allowed: bool,
+    // Requests left once this one is counted
+    pub remaining: u32,
+    // Until the bucket is full again
+    pub reset_sec: u64,
+    // Until the next request is allowed, 0 when it already is
+    pub retry_after_sec: u64,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Unavailable,
+}
+
+// Where buckets live, shared stores letting instances enforce limits together
+#[async_trait]
+pub trait Store: Send + Sync {
+    // Takes a request off the bucket of key, if there is one left
+    async fn take(&self, key: &str, limit: Limit) -> Result<Decision, Error>;
+}
+
+#[derive(Clone)]
+pub struct Limiter {
+    pub limits: Limits,
+    pub store: Arc<dyn Store>,
+}
+
+impl RateLimit {
+    fn name(&self) -> &'static str {
+        match self {
+            RateLimit::Registration => "registration",
+            RateLimit::Login => "login",
+            RateLimit::Search => "search",
+            RateLimit::JokeCreation => "joke_creation",
+        }
+    }
+}
+
+impl Limit {
+    pub const fn per_minute(capacity: u32) -> Self {
+        Limit { capacity, period_sec: 60 }
+    }
+
+    pub const fn per_hour(capacity: u32) -> Self {
+        Limit { capacity, period_sec: 60 * 60 }
+    }
+}
+
+// Such as "10/minute" or "5/hour"
+impl FromStr for Limit {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (capacity, period) = value.split_once('/').ok_or(())?;
+        let capacity = capacity.trim().parse().map_err(|_| ())?;
+        match period.trim() {
+            "minute" => Ok(Limit::per_minute(capacity)),
+            "hour" => Ok(Limit::per_hour(capacity)),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Limits {
+    pub const fn none() -> Self {
+        Limits {
+            registration: None,
+            login: None,
+            search: None,
+            joke_creation: None,
+        }
+    }
+
+    fn of(&self, group: RateLimit) -> Option<Limit> {
+        match group {
+            RateLimit::Registration => self.registration,
+            RateLimit::Login => self.login,
+            RateLimit::Search => self.search,
+            RateLimit::JokeCreation => self.joke_creation,
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            registration: Some(Limit::per_hour(5)),
+            login: Some(Limit::per_minute(10)),
+            search: Some(Limit::per_minute(60)),
+            joke_creation: Some(Limit::per_hour(20)),
+        }
+    }
+}
+
+impl Limiter {
+    pub fn new(limits: Limits, store: Arc<dyn Store>) -> Self {
+        Limiter { limits, store }
+    }
+}
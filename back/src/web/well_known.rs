@@ -0,0 +1,13 @@
+use crate::core::jwt_keys;
+use actix_web::{get, http::StatusCode, HttpResponse};
+use serde_json::json;
+
+// Public keys our JWTs are signed with, for other services to check them
+#[get("/.well-known/jwks.json")]
+async fn jwks() -> HttpResponse {
+    HttpResponse::build(StatusCode::OK)
+        .content_type("application/json")
+        // Short enough for rotated keys to be picked up before they sign
+        .insert_header(("Cache-Control", "max-age=300"))
+        .body(json!(jwt_keys::get().jwks()).to_string())
+}
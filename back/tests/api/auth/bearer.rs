@@ -0,0 +1,64 @@
+use crate::api::{
+    bearer, get, insert_test_user, spawn_app, users::create_user_and_login_with_username,
+    TestApp,
+};
+use camion::core::users::Role;
+use reqwest::{Client as HttpClient, Response, StatusCode};
+use serde_json::json;
+
+async fn get_settings(app: &TestApp, authorization: &str) -> Response {
+    HttpClient::new()
+        .get(format!("{}/api/settings", app.url))
+        .header("Authorization", authorization)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[actix_rt::test]
+async fn tokens_must_come_with_the_bearer_scheme() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "admin", "a0@test.fr", "pass", &Role::Admin).await;
+
+    let (status_code, body) = get(&app, "/api/users/admin", vec![("Authorization", &jwt)]).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"], json!("AuthorizationParsing"));
+
+    let (status_code, body) = get(&app, "/api/users/admin", vec![("Authorization", &format!("Basic {}", jwt))]).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"], json!("AuthorizationParsing"));
+
+    let (status_code, _) = get(&app, "/api/users/admin", vec![("Authorization", &format!("bearer {}", jwt))]).await;
+    assert_eq!(status_code, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn invalid_tokens_are_refused_even_where_anonymous_is_welcome() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+
+    let (status_code, _) = get(&app, "/api/users/Anicet", vec![]).await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (status_code, body) = get(&app, "/api/users/Anicet", vec![("Authorization", &bearer("garbage"))]).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"], json!("InvalidToken"));
+}
+
+#[actix_rt::test]
+async fn unknown_requesters_get_401_and_known_but_unallowed_ones_403() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author).await;
+
+    let res = get_settings(&app, "Bearer garbage").await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(res.headers()["WWW-Authenticate"], "Bearer");
+
+    let res = get_settings(&app, &bearer(&jwt)).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    assert!(res.headers().get("WWW-Authenticate").is_none());
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["error"], json!("RoleNotAllowed"));
+}
@@ -0,0 +1,110 @@
+use camion::core::users::Role;
+use crate::api::{
+    bearer, auth::{login::login, refresh::refresh},
+    get, insert_test_user, post_json, spawn_app, TestApp,
+};
+use reqwest::StatusCode;
+use serde_json::json;
+
+async fn change_password(
+    app: &TestApp,
+    jwt: &str,
+    current_password: &str,
+    new_password: &str,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    post_json(
+        app,
+        "/api/auth/password",
+        json!({
+            "current_password": current_password,
+            "new_password": new_password
+        }),
+        vec![("Authorization", &bearer(jwt))],
+    )
+    .await
+}
+
+#[actix_rt::test]
+async fn the_new_password_replaces_the_old_one() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+    let (_, tokens) = login(&app, "Anicet", "pass").await;
+
+    let (status_code, body) = change_password(&app, tokens["token"].as_str().unwrap(), "pass", "N3w-password").await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["success"], json!(true));
+
+    let (status_code, _) = login(&app, "Anicet", "pass").await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    let (status_code, _) = login(&app, "Anicet", "N3w-password").await;
+    assert_eq!(status_code, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn requires_the_current_password() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+    let (_, tokens) = login(&app, "Anicet", "pass").await;
+
+    let (status_code, body) = change_password(&app, tokens["token"].as_str().unwrap(), "wrong", "N3w-password").await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("InvalidPassword"));
+
+    let (status_code, _) = login(&app, "Anicet", "pass").await;
+    assert_eq!(status_code, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn weak_passwords_are_refused() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+    let (_, tokens) = login(&app, "Anicet", "pass").await;
+
+    let (status_code, body) = change_password(&app, tokens["token"].as_str().unwrap(), "pass", "weak").await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["success"], json!(false));
+    assert!(body["issues"]["password"]
+        .as_array()
+        .unwrap()
+        .contains(&json!("NotLongEnough")));
+
+    let (status_code, _) = login(&app, "Anicet", "pass").await;
+    assert_eq!(status_code, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn only_the_current_session_survives() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+    let (_, current) = login(&app, "Anicet", "pass").await;
+    let (_, other) = login(&app, "Anicet", "pass").await;
+
+    change_password(&app, current["token"].as_str().unwrap(), "pass", "N3w-password").await;
+
+    let (status_code, body) = get(&app, "/api/users/Anicet", vec![("Authorization", &bearer(current["token"].as_str().unwrap()))]).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"], json!("OutdatedToken"));
+    let (status_code, _) = refresh(&app, current["refresh_token"].as_str().unwrap()).await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (status_code, body) = refresh(&app, other["refresh_token"].as_str().unwrap()).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("RevokedSession"));
+}
+
+#[actix_rt::test]
+async fn anonymous_cannot_change_password() {
+    let app = spawn_app().await;
+
+    let (status_code, _) = post_json(
+        &app,
+        "/api/auth/password",
+        json!({
+            "current_password": "pass",
+            "new_password": "N3w-password"
+        }),
+        vec![],
+    )
+    .await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,126 @@
+use crate::api::{
+    bearer, auth::login::login, get, post_json, spawn_app, TestApp,
+};
+use reqwest::StatusCode;
+use serde_json::json;
+
+async fn register_and_login(app: &TestApp, username: &str, email: &str) -> String {
+    post_json(
+        app,
+        "/api/auth/register",
+        json!({
+            "username": username,
+            "email": email,
+            "password": "superPass2021'-"
+        }),
+        vec![],
+    )
+    .await;
+    let (_, tokens) = login(app, username, "superPass2021'-").await;
+    tokens["token"].as_str().unwrap().to_owned()
+}
+
+async fn resend(app: &TestApp, jwt: &str) -> (reqwest::StatusCode, serde_json::Value) {
+    post_json(app, "/api/auth/email-verification", json!({}), vec![("Authorization", &bearer(jwt))]).await
+}
+
+fn last_link_sent_to(app: &TestApp, email: &str) -> String {
+    let mails = app.mailer().sent_to(email);
+    let text = &mails.last().expect("no mail sent").text;
+    let link = text
+        .lines()
+        .find(|line| line.contains("/api/auth/email-verification/confirm?token="))
+        .expect("no link in mail");
+    link[link.find("/api/").unwrap()..].trim().to_owned()
+}
+
+async fn allow_resending(app: &TestApp) {
+    sqlx::query!("UPDATE users SET email_verification_sent_at = NOW() - INTERVAL '1 hour'")
+        .execute(&app.db_conn_pool)
+        .await
+        .unwrap();
+}
+
+#[actix_rt::test]
+async fn registering_mails_a_link_that_verifies_the_email() {
+    let app = spawn_app().await;
+    let jwt = register_and_login(&app, "Anicet", "anicet@mail.com").await;
+
+    let (_, body) = get(&app, "/api/users/Anicet", vec![("Authorization", &bearer(&jwt))]).await;
+    assert_eq!(body["email_verified"], json!(false));
+
+    let (status_code, body) = get(&app, &last_link_sent_to(&app, "anicet@mail.com"), vec![]).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["verified"], json!(true));
+
+    let (_, body) = get(&app, "/api/users/Anicet", vec![("Authorization", &bearer(&jwt))]).await;
+    assert_eq!(body["email_verified"], json!(true));
+}
+
+#[actix_rt::test]
+async fn forged_links_are_refused() {
+    let app = spawn_app().await;
+
+    let (status_code, body) = get(&app, "/api/auth/email-verification/confirm?token=forged", vec![]).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("InvalidVerificationToken"));
+}
+
+#[actix_rt::test]
+async fn links_only_hold_for_the_email_they_were_sent_to() {
+    let app = spawn_app().await;
+    let jwt = register_and_login(&app, "Anicet", "anicet@mail.com").await;
+    let link = last_link_sent_to(&app, "anicet@mail.com");
+
+    sqlx::query!("UPDATE users SET email = 'other@mail.com'")
+        .execute(&app.db_conn_pool)
+        .await
+        .unwrap();
+
+    let (status_code, body) = get(&app, &link, vec![]).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("InvalidVerificationToken"));
+
+    let (_, body) = get(&app, "/api/users/Anicet", vec![("Authorization", &bearer(&jwt))]).await;
+    assert_eq!(body["email_verified"], json!(false));
+}
+
+#[actix_rt::test]
+async fn resending_is_throttled() {
+    let app = spawn_app().await;
+    let jwt = register_and_login(&app, "Anicet", "anicet@mail.com").await;
+
+    let (status_code, body) = resend(&app, &jwt).await;
+    assert_eq!(status_code, StatusCode::TOO_MANY_REQUESTS);
+    assert!(body["retry_after"].as_i64().unwrap() > 0);
+    assert_eq!(app.mailer().sent_to("anicet@mail.com").len(), 1);
+
+    allow_resending(&app).await;
+    let (status_code, _) = resend(&app, &jwt).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(app.mailer().sent_to("anicet@mail.com").len(), 2);
+
+    let (status_code, body) = get(&app, &last_link_sent_to(&app, "anicet@mail.com"), vec![]).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["verified"], json!(true));
+}
+
+#[actix_rt::test]
+async fn verified_emails_get_no_more_links() {
+    let app = spawn_app().await;
+    let jwt = register_and_login(&app, "Anicet", "anicet@mail.com").await;
+    get(&app, &last_link_sent_to(&app, "anicet@mail.com"), vec![]).await;
+    allow_resending(&app).await;
+
+    let (status_code, body) = resend(&app, &jwt).await;
+    assert_eq!(status_code, StatusCode::CONFLICT);
+    assert_eq!(body["reason"], json!("AlreadyVerified"));
+}
+
+#[actix_rt::test]
+async fn anonymous_cannot_ask_for_a_link() {
+    let app = spawn_app().await;
+
+    let (status_code, _) = post_json(&app, "/api/auth/email-verification", json!({}), vec![]).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,160 @@
+use crate::api::{
+    bearer, get, spawn_app, users::create_user_and_login_with_username, TestApp,
+};
+use camion::core::users::Role;
+use data_encoding::BASE64URL_NOPAD;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use reqwest::StatusCode;
+use serde_json::json;
+use std::env;
+
+const RETIRED_KEY: &str = include_str!("../../keys/retired.pem");
+
+fn payload(jwt: &str) -> serde_json::Value {
+    let payload = jwt.split('.').nth(1).unwrap();
+    serde_json::from_slice(&BASE64URL_NOPAD.decode(payload.as_bytes()).unwrap()).unwrap()
+}
+
+async fn jwks(app: &TestApp) -> Vec<serde_json::Value> {
+    let (status_code, body) = get(app, "/.well-known/jwks.json", vec![]).await;
+    assert_eq!(status_code, StatusCode::OK);
+    body["keys"].as_array().unwrap().clone()
+}
+
+// Through a route any logged in user may call
+async fn status_with_token(app: &TestApp, jwt: &str) -> StatusCode {
+    get(app, "/api/users/me/api-keys", vec![("Authorization", &bearer(jwt))]).await.0
+}
+
+#[actix_rt::test]
+async fn access_tokens_are_checked_with_the_published_keys() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "Anicet", "a0@test.fr", "pass", &Role::None).await;
+
+    let keys = jwks(&app).await;
+    assert_eq!(keys.len(), 2);
+    assert!(keys.iter().all(|key| key["kty"] == "RSA" && key["alg"] == "RS256"));
+    assert_ne!(keys[0]["kid"], keys[1]["kid"]);
+
+    let header = jsonwebtoken::decode_header(&jwt).unwrap();
+    assert_eq!(header.alg, Algorithm::RS256);
+    let key = keys
+        .iter()
+        .find(|key| Some(key["kid"].as_str().unwrap()) == header.kid.as_deref())
+        .unwrap();
+
+    let mut validation = Validation::new(Algorithm::RS256);
+    validation.iss = Some("camion".to_owned());
+    validation.set_audience(&["camion"]);
+    let claims = jsonwebtoken::decode::<serde_json::Value>(
+        &jwt,
+        &DecodingKey::from_rsa_components(key["n"].as_str().unwrap(), key["e"].as_str().unwrap()),
+        &validation,
+    )
+    .unwrap()
+    .claims;
+    assert_eq!(claims["iss"], json!("camion"));
+    assert_eq!(claims["aud"], json!("camion"));
+}
+
+#[actix_rt::test]
+async fn tokens_of_retired_keys_are_accepted_until_they_expire() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "Anicet", "a0@test.fr", "pass", &Role::None).await;
+    let signing_kid = jsonwebtoken::decode_header(&jwt).unwrap().kid.unwrap();
+    let retired_kid = jwks(&app)
+        .await
+        .into_iter()
+        .map(|key| key["kid"].as_str().unwrap().to_owned())
+        .find(|kid| *kid != signing_kid)
+        .unwrap();
+    let claims = payload(&jwt);
+    let retired_key = EncodingKey::from_rsa_pem(RETIRED_KEY.as_bytes()).unwrap();
+    let sign = |kid: &str, claims: &serde_json::Value| {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(kid.to_owned());
+        jsonwebtoken::encode(&header, claims, &retired_key).unwrap()
+    };
+
+    assert_eq!(status_with_token(&app, &sign(&retired_kid, &claims)).await, StatusCode::OK);
+
+    // Under the kid of the signing key, the signature does not match
+    assert_eq!(status_with_token(&app, &sign(&signing_kid, &claims)).await, StatusCode::UNAUTHORIZED);
+
+    let mut expired = claims.clone();
+    expired["exp"] = json!(chrono::Utc::now().timestamp() - 3600);
+    assert_eq!(status_with_token(&app, &sign(&retired_kid, &expired)).await, StatusCode::UNAUTHORIZED);
+}
+
+#[actix_rt::test]
+async fn tokens_for_another_issuer_or_audience_are_refused() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "Anicet", "a0@test.fr", "pass", &Role::None).await;
+    let retired_kid = jwks(&app).await[1]["kid"].as_str().unwrap().to_owned();
+    let retired_key = EncodingKey::from_rsa_pem(RETIRED_KEY.as_bytes()).unwrap();
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(retired_kid);
+
+    for (claim, value) in [("iss", "someone-else"), ("aud", "someone-else")] {
+        let mut claims = payload(&jwt);
+        claims[claim] = json!(value);
+        let forged = jsonwebtoken::encode(&header, &claims, &retired_key).unwrap();
+        let (status_code, body) =
+            get(&app, "/api/users/me/api-keys", vec![("Authorization", &bearer(&forged))]).await;
+        assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"], json!("InvalidToken"));
+    }
+}
+
+#[actix_rt::test]
+async fn tokens_signed_with_the_secret_are_still_accepted() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "Anicet", "a0@test.fr", "pass", &Role::None).await;
+    let secret = env::var("JWT_SECRET_KEY").unwrap();
+
+    let signed = jsonwebtoken::encode(
+        &Header::new(Algorithm::HS512),
+        &payload(&jwt),
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )
+    .unwrap();
+    assert_eq!(status_with_token(&app, &signed).await, StatusCode::OK);
+
+    let forged = jsonwebtoken::encode(
+        &Header::new(Algorithm::HS512),
+        &payload(&jwt),
+        &EncodingKey::from_secret(b"guessed"),
+    )
+    .unwrap();
+    assert_eq!(status_with_token(&app, &forged).await, StatusCode::UNAUTHORIZED);
+}
+
+#[actix_rt::test]
+async fn legacy_tokens_of_the_secret_need_no_issuer_nor_audience() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "Anicet", "a0@test.fr", "pass", &Role::None).await;
+    let secret = env::var("JWT_SECRET_KEY").unwrap();
+    let sign = |claims: &serde_json::Value| {
+        jsonwebtoken::encode(&Header::new(Algorithm::HS512), claims, &EncodingKey::from_secret(secret.as_bytes()))
+            .unwrap()
+    };
+
+    let mut legacy = payload(&jwt);
+    legacy.as_object_mut().unwrap().remove("iss");
+    legacy.as_object_mut().unwrap().remove("aud");
+    assert_eq!(status_with_token(&app, &sign(&legacy)).await, StatusCode::OK);
+
+    // Those telling one still have to be ours
+    let mut other_issuer = legacy.clone();
+    other_issuer["iss"] = json!("someone-else");
+    assert_eq!(status_with_token(&app, &sign(&other_issuer)).await, StatusCode::UNAUTHORIZED);
+
+    let mut expired = legacy;
+    expired["exp"] = json!(chrono::Utc::now().timestamp() - 3600);
+    assert_eq!(status_with_token(&app, &sign(&expired)).await, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,174 @@
+use camion::core::users::{lockout, Role};
+use crate::api::{
+    bearer, auth::login::login, delete, insert_test_user, post_json, put_json, spawn_app,
+    spawn_app_with, users::create_user_and_login_with_username, TestApp, TestConfig,
+};
+use reqwest::StatusCode;
+use serde_json::json;
+
+async fn fail_logins(app: &TestApp, login_: &str, times: i32) {
+    for _ in 0..times {
+        let (status_code, _) = login(app, login_, "wrong").await;
+        assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    }
+}
+
+#[actix_rt::test]
+async fn accounts_get_locked_after_too_many_failures() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+    fail_logins(&app, "Anicet", lockout::ACCOUNT_FREE_FAILURES + 1).await;
+
+    // Even with the right password
+    let (status_code, body) = login(&app, "Anicet", "pass").await;
+    assert_eq!(status_code, StatusCode::TOO_MANY_REQUESTS);
+    let retry_after_sec = body["reason"]["Locked"]["retry_after_sec"].as_i64().unwrap();
+    assert!(retry_after_sec > 0 && retry_after_sec <= lockout::BASE_LOCK_SEC + 1);
+}
+
+#[actix_rt::test]
+async fn unknown_logins_get_locked_too() {
+    let app = spawn_app().await;
+    fail_logins(&app, "Nobody", lockout::ACCOUNT_FREE_FAILURES + 1).await;
+
+    let (status_code, body) = login(&app, "Nobody", "pass").await;
+    assert_eq!(status_code, StatusCode::TOO_MANY_REQUESTS);
+    assert!(body["reason"]["Locked"].is_object());
+}
+
+#[actix_rt::test]
+async fn succeeding_forgets_the_failures_of_the_account() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+    fail_logins(&app, "Anicet", lockout::ACCOUNT_FREE_FAILURES - 1).await;
+    let (status_code, _) = login(&app, "Anicet", "pass").await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    fail_logins(&app, "Anicet", lockout::ACCOUNT_FREE_FAILURES - 1).await;
+    let (status_code, _) = login(&app, "Anicet", "pass").await;
+    assert_eq!(status_code, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn addresses_get_locked_after_too_many_failures() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+    for i in 0..=lockout::IP_FREE_FAILURES {
+        fail_logins(&app, &format!("Nobody{}", i), 1).await;
+    }
+
+    let (status_code, _) = login(&app, "Anicet", "pass").await;
+    assert_eq!(status_code, StatusCode::TOO_MANY_REQUESTS);
+}
+
+async fn login_from(app: &TestApp, login_: &str, password: &str, forwarded_for: &str) -> StatusCode {
+    let (status_code, _) = post_json(
+        app,
+        "/api/auth/login",
+        json!({ "login": login_, "password": password }),
+        vec![("X-Forwarded-For", forwarded_for)],
+    )
+    .await;
+    status_code
+}
+
+#[actix_rt::test]
+async fn trusted_proxies_tell_the_addresses_of_clients() {
+    let app = spawn_app_with(TestConfig { trusted_proxies: vec!["127.0.0.1".parse().unwrap()], ..Default::default() }).await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+    for i in 0..=lockout::IP_FREE_FAILURES {
+        login_from(&app, &format!("Nobody{}", i), "wrong", "192.0.2.1").await;
+    }
+
+    assert_eq!(login_from(&app, "Anicet", "pass", "192.0.2.1").await, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(login_from(&app, "Anicet", "pass", "192.0.2.2").await, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn others_cannot_tell_the_addresses_of_clients() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+    for i in 0..=lockout::IP_FREE_FAILURES {
+        login_from(&app, &format!("Nobody{}", i), "wrong", &format!("192.0.2.{}", i)).await;
+    }
+
+    assert_eq!(login_from(&app, "Anicet", "pass", "192.0.2.200").await, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[actix_rt::test]
+async fn old_failures_are_purged() {
+    let app = spawn_app().await;
+    sqlx::query!(
+        "INSERT INTO login_failures ( subject, failures, last_failure_at ) VALUES ( 'login:old', 3, $1 )",
+        chrono::Utc::now().naive_utc() - chrono::Duration::minutes(lockout::FAILURE_MEMORY_MIN + 1)
+    )
+    .execute(&app.db_conn_pool)
+    .await
+    .unwrap();
+
+    fail_logins(&app, "Nobody", 1).await;
+
+    let remaining = sqlx::query!("SELECT subject FROM login_failures WHERE subject = 'login:old'")
+        .fetch_optional(&app.db_conn_pool)
+        .await
+        .unwrap();
+    assert!(remaining.is_none());
+}
+
+#[test]
+fn locks_get_longer_up_to_a_limit() {
+    assert_eq!(lockout::lock_duration_sec(1), lockout::BASE_LOCK_SEC);
+    assert_eq!(lockout::lock_duration_sec(2), lockout::BASE_LOCK_SEC * 2);
+    assert_eq!(lockout::lock_duration_sec(3), lockout::BASE_LOCK_SEC * 4);
+    assert_eq!(lockout::lock_duration_sec(1000), lockout::MAX_LOCK_SEC);
+}
+
+#[actix_rt::test]
+async fn admins_can_unlock_users() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "admin", "a0@test.fr", "pass", &Role::Admin).await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+    fail_logins(&app, "Anicet", lockout::ACCOUNT_FREE_FAILURES + 1).await;
+
+    let (status_code, _) = delete(&app, "/api/users/Anicet/lock", vec![("Authorization", &bearer(&jwt))]).await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (status_code, _) = login(&app, "Anicet", "pass").await;
+    assert_eq!(status_code, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn only_admins_can_unlock_users() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author).await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+    fail_logins(&app, "Anicet", lockout::ACCOUNT_FREE_FAILURES + 1).await;
+
+    let (status_code, _) = delete(&app, "/api/users/Anicet/lock", vec![("Authorization", &bearer(&jwt))]).await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+
+    let (status_code, _) = login(&app, "Anicet", "pass").await;
+    assert_eq!(status_code, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[actix_rt::test]
+async fn denial_reasons_can_be_made_generic() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "admin", "a0@test.fr", "pass", &Role::Admin).await;
+    let (status_code, _) = put_json(
+        &app,
+        "/api/settings",
+        json!({ "generic_login_denials": true }),
+        vec![("Authorization", &bearer(&jwt))],
+    )
+    .await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (_, body) = login(&app, "Nobody", "pass").await;
+    assert_eq!(body["reason"], json!("InvalidCredentials"));
+    let (_, body) = login(&app, "admin", "wrong").await;
+    assert_eq!(body["reason"], json!("InvalidCredentials"));
+}
@@ -78,4 +78,14 @@ async fn is_ambiguous_when_email_unknown_or_email_known_and_password_wrong() {
     let (status_code, body) = login(&app, "wrong@test.fr", password).await;
     assert_eq!(status_code, reqwest::StatusCode::UNAUTHORIZED);
     assert_eq!(body["reason"], json!("InvalidCredentials"));
-}
\ No newline at end of file
+}
+
+#[actix_rt::test]
+async fn hands_out_a_refresh_token_along_with_the_access_token() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "", "secret_password", &Role::None, &app.db_conn_pool).await;
+
+    let (_, body) = login(&app, "Anicet", "secret_password").await;
+    assert!(body["refresh_token"].is_string());
+    assert_ne!(body["refresh_token"], body["token"]);
+}
@@ -0,0 +1,56 @@
+use camion::core::users::Role;
+use crate::api::{
+    auth_headers, bearer, auth::{login::login, refresh::refresh},
+    get, insert_test_user, post_json, spawn_app, TestApp,
+};
+use reqwest::StatusCode;
+use serde_json::json;
+
+async fn logout(app: &TestApp, jwt: Option<&str>) -> (reqwest::StatusCode, serde_json::Value) {
+    post_json(app, "/api/auth/logout", json!({}), auth_headers(jwt.map(bearer).as_deref())).await
+}
+
+#[actix_rt::test]
+async fn revokes_the_access_and_refresh_tokens_of_the_session() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+    let (_, tokens) = login(&app, "Anicet", "pass").await;
+    let jwt = tokens["token"].as_str().unwrap();
+
+    let (status_code, _) = logout(&app, Some(jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (status_code, body) = get(&app, "/api/users/Anicet", vec![("Authorization", &bearer(jwt))]).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"], json!("RevokedSession"));
+
+    let (status_code, body) = refresh(&app, tokens["refresh_token"].as_str().unwrap()).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("RevokedSession"));
+}
+
+#[actix_rt::test]
+async fn leaves_other_sessions_alone() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+    let (_, first) = login(&app, "Anicet", "pass").await;
+    let (_, second) = login(&app, "Anicet", "pass").await;
+
+    logout(&app, first["token"].as_str()).await;
+
+    let (status_code, _) = get(
+        &app,
+        "/api/users/Anicet",
+        vec![("Authorization", &bearer(second["token"].as_str().unwrap()))]
+    )
+    .await;
+    assert_eq!(status_code, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn anonymous_cannot_logout() {
+    let app = spawn_app().await;
+
+    let (status_code, _) = logout(&app, None).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+}
@@ -1,2 +1,12 @@
 mod register;
-pub(crate) mod login;
\ No newline at end of file
+pub(crate) mod login;
+pub(crate) mod refresh;
+mod logout;
+mod change_password;
+mod password_reset;
+mod email_verification;
+mod two_factor;
+mod lockout;
+mod bearer;
+mod oidc;
+mod jwks;
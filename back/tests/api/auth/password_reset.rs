@@ -0,0 +1,156 @@
+use camion::core::users::Role;
+use crate::api::{
+    auth::{login::login, refresh::refresh},
+    insert_test_user, mails_sent_to, post_json, spawn_app, TestApp,
+};
+use reqwest::StatusCode;
+use serde_json::json;
+
+async fn request_reset(app: &TestApp, email: &str) -> (reqwest::StatusCode, serde_json::Value) {
+    post_json(app, "/api/auth/password-reset", json!({ "email": email }), vec![]).await
+}
+
+async fn reset(app: &TestApp, token: &str, new_password: &str) -> (reqwest::StatusCode, serde_json::Value) {
+    post_json(
+        app,
+        "/api/auth/password-reset/confirm",
+        json!({
+            "token": token,
+            "new_password": new_password
+        }),
+        vec![],
+    )
+    .await
+}
+
+// Of the nth mail sent to the address
+async fn token_sent_to(app: &TestApp, email: &str, nth: usize) -> String {
+    let mails = mails_sent_to(app, email, nth).await;
+    let body = &mails[nth - 1].text;
+    body.lines()
+        .map(str::trim)
+        .find(|line| line.len() == 64 && line.chars().all(|c| c.is_ascii_hexdigit()))
+        .expect("no token in mail")
+        .to_owned()
+}
+
+#[actix_rt::test]
+async fn a_mailed_token_sets_a_new_password() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "anicet@mail.com", "pass", &Role::None, &app.db_conn_pool).await;
+
+    let (status_code, _) = request_reset(&app, "anicet@mail.com").await;
+    assert_eq!(status_code, StatusCode::OK);
+    let token = token_sent_to(&app, "anicet@mail.com", 1).await;
+
+    let (status_code, body) = reset(&app, &token, "N3w-password").await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["success"], json!(true));
+
+    let (status_code, _) = login(&app, "Anicet", "pass").await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    let (status_code, _) = login(&app, "Anicet", "N3w-password").await;
+    assert_eq!(status_code, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn unknown_emails_get_the_same_answer_and_no_mail() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "anicet@mail.com", "pass", &Role::None, &app.db_conn_pool).await;
+
+    let known = request_reset(&app, "anicet@mail.com").await;
+    let unknown = request_reset(&app, "nobody@mail.com").await;
+
+    assert_eq!(known, unknown);
+    mails_sent_to(&app, "anicet@mail.com", 1).await;
+    actix_rt::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert_eq!(app.mailer().sent().len(), 1);
+}
+
+#[actix_rt::test]
+async fn tokens_are_single_use() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "anicet@mail.com", "pass", &Role::None, &app.db_conn_pool).await;
+    request_reset(&app, "anicet@mail.com").await;
+    let token = token_sent_to(&app, "anicet@mail.com", 1).await;
+
+    reset(&app, &token, "N3w-password").await;
+    let (status_code, body) = reset(&app, &token, "Other-passw0rd").await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("InvalidResetToken"));
+
+    let (status_code, _) = login(&app, "Anicet", "N3w-password").await;
+    assert_eq!(status_code, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn using_a_token_voids_the_other_ones() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "anicet@mail.com", "pass", &Role::None, &app.db_conn_pool).await;
+    request_reset(&app, "anicet@mail.com").await;
+    let first = token_sent_to(&app, "anicet@mail.com", 1).await;
+    request_reset(&app, "anicet@mail.com").await;
+    let second = token_sent_to(&app, "anicet@mail.com", 2).await;
+
+    reset(&app, &second, "N3w-password").await;
+    let (status_code, body) = reset(&app, &first, "Other-passw0rd").await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("InvalidResetToken"));
+}
+
+#[actix_rt::test]
+async fn expired_tokens_are_refused() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "anicet@mail.com", "pass", &Role::None, &app.db_conn_pool).await;
+    request_reset(&app, "anicet@mail.com").await;
+    let token = token_sent_to(&app, "anicet@mail.com", 1).await;
+
+    sqlx::query!("UPDATE password_reset_tokens SET expires_at = NOW() - INTERVAL '1 minute'")
+        .execute(&app.db_conn_pool)
+        .await
+        .unwrap();
+
+    let (status_code, body) = reset(&app, &token, "N3w-password").await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("InvalidResetToken"));
+}
+
+#[actix_rt::test]
+async fn unknown_tokens_are_refused() {
+    let app = spawn_app().await;
+
+    let (status_code, body) = reset(&app, "not-a-token", "N3w-password").await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("InvalidResetToken"));
+}
+
+#[actix_rt::test]
+async fn weak_passwords_do_not_use_up_the_token() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "anicet@mail.com", "pass", &Role::None, &app.db_conn_pool).await;
+    request_reset(&app, "anicet@mail.com").await;
+    let token = token_sent_to(&app, "anicet@mail.com", 1).await;
+
+    let (status_code, body) = reset(&app, &token, "weak").await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["success"], json!(false));
+
+    let (status_code, body) = reset(&app, &token, "N3w-password").await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["success"], json!(true));
+}
+
+#[actix_rt::test]
+async fn resetting_revokes_every_session() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "anicet@mail.com", "pass", &Role::None, &app.db_conn_pool).await;
+    let (_, tokens) = login(&app, "Anicet", "pass").await;
+    request_reset(&app, "anicet@mail.com").await;
+    let token = token_sent_to(&app, "anicet@mail.com", 1).await;
+
+    reset(&app, &token, "N3w-password").await;
+
+    let (status_code, body) = refresh(&app, tokens["refresh_token"].as_str().unwrap()).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("RevokedSession"));
+}
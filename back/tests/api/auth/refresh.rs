@@ -0,0 +1,102 @@
+use camion::core::users::Role;
+use crate::api::{bearer, auth::login::login, get, insert_test_user, post_json, spawn_app, TestApp};
+use reqwest::StatusCode;
+use serde_json::json;
+
+pub async fn refresh(app: &TestApp, refresh_token: &str) -> (reqwest::StatusCode, serde_json::Value) {
+    post_json(
+        app,
+        "/api/auth/refresh",
+        json!({ "refresh_token": refresh_token }),
+        vec![]
+    )
+    .await
+}
+
+async fn login_test_user(app: &TestApp) -> serde_json::Value {
+    insert_test_user("Anicet", "", "pass", &Role::Author, &app.db_conn_pool).await;
+    let (_, body) = login(app, "Anicet", "pass").await;
+    body
+}
+
+async fn get_user_data_request(app: &TestApp, jwt: &str) -> reqwest::StatusCode {
+    let (status_code, _) = get(app, "/api/users/Anicet", vec![("Authorization", &bearer(jwt))]).await;
+    status_code
+}
+
+#[actix_rt::test]
+async fn exchanges_a_refresh_token_for_new_tokens() {
+    let app = spawn_app().await;
+    let tokens = login_test_user(&app).await;
+
+    let (status_code, body) = refresh(&app, tokens["refresh_token"].as_str().unwrap()).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_ne!(body["refresh_token"], tokens["refresh_token"]);
+    assert_eq!(get_user_data_request(&app, body["token"].as_str().unwrap()).await, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn new_access_tokens_carry_the_current_role() {
+    let app = spawn_app().await;
+    let tokens = login_test_user(&app).await;
+    sqlx::query!("UPDATE users SET role = $1 WHERE username = 'Anicet'", Role::Admin as i32)
+        .execute(&app.db_conn_pool)
+        .await
+        .unwrap();
+
+    let (_, body) = refresh(&app, tokens["refresh_token"].as_str().unwrap()).await;
+    let (_, user) = get(
+        &app,
+        "/api/users/Anicet",
+        vec![("Authorization", &bearer(body["token"].as_str().unwrap()))]
+    )
+    .await;
+    assert_eq!(user["role"], json!("Admin"));
+}
+
+#[actix_rt::test]
+async fn rejects_unknown_refresh_tokens() {
+    let app = spawn_app().await;
+
+    let (status_code, body) = refresh(&app, "not a refresh token").await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("InvalidRefreshToken"));
+}
+
+#[actix_rt::test]
+async fn reusing_a_refresh_token_revokes_the_session() {
+    let app = spawn_app().await;
+    let tokens = login_test_user(&app).await;
+    let first_refresh_token = tokens["refresh_token"].as_str().unwrap();
+
+    let (_, rotated) = refresh(&app, first_refresh_token).await;
+
+    let (status_code, body) = refresh(&app, first_refresh_token).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("ReusedRefreshToken"));
+
+    let (status_code, body) = refresh(&app, rotated["refresh_token"].as_str().unwrap()).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("RevokedSession"));
+
+    for jwt in [&tokens["token"], &rotated["token"]] {
+        assert_eq!(
+            get_user_data_request(&app, jwt.as_str().unwrap()).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+}
+
+#[actix_rt::test]
+async fn rejects_expired_sessions() {
+    let app = spawn_app().await;
+    let tokens = login_test_user(&app).await;
+    sqlx::query!("UPDATE sessions SET expires_at = NOW() - INTERVAL '1 day'")
+        .execute(&app.db_conn_pool)
+        .await
+        .unwrap();
+
+    let (status_code, body) = refresh(&app, tokens["refresh_token"].as_str().unwrap()).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("ExpiredSession"));
+}
@@ -40,10 +40,10 @@ async fn registers_using_same_username_email_and_hashed_salted_password() {
 
     match res {
         Ok(record) => {
-            assert!(password_verify(&password.to_string(), &record.password));
+            assert!(password_verify(password, &record.password));
             assert_ne!(record.password, password_salt_and_hash(&password.to_string()).unwrap());
         },
-        Err(_) => assert!(false)
+        Err(_) => panic!()
     };
 }
 
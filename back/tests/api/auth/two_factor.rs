@@ -0,0 +1,309 @@
+use camion::core::{security, users::{lockout, Role}};
+use crate::api::{
+    bearer, auth::{login::login, refresh::refresh},
+    delete_json, get, insert_test_user,
+    jokes::create::{post_create_joke_request, valid_joke},
+    post_json, put_json, spawn_app, TestApp,
+};
+use chrono::Utc;
+use data_encoding::BASE32_NOPAD;
+use reqwest::StatusCode;
+use serde_json::json;
+
+fn current_step() -> u64 {
+    security::totp_step(Utc::now().timestamp() as u64)
+}
+
+fn code_at(secret: &str, step: u64) -> String {
+    let secret = BASE32_NOPAD.decode(secret.as_bytes()).unwrap();
+    format!("{:06}", security::totp(&secret, step))
+}
+
+async fn login_two_factor(app: &TestApp, two_factor_token: &str, code: &str) -> (reqwest::StatusCode, serde_json::Value) {
+    post_json(
+        app,
+        "/api/auth/login/two-factor",
+        json!({ "two_factor_token": two_factor_token, "code": code }),
+        vec![],
+    )
+    .await
+}
+
+// Returns the secret and the recovery codes
+async fn enable_two_factor(app: &TestApp, jwt: &str) -> (String, Vec<String>) {
+    let (status_code, body) = post_json(app, "/api/auth/two-factor/enrol", json!({}), vec![("Authorization", &bearer(jwt))]).await;
+    assert_eq!(status_code, StatusCode::OK);
+    let secret = body["secret"].as_str().unwrap().to_owned();
+
+    let (status_code, body) = post_json(
+        app,
+        "/api/auth/two-factor/confirm",
+        json!({ "code": code_at(&secret, current_step()) }),
+        vec![("Authorization", &bearer(jwt))],
+    )
+    .await;
+    assert_eq!(status_code, StatusCode::OK);
+    let recovery_codes = body["recovery_codes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|code| code.as_str().unwrap().to_owned())
+        .collect();
+    (secret, recovery_codes)
+}
+
+async fn pending_login(app: &TestApp, username: &str) -> String {
+    let (status_code, body) = login(app, username, "pass").await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["two_factor_required"], json!(true));
+    assert!(body.get("refresh_token").is_none());
+    body["two_factor_token"].as_str().unwrap().to_owned()
+}
+
+#[test]
+fn totp_matches_the_rfc_test_vectors() {
+    let secret = b"12345678901234567890";
+    assert_eq!(security::totp(secret, security::totp_step(59)), 287082);
+    assert_eq!(security::totp(secret, security::totp_step(1111111109)), 81804);
+    assert_eq!(security::totp(secret, security::totp_step(2000000000)), 279037);
+}
+
+#[actix_rt::test]
+async fn enrolment_gives_an_otpauth_uri() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet Ngrt", "", "pass", &Role::None, &app.db_conn_pool).await;
+    let (_, tokens) = login(&app, "Anicet Ngrt", "pass").await;
+    let jwt = tokens["token"].as_str().unwrap();
+
+    let (status_code, body) = post_json(&app, "/api/auth/two-factor/enrol", json!({}), vec![("Authorization", &bearer(jwt))]).await;
+    assert_eq!(status_code, StatusCode::OK);
+    let secret = body["secret"].as_str().unwrap();
+    assert_eq!(
+        body["otpauth_uri"],
+        json!(format!(
+            "otpauth://totp/Camion:Anicet%20Ngrt?secret={}&issuer=Camion&algorithm=SHA1&digits=6&period=30",
+            secret
+        ))
+    );
+
+    // Not enabled until confirmed
+    let (_, body) = login(&app, "Anicet Ngrt", "pass").await;
+    assert!(body["token"].is_string());
+}
+
+#[actix_rt::test]
+async fn confirming_requires_a_valid_code() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+    let (_, tokens) = login(&app, "Anicet", "pass").await;
+    let jwt = tokens["token"].as_str().unwrap();
+
+    let (status_code, body) = post_json(&app, "/api/auth/two-factor/confirm", json!({ "code": "123456" }), vec![("Authorization", &bearer(jwt))]).await;
+    assert_eq!(status_code, StatusCode::CONFLICT);
+    assert_eq!(body["reason"], json!("NotEnrolling"));
+
+    let (_, body) = post_json(&app, "/api/auth/two-factor/enrol", json!({}), vec![("Authorization", &bearer(jwt))]).await;
+    let secret = body["secret"].as_str().unwrap();
+    let wrong_code = code_at(secret, current_step() + 5);
+    let (status_code, body) = post_json(&app, "/api/auth/two-factor/confirm", json!({ "code": wrong_code }), vec![("Authorization", &bearer(jwt))]).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("InvalidCode"));
+}
+
+#[actix_rt::test]
+async fn login_then_needs_a_code() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+    let (_, tokens) = login(&app, "Anicet", "pass").await;
+    let (secret, recovery_codes) = enable_two_factor(&app, tokens["token"].as_str().unwrap()).await;
+    assert_eq!(recovery_codes.len(), 10);
+
+    let two_factor_token = pending_login(&app, "Anicet").await;
+
+    let (status_code, body) = login_two_factor(&app, &two_factor_token, &code_at(&secret, current_step() + 5)).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("InvalidCode"));
+
+    // The code of the confirmation was used already
+    let (status_code, body) = login_two_factor(&app, &two_factor_token, &code_at(&secret, current_step() + 1)).await;
+    assert_eq!(status_code, StatusCode::OK);
+    let jwt = body["token"].as_str().unwrap();
+    let (status_code, _) = get(&app, "/api/users/Anicet", vec![("Authorization", &bearer(jwt))]).await;
+    assert_eq!(status_code, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn codes_cannot_be_replayed() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+    let (_, tokens) = login(&app, "Anicet", "pass").await;
+    let (secret, _) = enable_two_factor(&app, tokens["token"].as_str().unwrap()).await;
+    let code = code_at(&secret, current_step() + 1);
+
+    let two_factor_token = pending_login(&app, "Anicet").await;
+    let (status_code, _) = login_two_factor(&app, &two_factor_token, &code).await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let two_factor_token = pending_login(&app, "Anicet").await;
+    let (status_code, body) = login_two_factor(&app, &two_factor_token, &code).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("InvalidCode"));
+}
+
+#[actix_rt::test]
+async fn recovery_codes_work_once() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+    let (_, tokens) = login(&app, "Anicet", "pass").await;
+    let (_, recovery_codes) = enable_two_factor(&app, tokens["token"].as_str().unwrap()).await;
+
+    let two_factor_token = pending_login(&app, "Anicet").await;
+    let (status_code, _) = login_two_factor(&app, &two_factor_token, &recovery_codes[3].to_uppercase()).await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (status_code, body) = login_two_factor(&app, &two_factor_token, &recovery_codes[3]).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("InvalidCode"));
+}
+
+#[actix_rt::test]
+async fn other_tokens_are_not_two_factor_tokens() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+    let (_, tokens) = login(&app, "Anicet", "pass").await;
+    let (secret, _) = enable_two_factor(&app, tokens["token"].as_str().unwrap()).await;
+
+    let (status_code, body) = login_two_factor(&app, tokens["token"].as_str().unwrap(), &code_at(&secret, current_step() + 1)).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("InvalidTwoFactorToken"));
+}
+
+#[actix_rt::test]
+async fn pending_tokens_are_spent_after_too_many_wrong_codes() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+    let (_, tokens) = login(&app, "Anicet", "pass").await;
+    let (secret, _) = enable_two_factor(&app, tokens["token"].as_str().unwrap()).await;
+
+    let two_factor_token = pending_login(&app, "Anicet").await;
+    for _ in 0..lockout::PENDING_LOGIN_MAX_ATTEMPTS {
+        let (status_code, body) = login_two_factor(&app, &two_factor_token, "000000").await;
+        assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["reason"], json!("InvalidCode"));
+    }
+
+    let (status_code, body) = login_two_factor(&app, &two_factor_token, &code_at(&secret, current_step() + 1)).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("InvalidTwoFactorToken"));
+
+    // A new token is given its own attempts
+    let two_factor_token = pending_login(&app, "Anicet").await;
+    let (status_code, _) = login_two_factor(&app, &two_factor_token, &code_at(&secret, current_step() + 1)).await;
+    assert_eq!(status_code, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn disabling_requires_the_password_and_a_code() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+    let (_, tokens) = login(&app, "Anicet", "pass").await;
+    let jwt = tokens["token"].as_str().unwrap();
+    let (_, recovery_codes) = enable_two_factor(&app, jwt).await;
+
+    let (status_code, body) = delete_json(
+        &app,
+        "/api/auth/two-factor",
+        json!({ "password": "wrong", "code": recovery_codes[0] }),
+        vec![("Authorization", &bearer(jwt))],
+    )
+    .await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("InvalidPassword"));
+
+    let (status_code, body) = delete_json(
+        &app,
+        "/api/auth/two-factor",
+        json!({ "password": "pass", "code": "000000" }),
+        vec![("Authorization", &bearer(jwt))],
+    )
+    .await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("InvalidCode"));
+
+    let (status_code, _) = delete_json(
+        &app,
+        "/api/auth/two-factor",
+        json!({ "password": "pass", "code": recovery_codes[0] }),
+        vec![("Authorization", &bearer(jwt))],
+    )
+    .await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (_, body) = login(&app, "Anicet", "pass").await;
+    assert!(body["token"].is_string());
+}
+
+#[actix_rt::test]
+async fn admins_may_be_required_to_use_two_factors() {
+    let app = spawn_app().await;
+    insert_test_user("admin", "", "pass", &Role::Admin, &app.db_conn_pool).await;
+    let (_, tokens) = login(&app, "admin", "pass").await;
+    let jwt = tokens["token"].as_str().unwrap();
+
+    let (status_code, _) = put_json(&app, "/api/settings", json!({ "admins_need_two_factor": true }), vec![("Authorization", &bearer(jwt))]).await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (status_code, body) = get(&app, "/api/settings", vec![("Authorization", &bearer(jwt))]).await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"], json!("TwoFactorRequired"));
+
+    // Setting it up stays possible, and counts for the session doing it
+    let (secret, _) = enable_two_factor(&app, jwt).await;
+    let (_, tokens) = refresh(&app, tokens["refresh_token"].as_str().unwrap()).await;
+    let (status_code, _) = get(&app, "/api/settings", vec![("Authorization", &bearer(tokens["token"].as_str().unwrap()))]).await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let two_factor_token = pending_login(&app, "admin").await;
+    let (_, tokens) = login_two_factor(&app, &two_factor_token, &code_at(&secret, current_step() + 1)).await;
+    let jwt = tokens["token"].as_str().unwrap();
+    let (status_code, _) = get(&app, "/api/settings", vec![("Authorization", &bearer(jwt))]).await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (status_code, body) = delete_json(&app, "/api/auth/two-factor", json!({ "password": "pass", "code": "000000" }), vec![("Authorization", &bearer(jwt))]).await;
+    assert_eq!(status_code, StatusCode::CONFLICT);
+    assert_eq!(body["reason"], json!("MandatoryForRole"));
+}
+
+async fn create_joke_writing_key(app: &TestApp, jwt: &str) -> String {
+    let (_, body) = post_json(
+        app,
+        "/api/users/me/api-keys",
+        json!({ "name": "Bot", "scopes": ["write_jokes"] }),
+        vec![("Authorization", &bearer(jwt))],
+    )
+    .await;
+    body["created_api_key"]["key"].as_str().unwrap().to_owned()
+}
+
+#[actix_rt::test]
+async fn admin_api_keys_need_two_factors_when_their_session_needed_them() {
+    let app = spawn_app().await;
+    insert_test_user("admin", "", "pass", &Role::Admin, &app.db_conn_pool).await;
+    let (_, tokens) = login(&app, "admin", "pass").await;
+    let jwt = tokens["token"].as_str().unwrap();
+    let key_without_two_factor = create_joke_writing_key(&app, jwt).await;
+    enable_two_factor(&app, jwt).await;
+    let (_, tokens) = refresh(&app, tokens["refresh_token"].as_str().unwrap()).await;
+    let jwt = tokens["token"].as_str().unwrap();
+    let key_with_two_factor = create_joke_writing_key(&app, jwt).await;
+
+    let (status_code, _) = put_json(&app, "/api/settings", json!({ "admins_need_two_factor": true }), vec![("Authorization", &bearer(jwt))]).await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (status_code, body) = post_create_joke_request(&app, valid_joke(), Some(&key_without_two_factor)).await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"], json!("TwoFactorRequired"));
+
+    let (status_code, _) = post_create_joke_request(&app, valid_joke(), Some(&key_with_two_factor)).await;
+    assert_eq!(status_code, StatusCode::OK);
+}
@@ -0,0 +1,356 @@
+use crate::api::{
+    auth_headers, bearer, delete, get,
+    jokes::{
+        create::{post_create_joke_request, valid_joke},
+        create_joke_as,
+    },
+    post_json, put_json, spawn_app,
+    users::create_user_and_login_with_username,
+    TestApp,
+};
+use camion::core::users::Role;
+use reqwest::StatusCode;
+use serde_json::json;
+
+async fn post_comment_request(
+    app: &TestApp,
+    joke_id: i64,
+    content: &str,
+    reply_to: Option<i64>,
+    jwt: Option<&str>,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    post_json(
+        app,
+        &format!("/api/jokes/{}/comments", joke_id),
+        json!({ "comment": { "content": content }, "reply_to": reply_to }),
+        auth_headers(jwt.map(bearer).as_deref()),
+    )
+    .await
+}
+
+async fn list_comments_request(
+    app: &TestApp,
+    joke_id: i64,
+    query: &str,
+    jwt: Option<&str>,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    get(
+        app,
+        &format!("/api/jokes/{}/comments{}", joke_id, query),
+        auth_headers(jwt.map(bearer).as_deref()),
+    )
+    .await
+}
+
+async fn put_comment_request(
+    app: &TestApp,
+    joke_id: i64,
+    comment_id: i64,
+    content: &str,
+    jwt: Option<&str>,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    put_json(
+        app,
+        &format!("/api/jokes/{}/comments/{}", joke_id, comment_id),
+        json!({ "comment": { "content": content } }),
+        auth_headers(jwt.map(bearer).as_deref()),
+    )
+    .await
+}
+
+async fn delete_comment_request(
+    app: &TestApp,
+    joke_id: i64,
+    comment_id: i64,
+    jwt: Option<&str>,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    delete(
+        app,
+        &format!("/api/jokes/{}/comments/{}", joke_id, comment_id),
+        auth_headers(jwt.map(bearer).as_deref()),
+    )
+    .await
+}
+
+async fn post_hide_comment_request(
+    app: &TestApp,
+    joke_id: i64,
+    comment_id: i64,
+    jwt: Option<&str>,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    post_json(
+        app,
+        &format!("/api/jokes/{}/comments/{}/hide", joke_id, comment_id),
+        json!({}),
+        auth_headers(jwt.map(bearer).as_deref()),
+    )
+    .await
+}
+
+async fn comment(app: &TestApp, joke_id: i64, content: &str, reply_to: Option<i64>, jwt: &str) -> i64 {
+    let (_, body) = post_comment_request(app, joke_id, content, reply_to, Some(jwt)).await;
+    body["created_comment"]["id"].as_i64().unwrap()
+}
+
+async fn reader(app: &TestApp, username: &str, email: &str) -> String {
+    let (_, jwt) = create_user_and_login_with_username(app, username, email, "pass", &Role::None).await;
+    jwt
+}
+
+#[actix_rt::test]
+async fn users_can_comment_and_reply_on_jokes() {
+    let app = spawn_app().await;
+    let (joke_id, author_jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    let jwt = reader(&app, "reader", "a1@test.fr").await;
+
+    let (status_code, body) = post_comment_request(&app, joke_id, "Ha!", None, Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["created_comment"]["author_username"], json!("reader"));
+    assert_eq!(body["created_comment"]["content"], json!("Ha!"));
+    let comment_id = body["created_comment"]["id"].as_i64().unwrap();
+
+    comment(&app, joke_id, "Thanks", Some(comment_id), &author_jwt).await;
+
+    let (status_code, body) = list_comments_request(&app, joke_id, "", None).await;
+    assert_eq!(status_code, StatusCode::OK);
+    let comments = body["comments"].as_array().unwrap();
+    assert_eq!(comments.len(), 1);
+    assert_eq!(comments[0]["content"], json!("Ha!"));
+    assert!(comments[0]["created_at"].is_string());
+    assert_eq!(comments[0]["replies"][0]["content"], json!("Thanks"));
+    assert_eq!(comments[0]["replies"][0]["author_username"], json!("author"));
+    assert_eq!(comments[0]["replies"][0]["parent_id"], json!(comment_id));
+}
+
+#[actix_rt::test]
+async fn replies_cannot_be_replied_to() {
+    let app = spawn_app().await;
+    let (joke_id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    let comment_id = comment(&app, joke_id, "Ha!", None, &jwt).await;
+    let reply_id = comment(&app, joke_id, "Ho!", Some(comment_id), &jwt).await;
+
+    let (status_code, body) = post_comment_request(&app, joke_id, "Hi!", Some(reply_id), Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], json!("NestedReply"));
+}
+
+#[actix_rt::test]
+async fn replies_must_answer_a_comment_of_the_same_joke() {
+    let app = spawn_app().await;
+    let (joke_id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    let (other_joke_id, _) = create_joke_as(&app, "other", "a1@test.fr", &Role::Author).await;
+    let comment_id = comment(&app, other_joke_id, "Ha!", None, &jwt).await;
+
+    let (status_code, body) = post_comment_request(&app, joke_id, "Ho!", Some(comment_id), Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], json!("ParentNotFound"));
+}
+
+#[actix_rt::test]
+async fn hidden_or_deleted_comments_cannot_be_replied_to() {
+    let app = spawn_app().await;
+    let (joke_id, author_jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    let jwt = reader(&app, "reader", "a1@test.fr").await;
+    let hidden_id = comment(&app, joke_id, "Boo!", None, &jwt).await;
+    let deleted_id = comment(&app, joke_id, "Oops", None, &jwt).await;
+    post_hide_comment_request(&app, joke_id, hidden_id, Some(&author_jwt)).await;
+    delete_comment_request(&app, joke_id, deleted_id, Some(&jwt)).await;
+
+    // Not even by those who still see the hidden comment
+    for (parent_id, jwt) in [(hidden_id, &jwt), (hidden_id, &author_jwt), (deleted_id, &jwt)] {
+        let (status_code, body) = post_comment_request(&app, joke_id, "Ho!", Some(parent_id), Some(jwt)).await;
+        assert_eq!(status_code, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], json!("ParentNotFound"));
+    }
+
+    let (_, body) = list_comments_request(&app, joke_id, "", Some(&author_jwt)).await;
+    assert_eq!(body["comments"][0]["replies"], json!([]));
+}
+
+#[actix_rt::test]
+async fn empty_comments_are_refused() {
+    let app = spawn_app().await;
+    let (joke_id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+
+    let (_, body) = post_comment_request(&app, joke_id, "  ", None, Some(&jwt)).await;
+    assert_eq!(body["success"], json!(false));
+    assert_eq!(body["issues"], json!(["Empty"]));
+
+    let (_, body) = list_comments_request(&app, joke_id, "", None).await;
+    assert_eq!(body["comments"], json!([]));
+}
+
+#[actix_rt::test]
+async fn anonymous_can_read_but_not_comment() {
+    let app = spawn_app().await;
+    let (joke_id, _) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+
+    let (status_code, _) = post_comment_request(&app, joke_id, "Ha!", None, None).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+
+    let (status_code, _) = list_comments_request(&app, joke_id, "", None).await;
+    assert_eq!(status_code, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn comments_on_drafts_are_not_found_for_others() {
+    let app = spawn_app().await;
+    let (_, author_jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author).await;
+    let jwt = reader(&app, "reader", "a1@test.fr").await;
+    let (_, body) = post_create_joke_request(&app, valid_joke(), Some(&author_jwt)).await;
+    let draft_id = body["created_joke"]["id"].as_i64().unwrap();
+
+    let (status_code, _) = post_comment_request(&app, draft_id, "Ha!", None, Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+
+    let (status_code, _) = list_comments_request(&app, draft_id, "", None).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn top_level_comments_are_paginated_oldest_first() {
+    let app = spawn_app().await;
+    let (joke_id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    for content in ["first", "second", "third"] {
+        let comment_id = comment(&app, joke_id, content, None, &jwt).await;
+        comment(&app, joke_id, "reply", Some(comment_id), &jwt).await;
+    }
+
+    let (_, body) = list_comments_request(&app, joke_id, "?limit=2&page=2", None).await;
+    let comments = body["comments"].as_array().unwrap();
+    assert_eq!(comments.len(), 1);
+    assert_eq!(comments[0]["content"], json!("third"));
+    assert_eq!(comments[0]["replies"].as_array().unwrap().len(), 1);
+    assert_eq!(body["page"], json!(2));
+    assert_eq!(body["limit"], json!(2));
+}
+
+#[actix_rt::test]
+async fn only_comment_authors_can_edit_their_comments() {
+    let app = spawn_app().await;
+    let (joke_id, _) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    let jwt = reader(&app, "reader", "a1@test.fr").await;
+    let other_jwt = reader(&app, "other", "a2@test.fr").await;
+    let (_, admin_jwt) =
+        create_user_and_login_with_username(&app, "admin", "a3@test.fr", "pass", &Role::Admin).await;
+    let comment_id = comment(&app, joke_id, "Ha!", None, &jwt).await;
+
+    let (status_code, _) = put_comment_request(&app, joke_id, comment_id, "Nope", Some(&other_jwt)).await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+
+    let (status_code, _) = put_comment_request(&app, joke_id, comment_id, "Nope", Some(&admin_jwt)).await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+
+    let (status_code, body) = put_comment_request(&app, joke_id, comment_id, "Haha!", Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["updated_comment"]["content"], json!("Haha!"));
+
+    let (_, body) = list_comments_request(&app, joke_id, "", None).await;
+    assert_eq!(body["comments"][0]["content"], json!("Haha!"));
+}
+
+#[actix_rt::test]
+async fn comment_authors_and_admins_can_delete_comments() {
+    let app = spawn_app().await;
+    let (joke_id, author_jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    let jwt = reader(&app, "reader", "a1@test.fr").await;
+    let (_, admin_jwt) =
+        create_user_and_login_with_username(&app, "admin", "a2@test.fr", "pass", &Role::Admin).await;
+    let first_id = comment(&app, joke_id, "first", None, &jwt).await;
+    let second_id = comment(&app, joke_id, "second", None, &jwt).await;
+
+    let (status_code, _) = delete_comment_request(&app, joke_id, first_id, Some(&author_jwt)).await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+
+    let (status_code, _) = delete_comment_request(&app, joke_id, first_id, Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (status_code, _) = delete_comment_request(&app, joke_id, second_id, Some(&admin_jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (_, body) = list_comments_request(&app, joke_id, "", None).await;
+    assert_eq!(body["comments"], json!([]));
+}
+
+#[actix_rt::test]
+async fn deleting_a_comment_deletes_its_replies() {
+    let app = spawn_app().await;
+    let (joke_id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    let comment_id = comment(&app, joke_id, "Ha!", None, &jwt).await;
+    comment(&app, joke_id, "Ho!", Some(comment_id), &jwt).await;
+
+    delete_comment_request(&app, joke_id, comment_id, Some(&jwt)).await;
+
+    let remaining = sqlx::query!("SELECT id FROM joke_comments WHERE joke_id = $1", joke_id as i32)
+        .fetch_all(&app.db_conn_pool)
+        .await
+        .unwrap();
+    assert!(remaining.is_empty());
+}
+
+#[actix_rt::test]
+async fn joke_authors_can_hide_comments_on_their_jokes() {
+    let app = spawn_app().await;
+    let (joke_id, author_jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    let jwt = reader(&app, "reader", "a1@test.fr").await;
+    let other_jwt = reader(&app, "other", "a2@test.fr").await;
+    let comment_id = comment(&app, joke_id, "Boo!", None, &jwt).await;
+
+    let (status_code, _) = post_hide_comment_request(&app, joke_id, comment_id, Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+
+    let (status_code, body) = post_hide_comment_request(&app, joke_id, comment_id, Some(&author_jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["updated_comment"]["hidden"], json!(true));
+
+    let (_, body) = list_comments_request(&app, joke_id, "", None).await;
+    assert_eq!(body["comments"], json!([]));
+
+    let (_, body) = list_comments_request(&app, joke_id, "", Some(&other_jwt)).await;
+    assert_eq!(body["comments"], json!([]));
+
+    for jwt in [&jwt, &author_jwt] {
+        let (_, body) = list_comments_request(&app, joke_id, "", Some(jwt)).await;
+        assert_eq!(body["comments"][0]["hidden"], json!(true));
+    }
+}
+
+#[actix_rt::test]
+async fn hidden_comments_can_be_unhidden() {
+    let app = spawn_app().await;
+    let (joke_id, author_jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    let jwt = reader(&app, "reader", "a1@test.fr").await;
+    let comment_id = comment(&app, joke_id, "Boo!", None, &jwt).await;
+    post_hide_comment_request(&app, joke_id, comment_id, Some(&author_jwt)).await;
+
+    let (status_code, _) = post_json(
+        &app,
+        &format!("/api/jokes/{}/comments/{}/unhide", joke_id, comment_id),
+        json!({}),
+        vec![("Authorization", &bearer(&author_jwt))],
+    )
+    .await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (_, body) = list_comments_request(&app, joke_id, "", None).await;
+    assert_eq!(body["comments"][0]["content"], json!("Boo!"));
+}
+
+#[actix_rt::test]
+async fn moderators_can_hide_and_delete_any_comment() {
+    let app = spawn_app().await;
+    let (joke_id, _) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    let jwt = reader(&app, "reader", "a1@test.fr").await;
+    let (_, moderator_jwt) =
+        create_user_and_login_with_username(&app, "moderator", "a2@test.fr", "pass", &Role::Moderator).await;
+    let comment_id = comment(&app, joke_id, "Boo!", None, &jwt).await;
+
+    let (status_code, _) = post_hide_comment_request(&app, joke_id, comment_id, Some(&moderator_jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+    let (_, body) = list_comments_request(&app, joke_id, "", Some(&moderator_jwt)).await;
+    assert_eq!(body["comments"][0]["hidden"], json!(true));
+
+    let (status_code, _) = delete_comment_request(&app, joke_id, comment_id, Some(&moderator_jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+}
@@ -1,31 +1,27 @@
 use crate::api::{
-    post_json, spawn_app, users::create_user_and_login_with_username, TestApp,
+    auth_headers, bearer, post_json, spawn_app, users::create_user_and_login_with_username, TestApp,
 };
 use camion::core::users::Role;
 use reqwest::StatusCode;
 use serde_json::json;
 
-async fn post_create_joke_request(
+pub async fn post_create_joke_request(
     app: &TestApp,
     joke_json: serde_json::Value,
     jwt: Option<&str>,
 ) -> (reqwest::StatusCode, serde_json::Value) {
-    let headers = match jwt {
-        Some(jwt) => vec![("Authorization", jwt)],
-        None => vec![]
-    };
     post_json(
         app,
-        &format!("/api/jokes/create"),
+        "/api/jokes/create",
         json!({
             "joke": joke_json
         }),
-        headers,
+        auth_headers(jwt.map(bearer).as_deref()),
     )
     .await
 }
 
-fn valid_joke() -> serde_json::Value {
+pub fn valid_joke() -> serde_json::Value {
     json!({
         "title": "Test",
         "lines": [
@@ -72,7 +68,7 @@ async fn none_cannot_create_jokes() {
             .await;
 
     let (status_code, _) = post_create_joke_request(&app, valid_joke(), Some(&jwt)).await;
-    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
 }
 
 #[actix_rt::test]
@@ -93,10 +89,47 @@ async fn creates_valid_jokes_returning_id() {
 
     let (status_code, body) = post_create_joke_request(&app, valid_joke(), Some(&jwt)).await;
     assert_eq!(status_code, StatusCode::OK);
-    assert_eq!(body["success"].as_bool().unwrap(), true);
+    assert!(body["success"].as_bool().unwrap());
     assert!(body["created_joke"]["id"].as_i64().is_some());
+    assert_eq!(body["created_joke"]["title"], valid_joke()["title"]);
+    assert_eq!(body["created_joke"]["author_username"], json!("admin"));
+
+    let lines = body["created_joke"]["lines"].as_array().unwrap();
+    let expected_lines = valid_joke()["lines"].as_array().unwrap().clone();
+    assert_eq!(lines.len(), expected_lines.len());
+    for (i, (line, expected_line)) in lines.iter().zip(expected_lines.iter()).enumerate() {
+        assert_eq!(line["index_within_joke"], json!(i));
+        assert_eq!(line["speaker"], expected_line["speaker"]);
+        assert_eq!(line["content"], expected_line["content"]);
+    }
+}
 
-    println!("{:#?}", body);
+#[actix_rt::test]
+async fn failed_line_insertion_leaves_no_joke_behind() {
+    let app = spawn_app().await;
+
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "admin", "a0@test.fr", "pass", &Role::Admin)
+            .await;
 
-    assert!(false);
+    // Postgres refuses NUL bytes in text columns, making the lines insertion fail
+    let mut joke = valid_joke();
+    joke["lines"][3]["content"] = json!("Nul \u{0} byte");
+
+    let (status_code, _) = post_create_joke_request(&app, joke, Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let jokes_count = sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM jokes"#)
+        .fetch_one(&app.db_conn_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(jokes_count, 0);
+
+    let lines_count = sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM joke_lines"#)
+        .fetch_one(&app.db_conn_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(lines_count, 0);
 }
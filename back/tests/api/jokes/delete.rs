@@ -0,0 +1,68 @@
+use crate::api::{
+    auth_headers, bearer, delete,
+    jokes::{create_joke_as, get::get_joke_request},
+    spawn_app,
+    users::create_user_and_login_with_username,
+    TestApp,
+};
+use camion::core::users::Role;
+use reqwest::StatusCode;
+
+async fn delete_joke_request(
+    app: &TestApp,
+    id: i64,
+    jwt: Option<&str>,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    delete(app, &format!("/api/jokes/{}", id), auth_headers(jwt.map(bearer).as_deref())).await
+}
+
+#[actix_rt::test]
+async fn authors_can_delete_their_jokes_and_lines() {
+    let app = spawn_app().await;
+    let (id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+
+    let (status_code, _) = delete_joke_request(&app, id, Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (status_code, _) = get_joke_request(&app, id, None).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+
+    let remaining_lines = sqlx::query!("SELECT id FROM joke_lines WHERE joke_id = $1", id as i32)
+        .fetch_all(&app.db_conn_pool)
+        .await
+        .unwrap();
+    assert!(remaining_lines.is_empty());
+}
+
+#[actix_rt::test]
+async fn admins_can_delete_anyones_jokes() {
+    let app = spawn_app().await;
+    let (id, _) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    let (_, admin_jwt) =
+        create_user_and_login_with_username(&app, "admin", "a1@test.fr", "pass", &Role::Admin)
+            .await;
+
+    let (status_code, _) = delete_joke_request(&app, id, Some(&admin_jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (status_code, _) = get_joke_request(&app, id, None).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn other_authors_and_anonymous_cannot_delete_jokes() {
+    let app = spawn_app().await;
+    let (id, _) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    let (_, other_jwt) =
+        create_user_and_login_with_username(&app, "other", "a1@test.fr", "pass", &Role::Author)
+            .await;
+
+    let (status_code, _) = delete_joke_request(&app, id, Some(&other_jwt)).await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+
+    let (status_code, _) = delete_joke_request(&app, id, None).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+
+    let (status_code, _) = get_joke_request(&app, id, None).await;
+    assert_eq!(status_code, StatusCode::OK);
+}
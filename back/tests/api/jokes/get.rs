@@ -0,0 +1,59 @@
+use crate::api::{
+    auth_headers, bearer, get, jokes::create::{post_create_joke_request, valid_joke}, spawn_app,
+    users::create_user_and_login_with_username, TestApp,
+};
+use camion::core::users::Role;
+use reqwest::StatusCode;
+use serde_json::json;
+
+pub async fn get_joke_request(
+    app: &TestApp,
+    id: i64,
+    jwt: Option<&str>,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    get(app, &format!("/api/jokes/{}", id), auth_headers(jwt.map(bearer).as_deref())).await
+}
+
+#[actix_rt::test]
+async fn created_jokes_can_be_fetched_back() {
+    let app = spawn_app().await;
+
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author)
+            .await;
+
+    let (_, body) = post_create_joke_request(&app, valid_joke(), Some(&jwt)).await;
+    let created_joke = &body["created_joke"];
+
+    let (status_code, body) =
+        get_joke_request(&app, created_joke["id"].as_i64().unwrap(), Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(&body, created_joke);
+}
+
+#[actix_rt::test]
+async fn lines_are_returned_in_order() {
+    let app = spawn_app().await;
+
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author)
+            .await;
+
+    let (_, body) = post_create_joke_request(&app, valid_joke(), Some(&jwt)).await;
+
+    let (_, body) =
+        get_joke_request(&app, body["created_joke"]["id"].as_i64().unwrap(), Some(&jwt)).await;
+    let lines = body["lines"].as_array().unwrap();
+    for (i, line) in lines.iter().enumerate() {
+        assert_eq!(line["index_within_joke"], json!(i));
+        assert_eq!(line["content"], valid_joke()["lines"][i]["content"]);
+    }
+}
+
+#[actix_rt::test]
+async fn unknown_joke_is_not_found() {
+    let app = spawn_app().await;
+
+    let (status_code, _) = get_joke_request(&app, 4242, None).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+}
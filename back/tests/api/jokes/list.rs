@@ -0,0 +1,105 @@
+use crate::api::{
+    get,
+    jokes::{
+        create::{post_create_joke_request, valid_joke},
+        status::post_publish_joke_request,
+        titles,
+    },
+    spawn_app,
+    users::create_user_and_login_with_username,
+    TestApp,
+};
+use camion::core::users::Role;
+use reqwest::StatusCode;
+use serde_json::json;
+
+async fn get_jokes_list_request(
+    app: &TestApp,
+    query: &str,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    get(app, &format!("/api/jokes{}", query), vec![]).await
+}
+
+async fn create_titled_joke(app: &TestApp, title: &str, jwt: &str) -> i64 {
+    let mut joke = valid_joke();
+    joke["title"] = json!(title);
+    let (_, body) = post_create_joke_request(app, joke, Some(jwt)).await;
+    let id = body["created_joke"]["id"].as_i64().unwrap();
+    post_publish_joke_request(app, id, Some(jwt)).await;
+    id
+}
+
+#[actix_rt::test]
+async fn lists_jokes_with_their_lines_newest_first_by_default() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author)
+            .await;
+    create_titled_joke(&app, "First", &jwt).await;
+    create_titled_joke(&app, "Second", &jwt).await;
+
+    let (status_code, body) = get_jokes_list_request(&app, "").await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(titles(&body), vec!["Second", "First"]);
+    assert_eq!(
+        body["jokes"][0]["lines"].as_array().unwrap().len(),
+        valid_joke()["lines"].as_array().unwrap().len()
+    );
+
+    let (_, body) = get_jokes_list_request(&app, "?sort=created_at&order=asc").await;
+    assert_eq!(titles(&body), vec!["First", "Second"]);
+}
+
+#[actix_rt::test]
+async fn filters_jokes_by_author_username() {
+    let app = spawn_app().await;
+    let (_, jwt_anicet) =
+        create_user_and_login_with_username(&app, "Anicet", "a0@test.fr", "pass", &Role::Author)
+            .await;
+    let (_, jwt_felicie) =
+        create_user_and_login_with_username(&app, "Félicie", "a1@test.fr", "pass", &Role::Author)
+            .await;
+    create_titled_joke(&app, "From Anicet", &jwt_anicet).await;
+    create_titled_joke(&app, "From Félicie", &jwt_felicie).await;
+
+    let (status_code, body) = get_jokes_list_request(&app, "?author=Anicet").await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(titles(&body), vec!["From Anicet"]);
+    assert_eq!(body["jokes"][0]["author_username"], json!("Anicet"));
+
+    let (_, body) = get_jokes_list_request(&app, "?author=Nobody").await;
+    assert!(titles(&body).is_empty());
+}
+
+#[actix_rt::test]
+async fn paginates_jokes() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author)
+            .await;
+    for title in ["1", "2", "3", "4", "5"].iter() {
+        create_titled_joke(&app, title, &jwt).await;
+    }
+
+    let (status_code, body) =
+        get_jokes_list_request(&app, "?order=asc&page=1&limit=2").await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(titles(&body), vec!["1", "2"]);
+    assert_eq!(body["page"], json!(1));
+    assert_eq!(body["limit"], json!(2));
+
+    let (_, body) = get_jokes_list_request(&app, "?order=asc&page=3&limit=2").await;
+    assert_eq!(titles(&body), vec!["5"]);
+
+    let (_, body) = get_jokes_list_request(&app, "?order=asc&page=4&limit=2").await;
+    assert!(titles(&body).is_empty());
+}
+
+#[actix_rt::test]
+async fn modified_at_sort_is_accepted() {
+    let app = spawn_app().await;
+
+    let (status_code, body) = get_jokes_list_request(&app, "?sort=modified_at&order=desc").await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert!(titles(&body).is_empty());
+}
@@ -1 +1,49 @@
-pub mod create;
\ No newline at end of file
+use camion::core::users::Role;
+
+use super::{users::create_user_and_login_with_username, TestApp};
+use create::{post_create_joke_request, valid_joke};
+use status::post_publish_joke_request;
+
+pub mod create;
+pub mod get;
+mod list;
+pub mod update;
+mod delete;
+mod revisions;
+pub mod status;
+mod reactions;
+mod comments;
+mod tags;
+mod search;
+
+// Creates a published joke, visible by anyone
+pub async fn create_joke_as(
+    app: &TestApp,
+    username: &str,
+    email: &str,
+    role: &Role,
+) -> (i64, String) {
+    let (_, jwt) = create_user_and_login_with_username(app, username, email, "pass", role).await;
+    let (_, body) = post_create_joke_request(app, valid_joke(), Some(&jwt)).await;
+    let id = body["created_joke"]["id"].as_i64().unwrap();
+    post_publish_joke_request(app, id, Some(&jwt)).await;
+    (id, jwt)
+}
+
+// Titles of the listed jokes, in order, search hits included
+pub fn titles(body: &serde_json::Value) -> Vec<&str> {
+    match body.get("hits") {
+        Some(hits) => hits
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|hit| hit["joke"]["title"].as_str().unwrap())
+            .collect(),
+        None => body["jokes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|joke| joke["title"].as_str().unwrap())
+            .collect(),
+    }
+}
@@ -0,0 +1,134 @@
+use crate::api::{
+    auth_headers, bearer, delete,
+    jokes::{
+        create::{post_create_joke_request, valid_joke},
+        create_joke_as,
+        get::get_joke_request,
+    },
+    put_json, spawn_app,
+    users::create_user_and_login_with_username,
+    TestApp,
+};
+use camion::core::users::Role;
+use reqwest::StatusCode;
+use serde_json::json;
+
+async fn put_reaction_request(
+    app: &TestApp,
+    id: i64,
+    reaction: &str,
+    jwt: Option<&str>,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    put_json(
+        app,
+        &format!("/api/jokes/{}/reaction", id),
+        json!({ "reaction": reaction }),
+        auth_headers(jwt.map(bearer).as_deref()),
+    )
+    .await
+}
+
+async fn delete_reaction_request(
+    app: &TestApp,
+    id: i64,
+    jwt: Option<&str>,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    delete(app, &format!("/api/jokes/{}/reaction", id), auth_headers(jwt.map(bearer).as_deref())).await
+}
+
+#[actix_rt::test]
+async fn new_jokes_have_no_reactions() {
+    let app = spawn_app().await;
+    let (id, _) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+
+    let (_, joke) = get_joke_request(&app, id, None).await;
+    assert_eq!(joke["reactions"], json!({ "laugh": 0, "groan": 0, "meh": 0 }));
+}
+
+#[actix_rt::test]
+async fn users_reactions_are_counted_alongside_the_joke() {
+    let app = spawn_app().await;
+    let (id, author_jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "reader", "a1@test.fr", "pass", &Role::None)
+            .await;
+
+    let (status_code, body) = put_reaction_request(&app, id, "Laugh", Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["reactions"]["laugh"], json!(1));
+
+    put_reaction_request(&app, id, "Laugh", Some(&author_jwt)).await;
+
+    let (_, joke) = get_joke_request(&app, id, None).await;
+    assert_eq!(joke["reactions"], json!({ "laugh": 2, "groan": 0, "meh": 0 }));
+}
+
+#[actix_rt::test]
+async fn users_have_a_single_changeable_reaction_per_joke() {
+    let app = spawn_app().await;
+    let (id, _) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "reader", "a1@test.fr", "pass", &Role::None)
+            .await;
+
+    put_reaction_request(&app, id, "Laugh", Some(&jwt)).await;
+    let (status_code, body) = put_reaction_request(&app, id, "Groan", Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["reactions"], json!({ "laugh": 0, "groan": 1, "meh": 0 }));
+
+    let (status_code, body) = delete_reaction_request(&app, id, Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["reactions"], json!({ "laugh": 0, "groan": 0, "meh": 0 }));
+}
+
+#[actix_rt::test]
+async fn listed_jokes_come_with_their_reaction_counts() {
+    let app = spawn_app().await;
+    let (id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    put_reaction_request(&app, id, "Meh", Some(&jwt)).await;
+
+    let (_, body) = crate::api::get(&app, "/api/jokes", vec![]).await;
+    assert_eq!(body["jokes"][0]["reactions"]["meh"], json!(1));
+}
+
+#[actix_rt::test]
+async fn anonymous_cannot_react() {
+    let app = spawn_app().await;
+    let (id, _) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+
+    let (status_code, _) = put_reaction_request(&app, id, "Laugh", None).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+
+    let (status_code, _) = delete_reaction_request(&app, id, None).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+}
+
+#[actix_rt::test]
+async fn cannot_react_to_jokes_one_cannot_see() {
+    let app = spawn_app().await;
+    let (_, author_jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author)
+            .await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "reader", "a1@test.fr", "pass", &Role::None)
+            .await;
+    let (_, body) = post_create_joke_request(&app, valid_joke(), Some(&author_jwt)).await;
+    let draft_id = body["created_joke"]["id"].as_i64().unwrap();
+
+    let (status_code, _) = put_reaction_request(&app, draft_id, "Laugh", Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn unknown_reactions_are_not_counted_as_meh() {
+    let app = spawn_app().await;
+    let (id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    put_reaction_request(&app, id, "Laugh", Some(&jwt)).await;
+    sqlx::query!("UPDATE joke_reactions SET reaction = 42 WHERE joke_id = $1", id as i32)
+        .execute(&app.db_conn_pool)
+        .await
+        .unwrap();
+
+    let (status_code, _) = get_joke_request(&app, id, None).await;
+    assert_eq!(status_code, StatusCode::INTERNAL_SERVER_ERROR);
+}
@@ -0,0 +1,240 @@
+use crate::api::{
+    auth_headers, bearer, get,
+    jokes::{create::valid_joke, create_joke_as, get::get_joke_request},
+    post_json, put_json, spawn_app,
+    users::create_user_and_login_with_username,
+    TestApp,
+};
+use camion::core::users::Role;
+use reqwest::StatusCode;
+use serde_json::json;
+
+async fn update_joke(app: &TestApp, id: i64, joke_json: serde_json::Value, jwt: &str) {
+    let (status_code, _) = put_json(
+        app,
+        &format!("/api/jokes/{}", id),
+        json!({ "joke": joke_json }),
+        vec![("Authorization", &bearer(jwt))],
+    )
+    .await;
+    assert_eq!(status_code, StatusCode::OK);
+}
+
+async fn post_restore_request(
+    app: &TestApp,
+    id: i64,
+    revision_number: i64,
+    jwt: Option<&str>,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    post_json(
+        app,
+        &format!("/api/jokes/{}/revisions/{}/restore", id, revision_number),
+        json!({}),
+        auth_headers(jwt.map(bearer).as_deref()),
+    )
+    .await
+}
+
+fn edited_joke() -> serde_json::Value {
+    let mut joke = valid_joke();
+    joke["title"] = json!("Edited");
+    joke["lines"][1]["content"] = json!("Edited content");
+    joke["lines"].as_array_mut().unwrap().pop();
+    joke
+}
+
+#[actix_rt::test]
+async fn every_change_is_recorded_as_a_revision() {
+    let app = spawn_app().await;
+    let (id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    update_joke(&app, id, edited_joke(), &jwt).await;
+
+    let (_, admin_jwt) =
+        create_user_and_login_with_username(&app, "admin", "a1@test.fr", "pass", &Role::Admin)
+            .await;
+    let (status_code, _) = put_json(
+        &app,
+        &format!("/api/jokes/{}/lines/0", id),
+        json!({ "line": { "speaker": "Admin", "content": "Moderated" } }),
+        vec![("Authorization", &bearer(&admin_jwt))],
+    )
+    .await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (status_code, body) = get(&app, &format!("/api/jokes/{}/revisions", id), vec![]).await;
+    assert_eq!(status_code, StatusCode::OK);
+    let revisions = body["revisions"].as_array().unwrap();
+    assert_eq!(revisions.len(), 3);
+    assert_eq!(revisions[0]["revision_number"], json!(1));
+    assert_eq!(revisions[0]["title"], valid_joke()["title"]);
+    assert_eq!(revisions[0]["editor_username"], json!("author"));
+    assert_eq!(revisions[1]["title"], json!("Edited"));
+    assert_eq!(revisions[2]["editor_username"], json!("admin"));
+
+    let (status_code, revision) =
+        get(&app, &format!("/api/jokes/{}/revisions/3", id), vec![]).await;
+    assert_eq!(status_code, StatusCode::OK);
+    let (_, joke) = get_joke_request(&app, id, None).await;
+    assert_eq!(revision["title"], joke["title"]);
+    assert_eq!(revision["lines"][0]["content"], json!("Moderated"));
+    assert_eq!(
+        revision["lines"].as_array().unwrap().len(),
+        joke["lines"].as_array().unwrap().len()
+    );
+}
+
+#[actix_rt::test]
+async fn concurrent_edits_get_their_own_revision_numbers() {
+    let app = spawn_app().await;
+    let (id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+
+    let edits: Vec<_> = (0..4)
+        .map(|_| {
+            let url = format!("{}/api/jokes/{}", app.url, id);
+            let authorization = bearer(&jwt);
+            tokio::spawn(async move {
+                reqwest::Client::new()
+                    .put(url)
+                    .header("Authorization", authorization)
+                    .json(&json!({ "joke": edited_joke() }))
+                    .send()
+                    .await
+                    .unwrap()
+                    .status()
+            })
+        })
+        .collect();
+    for edit in edits {
+        assert_eq!(edit.await.unwrap(), StatusCode::OK);
+    }
+
+    let (_, body) = get(&app, &format!("/api/jokes/{}/revisions", id), vec![]).await;
+    let numbers: Vec<_> = body["revisions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|revision| revision["revision_number"].as_i64().unwrap())
+        .collect();
+    assert_eq!(numbers, vec![1, 2, 3, 4, 5]);
+}
+
+#[actix_rt::test]
+async fn diffs_two_revisions_line_by_line() {
+    let app = spawn_app().await;
+    let (id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    update_joke(&app, id, edited_joke(), &jwt).await;
+
+    let (status_code, diff) =
+        get(&app, &format!("/api/jokes/{}/revisions/1/diff/2", id), vec![]).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(diff["from_revision"], json!(1));
+    assert_eq!(diff["to_revision"], json!(2));
+    assert_eq!(diff["title"]["from"], valid_joke()["title"]);
+    assert_eq!(diff["title"]["to"], json!("Edited"));
+
+    let lines = diff["lines"].as_array().unwrap();
+    assert_eq!(lines.len(), valid_joke()["lines"].as_array().unwrap().len());
+    assert_eq!(lines[0]["change"], json!("Unchanged"));
+    assert_eq!(lines[1]["change"], json!("Modified"));
+    assert_eq!(lines[1]["from"]["content"], valid_joke()["lines"][1]["content"]);
+    assert_eq!(lines[1]["to"]["content"], json!("Edited content"));
+    assert_eq!(lines[5]["change"], json!("Removed"));
+    assert_eq!(lines[5]["to"], serde_json::Value::Null);
+
+    let (_, diff) = get(&app, &format!("/api/jokes/{}/revisions/2/diff/1", id), vec![]).await;
+    assert_eq!(diff["lines"][5]["change"], json!("Added"));
+
+    let (_, diff) = get(&app, &format!("/api/jokes/{}/revisions/1/diff/1", id), vec![]).await;
+    assert_eq!(diff["title"], serde_json::Value::Null);
+}
+
+#[actix_rt::test]
+async fn restoring_a_revision_creates_a_new_one() {
+    let app = spawn_app().await;
+    let (id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    let (_, original) = get_joke_request(&app, id, None).await;
+    update_joke(&app, id, edited_joke(), &jwt).await;
+
+    let (status_code, body) = post_restore_request(&app, id, 1, Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["updated_joke"]["title"], original["title"]);
+
+    let (_, joke) = get_joke_request(&app, id, None).await;
+    assert_eq!(joke["title"], original["title"]);
+    let lines = joke["lines"].as_array().unwrap();
+    assert_eq!(lines.len(), original["lines"].as_array().unwrap().len());
+    for (line, original_line) in lines.iter().zip(original["lines"].as_array().unwrap()) {
+        assert_eq!(line["speaker"], original_line["speaker"]);
+        assert_eq!(line["content"], original_line["content"]);
+    }
+
+    let (_, body) = get(&app, &format!("/api/jokes/{}/revisions", id), vec![]).await;
+    assert_eq!(body["revisions"].as_array().unwrap().len(), 3);
+}
+
+#[actix_rt::test]
+async fn only_authors_and_admins_can_restore_revisions() {
+    let app = spawn_app().await;
+    let (id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    update_joke(&app, id, edited_joke(), &jwt).await;
+    let (_, other_jwt) =
+        create_user_and_login_with_username(&app, "other", "a1@test.fr", "pass", &Role::Author)
+            .await;
+    let (_, admin_jwt) =
+        create_user_and_login_with_username(&app, "admin", "a2@test.fr", "pass", &Role::Admin)
+            .await;
+
+    let (status_code, _) = post_restore_request(&app, id, 1, Some(&other_jwt)).await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+
+    let (status_code, _) = post_restore_request(&app, id, 1, None).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+
+    let (status_code, _) = post_restore_request(&app, id, 1, Some(&admin_jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn revisions_breaking_the_current_rules_are_not_restored() {
+    let app = spawn_app().await;
+    let (id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    // As left by jokes saved before titles and lines were checked
+    sqlx::query!(
+        r#"
+        INSERT INTO joke_revisions ( joke_id, revision_number, title, created_at )
+        VALUES ( $1, 2, '', NOW() )
+        "#,
+        id as i32
+    )
+    .execute(&app.db_conn_pool)
+    .await
+    .unwrap();
+
+    let (status_code, body) = post_restore_request(&app, id, 2, Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["success"], json!(false));
+    assert_eq!(body["issues"]["title"], json!(["Empty"]));
+    assert_eq!(body["issues"]["lines"], json!(["Empty"]));
+
+    let (_, joke) = get_joke_request(&app, id, None).await;
+    assert_eq!(joke["title"], valid_joke()["title"]);
+}
+
+#[actix_rt::test]
+async fn unknown_revisions_are_not_found() {
+    let app = spawn_app().await;
+    let (id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+
+    let (status_code, _) = get(&app, &format!("/api/jokes/{}/revisions/2", id), vec![]).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+
+    let (status_code, _) =
+        get(&app, &format!("/api/jokes/{}/revisions/1/diff/2", id), vec![]).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+
+    let (status_code, _) = post_restore_request(&app, id, 2, Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+
+    let (status_code, _) = get(&app, "/api/jokes/4242/revisions", vec![]).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,185 @@
+use crate::api::{
+    auth_headers, bearer, get,
+    jokes::{
+        create::post_create_joke_request, status::post_publish_joke_request,
+        titles, update::put_update_joke_request,
+    },
+    spawn_app,
+    users::create_user_and_login_with_username,
+    TestApp,
+};
+use camion::core::users::Role;
+use reqwest::StatusCode;
+use serde_json::json;
+
+async fn search_request(
+    app: &TestApp,
+    query: &str,
+    jwt: Option<&str>,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    get(app, &format!("/api/jokes/search{}", query), auth_headers(jwt.map(bearer).as_deref())).await
+}
+
+fn joke_json(title: &str, lines: &[(&str, &str)], tags: &[&str]) -> serde_json::Value {
+    json!({
+        "title": title,
+        "lines": lines
+            .iter()
+            .map(|(speaker, content)| json!({ "speaker": speaker, "content": content }))
+            .collect::<Vec<_>>(),
+        "tags": tags
+    })
+}
+
+async fn create_joke(app: &TestApp, joke: serde_json::Value, published: bool, jwt: &str) -> i64 {
+    let (_, body) = post_create_joke_request(app, joke, Some(jwt)).await;
+    let id = body["created_joke"]["id"].as_i64().unwrap();
+    if published {
+        post_publish_joke_request(app, id, Some(jwt)).await;
+    }
+    id
+}
+
+async fn author(app: &TestApp, username: &str, email: &str) -> String {
+    let (_, jwt) =
+        create_user_and_login_with_username(app, username, email, "pass", &Role::Author).await;
+    jwt
+}
+
+#[actix_rt::test]
+async fn finds_jokes_by_title_and_lines_titles_first() {
+    let app = spawn_app().await;
+    let jwt = author(&app, "author", "a0@test.fr").await;
+    create_joke(
+        &app,
+        joke_json("Bar", &[("Alice", "A penguin walks into a bar")], &[]),
+        true,
+        &jwt,
+    )
+    .await;
+    create_joke(&app, joke_json("Penguins", &[("Bob", "Nothing here")], &[]), true, &jwt).await;
+    create_joke(&app, joke_json("Cats", &[("Carol", "Meow")], &[]), true, &jwt).await;
+
+    let (status_code, body) = search_request(&app, "?q=penguin", None).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(titles(&body), vec!["Penguins", "Bar"]);
+}
+
+#[actix_rt::test]
+async fn highlights_the_matching_line() {
+    let app = spawn_app().await;
+    let jwt = author(&app, "author", "a0@test.fr").await;
+    create_joke(
+        &app,
+        joke_json(
+            "Bar",
+            &[("Alice", "Knock knock"), ("Bob", "A penguin walks into a bar")],
+            &[],
+        ),
+        true,
+        &jwt,
+    )
+    .await;
+    create_joke(&app, joke_json("Penguins", &[("Bob", "Nothing here")], &[]), true, &jwt).await;
+
+    let (_, body) = search_request(&app, "?q=penguins", None).await;
+    assert_eq!(
+        body["hits"][1]["snippet"],
+        json!({
+            "index_within_joke": 1,
+            "speaker": "Bob",
+            "content": "A <mark>penguin</mark> walks into a bar"
+        })
+    );
+    assert_eq!(body["hits"][0]["snippet"], json!(null));
+}
+
+#[actix_rt::test]
+async fn snippets_are_escaped_around_the_highlights() {
+    let app = spawn_app().await;
+    let jwt = author(&app, "author", "a0@test.fr").await;
+    create_joke(
+        &app,
+        joke_json(
+            "Bar",
+            &[("<b>Bob</b>", "A penguin <script>alert('walks')</script> & bar")],
+            &[],
+        ),
+        true,
+        &jwt,
+    )
+    .await;
+
+    let (_, body) = search_request(&app, "?q=penguin", None).await;
+    assert_eq!(
+        body["hits"][0]["snippet"],
+        json!({
+            "index_within_joke": 0,
+            "speaker": "&lt;b&gt;Bob&lt;/b&gt;",
+            "content": "A <mark>penguin</mark> &lt;script&gt;alert(&#39;walks&#39;)&lt;/script&gt; &amp; bar"
+        })
+    );
+}
+
+#[actix_rt::test]
+async fn follows_edits_of_jokes() {
+    let app = spawn_app().await;
+    let jwt = author(&app, "author", "a0@test.fr").await;
+    let id = create_joke(&app, joke_json("Cats", &[("Carol", "Meow")], &[]), true, &jwt).await;
+
+    put_update_joke_request(&app, id, joke_json("Dogs", &[("Carol", "Woof")], &[]), Some(&jwt))
+        .await;
+
+    let (_, body) = search_request(&app, "?q=meow", None).await;
+    assert_eq!(body["hits"], json!([]));
+
+    let (_, body) = search_request(&app, "?q=woof", None).await;
+    assert_eq!(titles(&body), vec!["Dogs"]);
+}
+
+#[actix_rt::test]
+async fn filters_by_author_and_tag() {
+    let app = spawn_app().await;
+    let jwt = author(&app, "author", "a0@test.fr").await;
+    let other_jwt = author(&app, "other", "a1@test.fr").await;
+    create_joke(&app, joke_json("Fish one", &[("A", "Blub")], &["sea"]), true, &jwt).await;
+    create_joke(&app, joke_json("Fish two", &[("A", "Blub")], &[]), true, &jwt).await;
+    create_joke(&app, joke_json("Fish three", &[("A", "Blub")], &["sea"]), true, &other_jwt).await;
+
+    let (_, body) = search_request(&app, "?q=fish&author=author", None).await;
+    assert_eq!(titles(&body), vec!["Fish two", "Fish one"]);
+
+    let (_, body) = search_request(&app, "?q=fish&tag=Sea", None).await;
+    assert_eq!(titles(&body), vec!["Fish three", "Fish one"]);
+
+    let (_, body) = search_request(&app, "?q=fish&tag=sea&author=other&limit=1", None).await;
+    assert_eq!(titles(&body), vec!["Fish three"]);
+}
+
+#[actix_rt::test]
+async fn drafts_are_only_found_by_their_author() {
+    let app = spawn_app().await;
+    let jwt = author(&app, "author", "a0@test.fr").await;
+    let other_jwt = author(&app, "other", "a1@test.fr").await;
+    create_joke(&app, joke_json("Secret", &[("A", "Hush")], &[]), false, &jwt).await;
+
+    let (_, body) = search_request(&app, "?q=secret", None).await;
+    assert_eq!(body["hits"], json!([]));
+
+    let (_, body) = search_request(&app, "?q=secret", Some(&other_jwt)).await;
+    assert_eq!(body["hits"], json!([]));
+
+    let (_, body) = search_request(&app, "?q=secret", Some(&jwt)).await;
+    assert_eq!(titles(&body), vec!["Secret"]);
+}
+
+#[actix_rt::test]
+async fn odd_queries_do_not_fail() {
+    let app = spawn_app().await;
+
+    for query in ["?q=", "?q=%22unclosed", "?q=a%26%7C!(", "?q=-"] {
+        let (status_code, body) = search_request(&app, query, None).await;
+        assert_eq!(status_code, StatusCode::OK);
+        assert_eq!(body["hits"], json!([]));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::api::{
+    auth_headers, bearer, get,
+    jokes::{
+        create::{post_create_joke_request, valid_joke},
+        get::get_joke_request,
+    },
+    post_json, spawn_app,
+    users::create_user_and_login_with_username,
+    TestApp,
+};
+use camion::core::users::Role;
+use reqwest::StatusCode;
+use serde_json::json;
+
+pub async fn post_publish_joke_request(
+    app: &TestApp,
+    id: i64,
+    jwt: Option<&str>,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    post_json(app, &format!("/api/jokes/{}/publish", id), json!({}), auth_headers(jwt.map(bearer).as_deref())).await
+}
+
+async fn post_archive_joke_request(
+    app: &TestApp,
+    id: i64,
+    jwt: Option<&str>,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    post_json(app, &format!("/api/jokes/{}/archive", id), json!({}), auth_headers(jwt.map(bearer).as_deref())).await
+}
+
+async fn list_titles(app: &TestApp, query: &str, jwt: Option<&str>) -> Vec<String> {
+    let (_, body) = get(app, &format!("/api/jokes{}", query), auth_headers(jwt.map(bearer).as_deref())).await;
+    body["jokes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|joke| joke["title"].as_str().unwrap().to_owned())
+        .collect()
+}
+
+async fn create_draft(app: &TestApp, title: &str, jwt: &str) -> i64 {
+    let mut joke = valid_joke();
+    joke["title"] = json!(title);
+    let (_, body) = post_create_joke_request(app, joke, Some(jwt)).await;
+    body["created_joke"]["id"].as_i64().unwrap()
+}
+
+#[actix_rt::test]
+async fn jokes_are_created_as_drafts() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author)
+            .await;
+
+    let (_, body) = post_create_joke_request(&app, valid_joke(), Some(&jwt)).await;
+    assert_eq!(body["created_joke"]["status"], json!("Draft"));
+    assert_eq!(body["created_joke"]["published_at"], serde_json::Value::Null);
+}
+
+#[actix_rt::test]
+async fn drafts_are_only_visible_by_their_author_and_admins() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author)
+            .await;
+    let (_, other_jwt) =
+        create_user_and_login_with_username(&app, "other", "a1@test.fr", "pass", &Role::Author)
+            .await;
+    let (_, admin_jwt) =
+        create_user_and_login_with_username(&app, "admin", "a2@test.fr", "pass", &Role::Admin)
+            .await;
+    let id = create_draft(&app, "Draft", &jwt).await;
+
+    let (status_code, _) = get_joke_request(&app, id, None).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+    let (status_code, _) = get_joke_request(&app, id, Some(&other_jwt)).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+    let (status_code, _) =
+        get(&app, &format!("/api/jokes/{}/revisions", id), vec![]).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+
+    let (status_code, _) = get_joke_request(&app, id, Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+    let (status_code, _) = get_joke_request(&app, id, Some(&admin_jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn publishing_makes_jokes_visible_to_everyone() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author)
+            .await;
+    let id = create_draft(&app, "Draft", &jwt).await;
+
+    let (status_code, body) = post_publish_joke_request(&app, id, Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["updated_joke"]["status"], json!("Published"));
+    assert_ne!(body["updated_joke"]["published_at"], serde_json::Value::Null);
+
+    let (status_code, joke) = get_joke_request(&app, id, None).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(joke["published_at"], body["updated_joke"]["published_at"]);
+}
+
+#[actix_rt::test]
+async fn archived_jokes_are_hidden_again_but_keep_their_publication_date() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author)
+            .await;
+    let id = create_draft(&app, "Draft", &jwt).await;
+    let (_, published) = post_publish_joke_request(&app, id, Some(&jwt)).await;
+
+    let (status_code, body) = post_archive_joke_request(&app, id, Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["updated_joke"]["status"], json!("Archived"));
+
+    let (status_code, _) = get_joke_request(&app, id, None).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+
+    let (_, body) = post_publish_joke_request(&app, id, Some(&jwt)).await;
+    assert_eq!(
+        body["updated_joke"]["published_at"],
+        published["updated_joke"]["published_at"]
+    );
+}
+
+#[actix_rt::test]
+async fn only_authors_and_admins_can_change_a_jokes_status() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author)
+            .await;
+    let (_, other_jwt) =
+        create_user_and_login_with_username(&app, "other", "a1@test.fr", "pass", &Role::Author)
+            .await;
+    let (_, admin_jwt) =
+        create_user_and_login_with_username(&app, "admin", "a2@test.fr", "pass", &Role::Admin)
+            .await;
+    let id = create_draft(&app, "Draft", &jwt).await;
+
+    // Drafts of others are not even known to exist
+    let (status_code, _) = post_publish_joke_request(&app, id, Some(&other_jwt)).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+    let (status_code, _) = post_publish_joke_request(&app, id, None).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+
+    let (status_code, _) = post_publish_joke_request(&app, id, Some(&admin_jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+    let (status_code, _) = post_archive_joke_request(&app, id, Some(&admin_jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn listings_only_show_published_jokes_to_anonymous_and_others() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author)
+            .await;
+    let (_, other_jwt) =
+        create_user_and_login_with_username(&app, "other", "a1@test.fr", "pass", &Role::Author)
+            .await;
+    let (_, admin_jwt) =
+        create_user_and_login_with_username(&app, "admin", "a2@test.fr", "pass", &Role::Admin)
+            .await;
+    create_draft(&app, "Draft", &jwt).await;
+    let published_id = create_draft(&app, "Published", &jwt).await;
+    post_publish_joke_request(&app, published_id, Some(&jwt)).await;
+    let archived_id = create_draft(&app, "Archived", &jwt).await;
+    post_archive_joke_request(&app, archived_id, Some(&jwt)).await;
+
+    assert_eq!(list_titles(&app, "", None).await, vec!["Published"]);
+    assert_eq!(list_titles(&app, "", Some(&other_jwt)).await, vec!["Published"]);
+    assert_eq!(
+        list_titles(&app, "", Some(&jwt)).await,
+        vec!["Archived", "Published", "Draft"]
+    );
+    assert_eq!(
+        list_titles(&app, "", Some(&admin_jwt)).await,
+        vec!["Archived", "Published", "Draft"]
+    );
+
+    assert_eq!(list_titles(&app, "?status=Draft", Some(&jwt)).await, vec!["Draft"]);
+    assert!(list_titles(&app, "?status=Draft", None).await.is_empty());
+}
+
+#[actix_rt::test]
+async fn jokes_of_an_unknown_status_are_not_taken_for_drafts() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author)
+            .await;
+    let id = create_draft(&app, "Draft", &jwt).await;
+    sqlx::query!("UPDATE jokes SET status = 42 WHERE id = $1", id as i32)
+        .execute(&app.db_conn_pool)
+        .await
+        .unwrap();
+
+    let (status_code, _) = get_joke_request(&app, id, Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::INTERNAL_SERVER_ERROR);
+}
@@ -0,0 +1,110 @@
+use crate::api::{
+    bearer,
+    jokes::{
+        create::{post_create_joke_request, valid_joke},
+        create_joke_as,
+        get::get_joke_request,
+        update::put_update_joke_request,
+    },
+    post_json, spawn_app,
+    users::create_user_and_login_with_username,
+};
+use camion::core::users::Role;
+use reqwest::StatusCode;
+use serde_json::json;
+
+fn tagged_joke(tags: serde_json::Value) -> serde_json::Value {
+    let mut joke = valid_joke();
+    joke["tags"] = tags;
+    joke
+}
+
+#[actix_rt::test]
+async fn tags_are_normalized_and_deduplicated() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author)
+            .await;
+
+    let (status_code, body) = post_create_joke_request(
+        &app,
+        tagged_joke(json!(["Puns", " puns ", "Knock-knock"])),
+        Some(&jwt),
+    )
+    .await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["created_joke"]["tags"], json!(["knock-knock", "puns"]));
+}
+
+#[actix_rt::test]
+async fn jokes_without_tags_have_none() {
+    let app = spawn_app().await;
+    let (id, _) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+
+    let (_, joke) = get_joke_request(&app, id, None).await;
+    assert_eq!(joke["tags"], json!([]));
+}
+
+#[actix_rt::test]
+async fn invalid_tags_are_refused_with_their_issues() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author)
+            .await;
+    let long_tag = "a".repeat(33);
+
+    let (_, body) = post_create_joke_request(
+        &app,
+        tagged_joke(json!(["x", long_tag, "a/b", "fine"])),
+        Some(&jwt),
+    )
+    .await;
+    assert_eq!(body["success"], json!(false));
+    assert_eq!(body["issues"]["tags"]["x"], json!(["TooShort"]));
+    assert_eq!(body["issues"]["tags"][&long_tag], json!(["TooLong"]));
+    assert_eq!(body["issues"]["tags"]["a/b"], json!(["ForbiddenCharacters"]));
+    assert!(body["issues"]["tags"].get("fine").is_none());
+
+    let jokes = sqlx::query!("SELECT id FROM jokes")
+        .fetch_all(&app.db_conn_pool)
+        .await
+        .unwrap();
+    assert!(jokes.is_empty());
+}
+
+#[actix_rt::test]
+async fn updating_with_tags_replaces_them() {
+    let app = spawn_app().await;
+    let (id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    put_update_joke_request(&app, id, tagged_joke(json!(["puns", "dad"])), Some(&jwt)).await;
+
+    let (status_code, body) =
+        put_update_joke_request(&app, id, tagged_joke(json!(["Dad", "office"])), Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["updated_joke"]["tags"], json!(["dad", "office"]));
+
+    let (_, body) = put_update_joke_request(&app, id, tagged_joke(json!(["?"])), Some(&jwt)).await;
+    assert_eq!(body["success"], json!(false));
+
+    let (_, joke) = get_joke_request(&app, id, None).await;
+    assert_eq!(joke["tags"], json!(["dad", "office"]));
+}
+
+#[actix_rt::test]
+async fn updating_without_tags_keeps_them() {
+    let app = spawn_app().await;
+    let (id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    put_update_joke_request(&app, id, tagged_joke(json!(["puns"])), Some(&jwt)).await;
+
+    let (_, body) = put_update_joke_request(&app, id, valid_joke(), Some(&jwt)).await;
+    assert_eq!(body["updated_joke"]["tags"], json!(["puns"]));
+
+    let (_, body) = post_json(
+        &app,
+        &format!("/api/jokes/{}/revisions/1/restore", id),
+        json!({}),
+        vec![("Authorization", &bearer(&jwt))],
+    )
+    .await;
+    assert_eq!(body["updated_joke"]["tags"], json!(["puns"]));
+}
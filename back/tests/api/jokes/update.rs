@@ -0,0 +1,236 @@
+use crate::api::{
+    auth_headers, bearer,
+    jokes::{create::{post_create_joke_request, valid_joke}, create_joke_as, get::get_joke_request},
+    put_json, spawn_app,
+    users::create_user_and_login_with_username,
+    TestApp,
+};
+use camion::core::users::Role;
+use reqwest::StatusCode;
+use serde_json::json;
+
+pub async fn put_update_joke_request(
+    app: &TestApp,
+    id: i64,
+    joke_json: serde_json::Value,
+    jwt: Option<&str>,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    put_json(app, &format!("/api/jokes/{}", id), json!({ "joke": joke_json }), auth_headers(jwt.map(bearer).as_deref())).await
+}
+
+async fn put_update_joke_line_request(
+    app: &TestApp,
+    id: i64,
+    index_within_joke: i64,
+    line_json: serde_json::Value,
+    jwt: Option<&str>,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    put_json(
+        app,
+        &format!("/api/jokes/{}/lines/{}", id, index_within_joke),
+        json!({ "line": line_json }),
+        auth_headers(jwt.map(bearer).as_deref()),
+    )
+    .await
+}
+
+fn updated_joke() -> serde_json::Value {
+    json!({
+        "title": "Updated",
+        "lines": [
+            { "speaker": "Knock", "content": "Knock knock" },
+            { "speaker": "Who", "content": "Who's there?" }
+        ]
+    })
+}
+
+#[actix_rt::test]
+async fn authors_can_replace_their_jokes_title_and_lines() {
+    let app = spawn_app().await;
+    let (id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    let (_, original) = get_joke_request(&app, id, None).await;
+
+    let (status_code, body) = put_update_joke_request(&app, id, updated_joke(), Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["updated_joke"]["title"], json!("Updated"));
+
+    let (_, joke) = get_joke_request(&app, id, None).await;
+    assert_eq!(joke["title"], json!("Updated"));
+    assert_eq!(joke["created_at"], original["created_at"]);
+    assert_ne!(joke["modified_at"], original["modified_at"]);
+    let lines = joke["lines"].as_array().unwrap();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[1]["index_within_joke"], json!(1));
+    assert_eq!(lines[1]["content"], json!("Who's there?"));
+}
+
+#[actix_rt::test]
+async fn authors_can_edit_a_single_line_of_their_jokes() {
+    let app = spawn_app().await;
+    let (id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    let (_, original) = get_joke_request(&app, id, None).await;
+
+    let (status_code, _) = put_update_joke_line_request(
+        &app,
+        id,
+        2,
+        json!({ "speaker": "Narrator", "content": "Edited" }),
+        Some(&jwt),
+    )
+    .await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (_, joke) = get_joke_request(&app, id, None).await;
+    assert_ne!(joke["modified_at"], original["modified_at"]);
+    assert_eq!(joke["lines"][2]["speaker"], json!("Narrator"));
+    assert_eq!(joke["lines"][2]["content"], json!("Edited"));
+    assert_eq!(joke["lines"][1], original["lines"][1]);
+    assert_eq!(joke["lines"][3], original["lines"][3]);
+}
+
+#[actix_rt::test]
+async fn editing_an_unknown_line_is_not_found() {
+    let app = spawn_app().await;
+    let (id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+
+    let (status_code, _) = put_update_joke_line_request(
+        &app,
+        id,
+        42,
+        json!({ "speaker": "Narrator", "content": "Edited" }),
+        Some(&jwt),
+    )
+    .await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn admins_can_update_anyones_jokes() {
+    let app = spawn_app().await;
+    let (id, _) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    let (_, admin_jwt) =
+        create_user_and_login_with_username(&app, "admin", "a1@test.fr", "pass", &Role::Admin)
+            .await;
+
+    let (status_code, _) =
+        put_update_joke_request(&app, id, updated_joke(), Some(&admin_jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (_, joke) = get_joke_request(&app, id, None).await;
+    assert_eq!(joke["title"], json!("Updated"));
+    assert_eq!(joke["author_username"], json!("author"));
+}
+
+#[actix_rt::test]
+async fn other_authors_and_anonymous_cannot_update_jokes() {
+    let app = spawn_app().await;
+    let (id, _) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    let (_, other_jwt) =
+        create_user_and_login_with_username(&app, "other", "a1@test.fr", "pass", &Role::Author)
+            .await;
+
+    let (status_code, _) =
+        put_update_joke_request(&app, id, updated_joke(), Some(&other_jwt)).await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+
+    let (status_code, _) = put_update_joke_line_request(
+        &app,
+        id,
+        0,
+        json!({ "speaker": "Narrator", "content": "Edited" }),
+        Some(&other_jwt),
+    )
+    .await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+
+    let (status_code, _) = put_update_joke_request(&app, id, updated_joke(), None).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+
+    let (_, joke) = get_joke_request(&app, id, None).await;
+    assert_eq!(joke["title"], valid_joke()["title"]);
+}
+
+#[actix_rt::test]
+async fn updating_an_unknown_joke_is_not_found() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "admin", "a0@test.fr", "pass", &Role::Admin)
+            .await;
+
+    let (status_code, _) = put_update_joke_request(&app, 4242, updated_joke(), Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn anonymous_cannot_tell_whether_a_joke_exists() {
+    let app = spawn_app().await;
+    let (id, _) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+
+    for unknown_or_not in [id, 4242] {
+        let (status_code, _) = put_update_joke_request(&app, unknown_or_not, updated_joke(), None).await;
+        assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    }
+}
+
+#[actix_rt::test]
+async fn others_drafts_are_not_found_when_updating() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author)
+            .await;
+    let (_, body) = post_create_joke_request(&app, valid_joke(), Some(&jwt)).await;
+    let draft_id = body["created_joke"]["id"].as_i64().unwrap();
+    let (_, other_jwt) =
+        create_user_and_login_with_username(&app, "other", "a1@test.fr", "pass", &Role::Author)
+            .await;
+
+    let (status_code, _) =
+        put_update_joke_request(&app, draft_id, updated_joke(), Some(&other_jwt)).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn invalid_titles_and_lines_are_refused_with_their_issues() {
+    let app = spawn_app().await;
+    let (id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+
+    let (status_code, body) =
+        put_update_joke_request(&app, id, json!({ "title": "  ", "lines": [] }), Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["success"], json!(false));
+    assert_eq!(body["issues"]["title"], json!(["Empty"]));
+    assert_eq!(body["issues"]["lines"], json!(["Empty"]));
+
+    let too_long = "a".repeat(2001);
+    let (_, body) = put_update_joke_request(
+        &app,
+        id,
+        json!({
+            "title": "Fine",
+            "lines": [
+                { "speaker": "", "content": too_long },
+                { "speaker": "Who", "content": "" }
+            ]
+        }),
+        Some(&jwt),
+    )
+    .await;
+    assert_eq!(body["success"], json!(false));
+    assert_eq!(body["issues"]["contents"]["0"], json!(["TooLong"]));
+    assert_eq!(body["issues"]["contents"]["1"], json!(["Empty"]));
+    assert!(body["issues"]["speakers"].is_null());
+
+    let (_, body) = put_update_joke_line_request(
+        &app,
+        id,
+        0,
+        json!({ "speaker": "Narrator", "content": "" }),
+        Some(&jwt),
+    )
+    .await;
+    assert_eq!(body["success"], json!(false));
+    assert_eq!(body["issues"]["contents"]["0"], json!(["Empty"]));
+
+    let (_, joke) = get_joke_request(&app, id, None).await;
+    assert_eq!(joke["title"], valid_joke()["title"]);
+}
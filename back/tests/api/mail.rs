@@ -0,0 +1,67 @@
+use camion::core::{
+    mail::{templates, FileMailer},
+    users::Role,
+};
+use crate::api::{insert_test_user, mails_sent_to, post_json, spawn_app, spawn_app_with, TestConfig};
+use serde_json::json;
+use std::{env, fs, sync::Arc, time::Duration};
+use uuid::Uuid;
+
+#[actix_rt::test]
+async fn the_file_mailer_drops_eml_files() {
+    let directory = env::temp_dir().join(Uuid::new_v4().to_string());
+    let mailer = FileMailer::new(&directory, "Camion <camion@localhost>".parse().unwrap());
+    let app = spawn_app_with(TestConfig { mailer: Some(Arc::new(mailer)), ..Default::default() }).await;
+    insert_test_user("Anicet", "anicet@mail.com", "pass", &Role::None, &app.db_conn_pool).await;
+
+    post_json(&app, "/api/auth/password-reset", json!({ "email": "anicet@mail.com" }), vec![]).await;
+
+    // Sent in the background
+    let mut files = vec![];
+    for _ in 0..100 {
+        files = fs::read_dir(&directory)
+            .map(|entries| entries.map(|entry| entry.unwrap().path()).collect::<Vec<_>>())
+            .unwrap_or_default();
+        if !files.is_empty() {
+            break;
+        }
+        actix_rt::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].extension().unwrap(), "eml");
+
+    let eml = fs::read_to_string(&files[0]).unwrap();
+    assert!(eml.contains("From: Camion <camion@localhost>"));
+    assert!(eml.contains("To: anicet@mail.com"));
+    assert!(eml.contains("Subject: Resetting your password"));
+    assert!(eml.contains("multipart/alternative"));
+    assert!(eml.contains("text/plain"));
+    assert!(eml.contains("text/html"));
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[actix_rt::test]
+async fn mails_come_in_plain_text_and_html() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "anicet@mail.com", "pass", &Role::None, &app.db_conn_pool).await;
+
+    post_json(&app, "/api/auth/password-reset", json!({ "email": "anicet@mail.com" }), vec![]).await;
+
+    let mails = mails_sent_to(&app, "anicet@mail.com", 1).await;
+    assert_eq!(mails.len(), 1);
+    assert!(mails[0].text.contains("Hello Anicet,"));
+    assert!(mails[0].html.as_ref().unwrap().contains("<p>Hello Anicet,</p>"));
+}
+
+#[test]
+fn templates_escape_values_in_html_only() {
+    let message = templates::PASSWORD_RESET.render(
+        "anicet@mail.com",
+        &[("username", "<b>Anicet</b>"), ("lifetime_min", "30"), ("token", "abc")],
+    );
+
+    assert_eq!(message.to, "anicet@mail.com");
+    assert!(message.text.contains("Hello <b>Anicet</b>,"));
+    assert!(message.html.unwrap().contains("Hello &lt;b&gt;Anicet&lt;/b&gt;,"));
+}
@@ -1,6 +1,10 @@
 use camion::{
-    core::{db, security::password_salt_and_hash, users::Role},
-    web::application::{Application, Config},
+    core::{db, mail::{InMemoryMailer, Mailer, Message}, oidc, security::password_salt_and_hash, users::Role},
+    web::{
+        application::{Application, Config},
+        client_ip::TrustedProxies,
+        rate_limit::{InMemoryStore, Limiter, Limits},
+    },
 };
 use dotenv::dotenv;
 use reqwest::{
@@ -8,30 +12,83 @@ use reqwest::{
     Client as HttpClient, StatusCode,
 };
 use std::env;
+use std::net::IpAddr;
 use std::str::FromStr;
+use std::sync::{Arc, Once};
+use std::time::Duration;
 use uuid::Uuid;
 
 mod auth;
 mod users;
 mod jokes;
+mod tags;
+mod mail;
+mod settings;
+mod rate_limit;
+mod roles;
 
 pub struct TestApp {
     pub url: String,
     pub db_conn_pool: db::DbPool,
+    // The app's own mailer, unless it was given another one
+    mailer: Option<Arc<InMemoryMailer>>,
+}
+
+impl TestApp {
+    pub fn mailer(&self) -> &InMemoryMailer {
+        self.mailer.as_deref().expect("the app was given another mailer")
+    }
+}
+
+// What tests change of the app's configuration
+pub struct TestConfig {
+    // Mails are kept in memory without one, see mails_sent_to
+    pub mailer: Option<Arc<dyn Mailer>>,
+    // Not rate limited unless asked, tests sending plenty of requests
+    pub rate_limits: Limits,
+    pub oidc_providers: Vec<oidc::Provider>,
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl Default for TestConfig {
+    fn default() -> Self {
+        TestConfig {
+            mailer: None,
+            rate_limits: Limits::none(),
+            oidc_providers: vec![],
+            trusted_proxies: vec![],
+        }
+    }
 }
 
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(TestConfig::default()).await
+}
+
+pub async fn spawn_app_with(test_config: TestConfig) -> TestApp {
     // Configuration
     dotenv().ok();
+    use_test_jwt_keys();
     let host = "127.0.0.1";
     let database_url_root = env::var("DATABASE_URL_ROOT").expect("DATABASE_URL_ROOT not set");
     let test_database_name = Uuid::new_v4().to_string();
     let test_database_url = format!("{}{}", &database_url_root, &test_database_name);
 
+    let (mailer, in_memory_mailer): (Arc<dyn Mailer>, _) = match test_config.mailer {
+        Some(mailer) => (mailer, None),
+        None => {
+            let in_memory_mailer = Arc::new(InMemoryMailer::default());
+            (in_memory_mailer.clone(), Some(in_memory_mailer))
+        }
+    };
     let config = Config {
         port: 0,
         host: host.to_owned(),
         db_url: test_database_url.clone(),
+        mailer,
+        rate_limiter: Limiter::new(test_config.rate_limits, Arc::new(InMemoryStore::default())),
+        oidc_providers: oidc::Providers::new(test_config.oidc_providers),
+        trusted_proxies: TrustedProxies::new(test_config.trusted_proxies),
     };
 
     // Setup test db
@@ -40,18 +97,30 @@ pub async fn spawn_app() -> TestApp {
     // Running the test server and retrieving its port
     let app = Application::create(&config).await.unwrap();
     let app_port = app.port;
-    let _ = tokio::spawn(app.run());
+    tokio::spawn(app.run());
     TestApp {
         url: format!("http://{}:{}", host, app_port),
         db_conn_pool: test_db_pool,
+        mailer: in_memory_mailer,
     }
 }
 
+// Every app signs with the fixture RSA key and still accepts tokens of a
+// retired one and of the secret, the keys being read once per process
+fn use_test_jwt_keys() {
+    static SET: Once = Once::new();
+    SET.call_once(|| {
+        env::set_var("JWT_SIGNING_KEY", concat!(env!("CARGO_MANIFEST_DIR"), "/tests/keys/signing.pem"));
+        env::set_var("JWT_VERIFICATION_KEYS", concat!(env!("CARGO_MANIFEST_DIR"), "/tests/keys/retired.pub.pem"));
+        env::set_var("JWT_ACCEPT_LEGACY_SECRET", "true");
+    });
+}
+
 async fn setup_test_database(database_url_root: &String, name: &String) -> db::DbPool {
     // Creating test database
     let postgres_db_pool = db::build_pool(&format!("{}/postgres", database_url_root)).await;
 
-    sqlx::query(&format!("CREATE DATABASE \"{}\";", name).as_str())
+    sqlx::query(format!("CREATE DATABASE \"{}\";", name).as_str())
         .execute(&postgres_db_pool)
         .await
         .unwrap();
@@ -118,6 +187,119 @@ pub async fn post_json(
     (status, serde_json::from_str(&body).unwrap())
 }
 
+pub async fn put_json(
+    app: &TestApp,
+    route: &str,
+    json_body: serde_json::Value,
+    headers: Vec<(&str, &str)>,
+) -> (StatusCode, serde_json::Value) {
+    let header_map = headers_vec_to_reqwest_map(headers);
+
+    let res = HttpClient::new()
+        .put(format!("{}{}", app.url, route))
+        .headers(header_map)
+        .json(&json_body)
+        .send()
+        .await
+        .unwrap();
+
+    let status = res.status();
+    let body = res.text().await.unwrap();
+    println!("{} : {}", status, body);
+    (status, serde_json::from_str(&body).unwrap())
+}
+
+pub async fn patch_json(
+    app: &TestApp,
+    route: &str,
+    json_body: serde_json::Value,
+    headers: Vec<(&str, &str)>,
+) -> (StatusCode, serde_json::Value) {
+    let header_map = headers_vec_to_reqwest_map(headers);
+
+    let res = HttpClient::new()
+        .patch(format!("{}{}", app.url, route))
+        .headers(header_map)
+        .json(&json_body)
+        .send()
+        .await
+        .unwrap();
+
+    let status = res.status();
+    let body = res.text().await.unwrap();
+    println!("{} : {}", status, body);
+    (status, serde_json::from_str(&body).unwrap())
+}
+
+pub async fn delete(
+    app: &TestApp,
+    route: &str,
+    headers: Vec<(&str, &str)>,
+) -> (StatusCode, serde_json::Value) {
+    let header_map = headers_vec_to_reqwest_map(headers);
+
+    let res = HttpClient::new()
+        .delete(format!("{}{}", app.url, route))
+        .headers(header_map)
+        .send()
+        .await
+        .unwrap();
+
+    let status = res.status();
+    let body = res.text().await.unwrap();
+    println!("{} : {}", status, body);
+    (status, serde_json::from_str(&body).unwrap())
+}
+
+pub async fn delete_json(
+    app: &TestApp,
+    route: &str,
+    json_body: serde_json::Value,
+    headers: Vec<(&str, &str)>,
+) -> (StatusCode, serde_json::Value) {
+    let header_map = headers_vec_to_reqwest_map(headers);
+
+    let res = HttpClient::new()
+        .delete(format!("{}{}", app.url, route))
+        .headers(header_map)
+        .json(&json_body)
+        .send()
+        .await
+        .unwrap();
+
+    let status = res.status();
+    let body = res.text().await.unwrap();
+    println!("{} : {}", status, body);
+    (status, serde_json::from_str(&body).unwrap())
+}
+
+// Mails sent to the address once there are at least count of them, some
+// routes sending theirs in the background
+pub async fn mails_sent_to(app: &TestApp, email: &str, count: usize) -> Vec<Message> {
+    for _ in 0..100 {
+        let mails = app.mailer().sent_to(email);
+        if mails.len() >= count {
+            return mails;
+        }
+        actix_rt::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("expected {} mails to {}", count, email);
+}
+
+// Value of the Authorization header carrying an access token
+pub fn bearer(jwt: &str) -> String {
+    format!("Bearer {}", jwt)
+}
+
+// Headers of a request sent as the user whose Authorization header it is, if
+// any, as in auth_headers(jwt.map(bearer).as_deref())
+pub fn auth_headers(authorization: Option<&str>) -> Vec<(&str, &str)> {
+    match authorization {
+        Some(authorization) => vec![("Authorization", authorization)],
+        None => vec![],
+    }
+}
+
 pub fn headers_vec_to_reqwest_map(headers: Vec<(&str, &str)>) -> HeaderMap {
     let mut header_map = HeaderMap::new();
     for (name, value) in headers.iter() {
@@ -0,0 +1,152 @@
+use crate::api::{
+    bearer, insert_test_user, spawn_app_with, users::create_user_and_login_with_username,
+    TestApp, TestConfig,
+};
+use camion::{
+    core::{security, users::{api_key, Role}},
+    web::rate_limit::{InMemoryStore, Limit, Limits, Store},
+};
+use reqwest::{Client as HttpClient, Response, StatusCode};
+use serde_json::json;
+use std::time::Duration;
+
+async fn post(app: &TestApp, route: &str, body: serde_json::Value, jwt: Option<&str>) -> Response {
+    let mut request = HttpClient::new().post(format!("{}{}", app.url, route)).json(&body);
+    if let Some(jwt) = jwt {
+        request = request.header("Authorization", &bearer(jwt));
+    }
+    request.send().await.unwrap()
+}
+
+fn header(res: &Response, name: &str) -> Option<String> {
+    res.headers()
+        .get(name)
+        .map(|value| value.to_str().unwrap().to_owned())
+}
+
+#[actix_rt::test]
+async fn requests_past_the_limit_are_refused() {
+    let app = spawn_app_with(TestConfig {
+        rate_limits: Limits { login: Some(Limit::per_minute(2)), ..Limits::none() },
+        ..Default::default()
+    })
+    .await;
+    let login = json!({ "login": "Nobody", "password": "pass" });
+
+    let res = post(&app, "/api/auth/login", login.clone(), None).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(header(&res, "RateLimit-Limit"), Some("2".to_owned()));
+    assert_eq!(header(&res, "RateLimit-Remaining"), Some("1".to_owned()));
+
+    let res = post(&app, "/api/auth/login", login.clone(), None).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(header(&res, "RateLimit-Remaining"), Some("0".to_owned()));
+
+    let res = post(&app, "/api/auth/login", login, None).await;
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(header(&res, "RateLimit-Remaining"), Some("0".to_owned()));
+    let retry_after: u64 = header(&res, "Retry-After").unwrap().parse().unwrap();
+    assert!(retry_after > 0 && retry_after <= 30);
+}
+
+#[actix_rt::test]
+async fn authenticated_users_have_their_own_buckets() {
+    let app = spawn_app_with(TestConfig {
+        rate_limits: Limits { search: Some(Limit::per_minute(1)), ..Limits::none() },
+        ..Default::default()
+    })
+    .await;
+    let (_, jwt_a) = create_user_and_login_with_username(&app, "Anicet", "a0@test.fr", "pass", &Role::None).await;
+    let (_, jwt_b) = create_user_and_login_with_username(&app, "Bidule", "a1@test.fr", "pass", &Role::None).await;
+    let search = json!({ "query": "Ani" });
+
+    let res = post(&app, "/api/users/search", search.clone(), Some(&jwt_a)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = post(&app, "/api/users/search", search.clone(), Some(&jwt_a)).await;
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // Same address, other users and anonymous ones
+    let res = post(&app, "/api/users/search", search.clone(), Some(&jwt_b)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = post(&app, "/api/users/search", search, None).await;
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn groups_are_limited_separately() {
+    let app = spawn_app_with(TestConfig {
+        rate_limits: Limits { login: Some(Limit::per_minute(1)), ..Limits::none() },
+        ..Default::default()
+    })
+    .await;
+    insert_test_user("Anicet", "", "pass", &Role::None, &app.db_conn_pool).await;
+
+    let res = post(&app, "/api/auth/login", json!({ "login": "Anicet", "password": "pass" }), None).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = post(&app, "/api/users/search", json!({ "query": "Ani" }), None).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(header(&res, "RateLimit-Limit"), None);
+}
+
+#[actix_rt::test]
+async fn tokens_of_revoked_sessions_share_the_bucket_of_their_address() {
+    let app = spawn_app_with(TestConfig {
+        rate_limits: Limits { search: Some(Limit::per_minute(1)), ..Limits::none() },
+        ..Default::default()
+    })
+    .await;
+    let (_, jwt) = create_user_and_login_with_username(&app, "Anicet", "a0@test.fr", "pass", &Role::None).await;
+    let res = post(&app, "/api/auth/logout", json!({}), Some(&jwt)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let search = json!({ "query": "Ani" });
+
+    let res = post(&app, "/api/users/search", search.clone(), None).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = post(&app, "/api/users/search", search, Some(&jwt)).await;
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[actix_rt::test]
+async fn unknown_api_keys_share_the_bucket_of_their_address() {
+    let app = spawn_app_with(TestConfig {
+        rate_limits: Limits { login: Some(Limit::per_minute(2)), ..Limits::none() },
+        ..Default::default()
+    })
+    .await;
+    let login = json!({ "login": "Nobody", "password": "pass" });
+
+    let random_key = || format!("{}{}", api_key::KEY_PREFIX, security::random_token());
+
+    for _ in 0..2 {
+        let res = post(&app, "/api/auth/login", login.clone(), Some(&random_key())).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+    let res = post(&app, "/api/auth/login", login, Some(&random_key())).await;
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[test]
+fn limits_are_read_per_minute_or_per_hour() {
+    let limit: Limit = "10/minute".parse().unwrap();
+    assert_eq!((limit.capacity, limit.period_sec), (10, 60));
+    let limit: Limit = "5/hour".parse().unwrap();
+    assert_eq!((limit.capacity, limit.period_sec), (5, 60 * 60));
+    assert!("5".parse::<Limit>().is_err());
+    assert!("5/day".parse::<Limit>().is_err());
+}
+
+#[actix_rt::test]
+async fn buckets_refill_over_time() {
+    let store = InMemoryStore::default();
+    let limit = Limit { capacity: 1, period_sec: 1 };
+
+    assert!(store.take("key", limit).await.unwrap().allowed);
+    let decision = store.take("key", limit).await.unwrap();
+    assert!(!decision.allowed);
+    assert_eq!(decision.retry_after_sec, 1);
+    assert!(store.take("other key", limit).await.unwrap().allowed);
+
+    actix_rt::time::sleep(Duration::from_millis(1100)).await;
+    assert!(store.take("key", limit).await.unwrap().allowed);
+}
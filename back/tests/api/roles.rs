@@ -0,0 +1,51 @@
+use crate::api::{bearer, delete, get, jokes::create_joke_as, spawn_app, users::create_user_and_login_with_username};
+use camion::core::users::Role;
+use reqwest::StatusCode;
+use serde_json::json;
+
+#[actix_rt::test]
+async fn admins_can_list_roles_and_their_permissions() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "admin", "a0@test.fr", "pass", &Role::Admin).await;
+
+    let (status_code, body) = get(&app, "/api/roles", vec![("Authorization", &bearer(&jwt))]).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(
+        body["roles"],
+        json!([
+            {
+                "role": "Admin",
+                "permissions": [
+                    "create_joke", "edit_any_joke", "manage_roles", "manage_settings",
+                    "manage_users", "moderate", "view_any_joke"
+                ]
+            },
+            { "role": "Author", "permissions": ["create_joke"] },
+            { "role": "None", "permissions": [] },
+            { "role": "Moderator", "permissions": ["create_joke", "moderate"] }
+        ])
+    );
+}
+
+#[actix_rt::test]
+async fn only_those_managing_roles_can_list_them() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "moderator", "a0@test.fr", "pass", &Role::Moderator).await;
+
+    let (status_code, body) = get(&app, "/api/roles", vec![("Authorization", &bearer(&jwt))]).await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"], json!("RoleNotAllowed"));
+}
+
+#[actix_rt::test]
+async fn moderators_cannot_edit_the_jokes_of_others() {
+    let app = spawn_app().await;
+    let (joke_id, _) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "moderator", "a1@test.fr", "pass", &Role::Moderator).await;
+
+    let (status_code, _) = delete(&app, &format!("/api/jokes/{}", joke_id), vec![("Authorization", &bearer(&jwt))]).await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,48 @@
+use crate::api::{bearer, get, put_json, spawn_app, users::create_user_and_login_with_username};
+use camion::core::users::Role;
+use reqwest::StatusCode;
+use serde_json::json;
+
+#[actix_rt::test]
+async fn admins_can_change_settings() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "admin", "a0@test.fr", "pass", &Role::Admin)
+            .await;
+
+    let (status_code, body) = get(&app, "/api/settings", vec![("Authorization", &bearer(&jwt))]).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["authors_need_verified_email"], json!(false));
+
+    let (status_code, _) = put_json(
+        &app,
+        "/api/settings",
+        json!({ "authors_need_verified_email": true }),
+        vec![("Authorization", &bearer(&jwt))],
+    )
+    .await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (_, body) = get(&app, "/api/settings", vec![("Authorization", &bearer(&jwt))]).await;
+    assert_eq!(body["authors_need_verified_email"], json!(true));
+}
+
+#[actix_rt::test]
+async fn only_admins_can_see_or_change_settings() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author)
+            .await;
+
+    let (status_code, _) = get(&app, "/api/settings", vec![("Authorization", &bearer(&jwt))]).await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+
+    let (status_code, _) = put_json(
+        &app,
+        "/api/settings",
+        json!({ "authors_need_verified_email": true }),
+        vec![("Authorization", &bearer(&jwt))],
+    )
+    .await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,38 @@
+use crate::api::{
+    get, jokes::titles, spawn_app, tags::create_tagged_joke,
+    users::create_user_and_login_with_username,
+};
+use camion::core::users::Role;
+use reqwest::StatusCode;
+use serde_json::json;
+
+#[actix_rt::test]
+async fn lists_visible_jokes_with_the_tag() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author)
+            .await;
+    create_tagged_joke(&app, "First", json!(["puns"]), true, &jwt).await;
+    create_tagged_joke(&app, "Other", json!(["dad"]), true, &jwt).await;
+    create_tagged_joke(&app, "Second", json!(["puns", "dad"]), true, &jwt).await;
+    create_tagged_joke(&app, "Draft", json!(["puns"]), false, &jwt).await;
+
+    let (status_code, body) = get(&app, "/api/tags/puns/jokes", vec![]).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(titles(&body), vec!["Second", "First"]);
+
+    let (_, body) = get(&app, "/api/tags/PUNS/jokes?order=asc&limit=1", vec![]).await;
+    assert_eq!(titles(&body), vec!["First"]);
+
+    let (_, body) = get(&app, "/api/jokes?tag=dad", vec![]).await;
+    assert_eq!(titles(&body), vec!["Second", "Other"]);
+}
+
+#[actix_rt::test]
+async fn unknown_tags_have_no_jokes() {
+    let app = spawn_app().await;
+
+    let (status_code, body) = get(&app, "/api/tags/nothing/jokes", vec![]).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["jokes"], json!([]));
+}
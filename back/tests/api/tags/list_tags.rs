@@ -0,0 +1,47 @@
+use crate::api::{
+    bearer, get, spawn_app, tags::create_tagged_joke, users::create_user_and_login_with_username,
+};
+use camion::core::users::Role;
+use reqwest::StatusCode;
+use serde_json::json;
+
+#[actix_rt::test]
+async fn lists_tags_with_their_usage_most_used_first() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author)
+            .await;
+    create_tagged_joke(&app, "One", json!(["puns", "dad"]), true, &jwt).await;
+    create_tagged_joke(&app, "Two", json!(["puns"]), true, &jwt).await;
+    create_tagged_joke(&app, "Three", json!(["office"]), true, &jwt).await;
+
+    let (status_code, body) = get(&app, "/api/tags", vec![]).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(
+        body["tags"],
+        json!([
+            { "name": "puns", "count": 2 },
+            { "name": "dad", "count": 1 },
+            { "name": "office", "count": 1 }
+        ])
+    );
+}
+
+#[actix_rt::test]
+async fn only_counts_jokes_visible_by_the_requester() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author)
+            .await;
+    create_tagged_joke(&app, "Published", json!(["puns"]), true, &jwt).await;
+    create_tagged_joke(&app, "Draft", json!(["puns", "secret"]), false, &jwt).await;
+
+    let (_, body) = get(&app, "/api/tags", vec![]).await;
+    assert_eq!(body["tags"], json!([{ "name": "puns", "count": 1 }]));
+
+    let (_, body) = get(&app, "/api/tags", vec![("Authorization", &bearer(&jwt))]).await;
+    assert_eq!(
+        body["tags"],
+        json!([{ "name": "puns", "count": 2 }, { "name": "secret", "count": 1 }])
+    );
+}
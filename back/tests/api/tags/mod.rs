@@ -0,0 +1,27 @@
+use crate::api::{
+    jokes::create::{post_create_joke_request, valid_joke},
+    jokes::status::post_publish_joke_request,
+    TestApp,
+};
+use serde_json::json;
+
+mod list_tags;
+mod list_tag_jokes;
+
+pub async fn create_tagged_joke(
+    app: &TestApp,
+    title: &str,
+    tags: serde_json::Value,
+    published: bool,
+    jwt: &str,
+) -> i64 {
+    let mut joke = valid_joke();
+    joke["title"] = json!(title);
+    joke["tags"] = tags;
+    let (_, body) = post_create_joke_request(app, joke, Some(jwt)).await;
+    let id = body["created_joke"]["id"].as_i64().unwrap();
+    if published {
+        post_publish_joke_request(app, id, Some(jwt)).await;
+    }
+    id
+}
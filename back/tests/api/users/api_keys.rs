@@ -0,0 +1,184 @@
+use crate::api::{
+    bearer, delete, get,
+    jokes::{create::{post_create_joke_request, valid_joke}, create_joke_as},
+    post_json, spawn_app, users::create_user_and_login_with_username, TestApp,
+};
+use camion::core::users::Role;
+use chrono::{Duration, Utc};
+use reqwest::StatusCode;
+use serde_json::json;
+
+async fn create_api_key(app: &TestApp, jwt: &str, data: serde_json::Value) -> (reqwest::StatusCode, serde_json::Value) {
+    post_json(app, "/api/users/me/api-keys", data, vec![("Authorization", &bearer(jwt))]).await
+}
+
+// Returns the id and the key
+async fn create_key_with_scopes(app: &TestApp, jwt: &str, scopes: serde_json::Value) -> (i64, String) {
+    let (_, body) = create_api_key(app, jwt, json!({ "name": "Bot", "scopes": scopes })).await;
+    (
+        body["created_api_key"]["id"].as_i64().unwrap(),
+        body["created_api_key"]["key"].as_str().unwrap().to_owned(),
+    )
+}
+
+#[actix_rt::test]
+async fn keys_are_shown_once_and_act_as_their_owner() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author).await;
+
+    let (status_code, body) =
+        create_api_key(&app, &jwt, json!({ "name": "Joke bot", "scopes": ["write_jokes"] })).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["success"], json!(true));
+    let key = body["created_api_key"]["key"].as_str().unwrap().to_owned();
+    assert!(key.starts_with(body["created_api_key"]["prefix"].as_str().unwrap()));
+    assert_eq!(body["created_api_key"]["last_used_at"], json!(null));
+
+    let (status_code, body) = post_create_joke_request(&app, valid_joke(), Some(&key)).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["created_joke"]["author_username"], json!("author"));
+
+    let (status_code, body) = get(&app, "/api/users/me/api-keys", vec![("Authorization", &bearer(&jwt))]).await;
+    assert_eq!(status_code, StatusCode::OK);
+    let api_keys = body["api_keys"].as_array().unwrap();
+    assert_eq!(api_keys.len(), 1);
+    assert_eq!(api_keys[0]["name"], json!("Joke bot"));
+    assert_eq!(api_keys[0]["scopes"], json!(["write_jokes"]));
+    assert!(api_keys[0].get("key").is_none());
+    assert!(api_keys[0]["last_used_at"].is_string());
+
+    let stored = sqlx::query!("SELECT key_hash FROM api_keys")
+        .fetch_one(&app.db_conn_pool)
+        .await
+        .unwrap();
+    assert_ne!(stored.key_hash, key);
+}
+
+#[actix_rt::test]
+async fn keys_can_only_call_the_routes_of_their_scopes() {
+    let app = spawn_app().await;
+    let (joke_id, jwt) = create_joke_as(&app, "author", "a0@test.fr", &Role::Author).await;
+    let (_, key) = create_key_with_scopes(&app, &jwt, json!(["read"])).await;
+
+    let (status_code, _) = get(&app, &format!("/api/jokes/{}", joke_id), vec![("Authorization", &bearer(&key))]).await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (status_code, body) = post_create_joke_request(&app, valid_joke(), Some(&key)).await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"], json!("MissingScope"));
+
+    // Nor can they manage the account, keys included
+    let (status_code, body) = get(&app, "/api/users/me/api-keys", vec![("Authorization", &bearer(&key))]).await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"], json!("ApiKeyNotAllowed"));
+
+    let (status_code, _) = get(&app, "/api/users/me/export", vec![("Authorization", &bearer(&key))]).await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+}
+
+#[actix_rt::test]
+async fn keys_are_only_marked_as_used_when_let_in() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author).await;
+    let (id, key) = create_key_with_scopes(&app, &jwt, json!(["read"])).await;
+    let last_used_at = || async {
+        sqlx::query!("SELECT last_used_at FROM api_keys WHERE id = $1", id as i32)
+            .fetch_one(&app.db_conn_pool)
+            .await
+            .unwrap()
+            .last_used_at
+    };
+
+    let (status_code, _) = post_create_joke_request(&app, valid_joke(), Some(&key)).await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+    let (status_code, _) = get(&app, "/api/users/me/api-keys", vec![("Authorization", &bearer(&key))]).await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+    assert!(last_used_at().await.is_none());
+
+    let (status_code, _) = get(&app, "/api/jokes", vec![("Authorization", &bearer(&key))]).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert!(last_used_at().await.is_some());
+}
+
+#[actix_rt::test]
+async fn revoked_keys_are_refused() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author).await;
+    let (_, other_jwt) =
+        create_user_and_login_with_username(&app, "other", "a1@test.fr", "pass", &Role::Author).await;
+    let (id, key) = create_key_with_scopes(&app, &jwt, json!(["write_jokes"])).await;
+    let route = format!("/api/users/me/api-keys/{}", id);
+
+    let (status_code, _) = delete(&app, &route, vec![("Authorization", &bearer(&other_jwt))]).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+
+    let (status_code, _) = delete(&app, &route, vec![("Authorization", &bearer(&jwt))]).await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (status_code, body) = post_create_joke_request(&app, valid_joke(), Some(&key)).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"], json!("InvalidApiKey"));
+
+    let (_, body) = get(&app, "/api/users/me/api-keys", vec![("Authorization", &bearer(&jwt))]).await;
+    assert_eq!(body["api_keys"], json!([]));
+}
+
+#[actix_rt::test]
+async fn expired_keys_are_refused() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author).await;
+    let (id, key) = create_key_with_scopes(&app, &jwt, json!(["write_jokes"])).await;
+    sqlx::query!(
+        "UPDATE api_keys SET expires_at = $1 WHERE id = $2",
+        Utc::now().naive_utc() - Duration::minutes(1),
+        id as i32
+    )
+    .execute(&app.db_conn_pool)
+    .await
+    .unwrap();
+
+    let (status_code, body) = post_create_joke_request(&app, valid_joke(), Some(&key)).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"], json!("InvalidApiKey"));
+}
+
+#[actix_rt::test]
+async fn keys_need_a_name_scopes_and_a_future_expiry() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author).await;
+    let yesterday = (Utc::now() - Duration::days(1)).naive_utc();
+
+    let (status_code, body) =
+        create_api_key(&app, &jwt, json!({ "name": " ", "scopes": [], "expires_at": yesterday })).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["success"], json!(false));
+    assert_eq!(body["issues"]["name"], json!(["Blank"]));
+    assert_eq!(body["issues"]["scopes"], json!(["Empty"]));
+    assert_eq!(body["issues"]["expires_at"], json!(["Past"]));
+
+    let res = reqwest::Client::new()
+        .post(format!("{}/api/users/me/api-keys", app.url))
+        .header("Authorization", bearer(&jwt))
+        .json(&json!({ "name": "Bot", "scopes": ["everything"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn keys_follow_the_role_of_their_owner() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "noneuser", "a0@test.fr", "pass", &Role::None).await;
+    let (_, key) = create_key_with_scopes(&app, &jwt, json!(["write_jokes"])).await;
+
+    let (status_code, body) = post_create_joke_request(&app, valid_joke(), Some(&key)).await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"], json!("RoleNotAllowed"));
+}
@@ -1,5 +1,6 @@
 use crate::api::{
-    insert_test_user, post_json, spawn_app, users::create_user_and_login_with_username, TestApp,
+    auth_headers, bearer, auth::{login::login, refresh::refresh},
+    insert_test_user, post_json, put_json, spawn_app, users::create_user_and_login_with_username, TestApp,
 };
 use camion::core::users::Role;
 use reqwest::StatusCode;
@@ -11,15 +12,11 @@ async fn post_change_role_request_by_username(
     role: &Role,
     jwt: Option<&str>,
 ) -> (reqwest::StatusCode, serde_json::Value) {
-    let headers = match jwt {
-        Some(jwt) => vec![("Authorization", jwt)],
-        None => vec![]
-    };
     post_json(
         app,
         &format!("/api/users/{}/role", username),
         json!({ "new_role": role }),
-        headers,
+        auth_headers(jwt.map(bearer).as_deref()),
     )
     .await
 }
@@ -48,21 +45,41 @@ async fn admins_can_change_anyones_role_to_anything() {
 
     let (status_code, _) = post_change_role_request_by_username(&app, "Anicet", &Role::Author, Some(&jwt)).await;
     assert_eq!(status_code, StatusCode::OK);
+
+    let (status_code, _) = post_change_role_request_by_username(&app, "Anicet", &Role::Moderator, Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn unknown_roles_are_refused() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "admin", "a0@test.fr", "pass", &Role::Admin).await;
+    insert_test_user("Anicet", "a1@test.fr", "pass", &Role::None, &app.db_conn_pool).await;
+
+    let res = reqwest::Client::new()
+        .post(format!("{}/api/users/Anicet/role", app.url))
+        .header("Authorization", &bearer(&jwt))
+        .json(&json!({ "new_role": "Overlord" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
 }
 
-async fn cannot_change_anyone_role(app: TestApp, jwt: Option<&str>) {
+async fn cannot_change_anyone_role(app: TestApp, jwt: Option<&str>, expected: StatusCode) {
     insert_test_user("Yoann", "a1@test.fr", "pass", &Role::None, &app.db_conn_pool).await;
     insert_test_user("Anicet", "a2@test.fr", "pass", &Role::Author, &app.db_conn_pool).await;
     insert_test_user("Félicie", "a3@test.fr", "pass", &Role::Admin, &app.db_conn_pool).await;
     
     let (status_code, _) = post_change_role_request_by_username(&app, "Yoann", &Role::Admin, jwt).await;
-    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(status_code, expected);
 
     let (status_code, _) = post_change_role_request_by_username(&app, "Anicet", &Role::None, jwt).await;
-    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(status_code, expected);
 
     let (status_code, _) = post_change_role_request_by_username(&app, "Félicie", &Role::Author, jwt).await;
-    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(status_code, expected);
 }
 
 #[actix_rt::test]
@@ -73,7 +90,7 @@ async fn authors_cannot_change_anyones_role() {
         create_user_and_login_with_username(&app, "author", "a0@test.fr", "pass", &Role::Author)
             .await;
     
-    cannot_change_anyone_role(app, Some(&jwt)).await;
+    cannot_change_anyone_role(app, Some(&jwt), StatusCode::FORBIDDEN).await;
 }
 
 #[actix_rt::test]
@@ -84,12 +101,90 @@ async fn none_cannot_change_anyones_role() {
         create_user_and_login_with_username(&app, "noneuser", "a0@test.fr", "pass", &Role::None)
             .await;
     
-    cannot_change_anyone_role(app, Some(&jwt)).await;
+    cannot_change_anyone_role(app, Some(&jwt), StatusCode::FORBIDDEN).await;
 }
 
 #[actix_rt::test]
 async fn anonymous_cannot_change_anyones_role() {
     let app = spawn_app().await;
     
-    cannot_change_anyone_role(app, None).await;
-}
\ No newline at end of file
+    cannot_change_anyone_role(app, None, StatusCode::UNAUTHORIZED).await;
+}
+#[actix_rt::test]
+async fn demoted_admins_lose_their_privileges_at_once() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "admin", "a0@test.fr", "pass", &Role::Admin)
+            .await;
+    let (_, demoted) = login(&app, "admin", "pass").await;
+    let demoted_jwt = demoted["token"].as_str().unwrap();
+    insert_test_user("Anicet", "a1@test.fr", "pass", &Role::None, &app.db_conn_pool).await;
+
+    // Gets the demoted admin's token confirmed before the change
+    let (status_code, _) =
+        post_change_role_request_by_username(&app, "Anicet", &Role::Author, Some(demoted_jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (status_code, _) = post_change_role_request_by_username(&app, "admin", &Role::Author, Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (status_code, body) =
+        post_change_role_request_by_username(&app, "Anicet", &Role::Admin, Some(demoted_jwt)).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"], json!("OutdatedToken"));
+
+    let (_, refreshed) = refresh(&app, demoted["refresh_token"].as_str().unwrap()).await;
+    let (status_code, body) = post_change_role_request_by_username(
+        &app,
+        "Anicet",
+        &Role::Admin,
+        refreshed["token"].as_str(),
+    )
+    .await;
+    assert_eq!(status_code, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"], json!("RoleNotAllowed"));
+}
+
+async fn require_verified_emails_for_authors(app: &TestApp, admin_jwt: &str) {
+    put_json(
+        app,
+        "/api/settings",
+        json!({ "authors_need_verified_email": true }),
+        vec![("Authorization", &bearer(admin_jwt))],
+    )
+    .await;
+}
+
+#[actix_rt::test]
+async fn unverified_users_cannot_become_authors_when_verification_is_required() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "admin", "a0@test.fr", "pass", &Role::Admin)
+            .await;
+    insert_test_user("Anicet", "a1@test.fr", "pass", &Role::None, &app.db_conn_pool).await;
+    require_verified_emails_for_authors(&app, &jwt).await;
+
+    let (status_code, body) = post_change_role_request_by_username(&app, "Anicet", &Role::Author, Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::CONFLICT);
+    assert_eq!(body["error"], json!("UnverifiedEmail"));
+
+    sqlx::query!("UPDATE users SET email_verified_at = NOW() WHERE username = 'Anicet'")
+        .execute(&app.db_conn_pool)
+        .await
+        .unwrap();
+
+    let (status_code, _) = post_change_role_request_by_username(&app, "Anicet", &Role::Author, Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn unverified_users_can_become_authors_by_default() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "admin", "a0@test.fr", "pass", &Role::Admin)
+            .await;
+    insert_test_user("Anicet", "a1@test.fr", "pass", &Role::None, &app.db_conn_pool).await;
+
+    let (status_code, _) = post_change_role_request_by_username(&app, "Anicet", &Role::Author, Some(&jwt)).await;
+    assert_eq!(status_code, StatusCode::OK);
+}
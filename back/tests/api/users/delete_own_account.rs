@@ -0,0 +1,169 @@
+use crate::api::{
+    auth_headers, bearer, auth::{login::login, refresh::refresh},
+    delete_json, get, insert_test_user, post_json, put_json,
+    jokes::{create_joke_as, get::get_joke_request},
+    spawn_app, users::create_user_and_login_with_username, TestApp,
+};
+use camion::core::users::Role;
+use reqwest::StatusCode;
+use serde_json::json;
+
+async fn delete_account(
+    app: &TestApp,
+    jwt: Option<&str>,
+    password: &str,
+    jokes: &str,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    delete_json(
+        app,
+        "/api/users/me",
+        json!({ "password": password, "jokes": jokes }),
+        auth_headers(jwt.map(bearer).as_deref()),
+    )
+    .await
+}
+
+// The author's joke, commented and reacted to by a reader, along with that
+// reader's own joke commented by the author
+async fn author_and_reader(app: &TestApp) -> (i64, String, i64, String) {
+    let (author_joke_id, author_jwt) = create_joke_as(app, "Anicet", "anicet@mail.com", &Role::Author).await;
+    let (reader_joke_id, reader_jwt) = create_joke_as(app, "Yoann", "yoann@mail.com", &Role::Author).await;
+    post_json(
+        app,
+        &format!("/api/jokes/{}/comments", author_joke_id),
+        json!({ "comment": { "content": "Nice one" } }),
+        vec![("Authorization", &bearer(&reader_jwt))],
+    )
+    .await;
+    post_json(
+        app,
+        &format!("/api/jokes/{}/comments", reader_joke_id),
+        json!({ "comment": { "content": "Thanks" } }),
+        vec![("Authorization", &bearer(&author_jwt))],
+    )
+    .await;
+    (author_joke_id, author_jwt, reader_joke_id, reader_jwt)
+}
+
+async fn comments_of(app: &TestApp, joke_id: i64) -> Vec<serde_json::Value> {
+    let (_, body) = get(app, &format!("/api/jokes/{}/comments", joke_id), vec![]).await;
+    body["comments"].as_array().unwrap().clone()
+}
+
+#[actix_rt::test]
+async fn deleting_can_take_the_jokes_along() {
+    let app = spawn_app().await;
+    let (author_joke_id, author_jwt, reader_joke_id, _) = author_and_reader(&app).await;
+
+    let (status_code, _) = delete_account(&app, Some(&author_jwt), "pass", "Delete").await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (status_code, _) = get_joke_request(&app, author_joke_id, None).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+    assert!(comments_of(&app, reader_joke_id).await.is_empty());
+    let (status_code, _) = get(&app, "/api/users/Anicet", vec![]).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn deleting_hands_answered_comments_over_to_keep_the_replies() {
+    let app = spawn_app().await;
+    let (_, author_jwt, reader_joke_id, reader_jwt) = author_and_reader(&app).await;
+    let comment_id = comments_of(&app, reader_joke_id).await[0]["id"].as_i64().unwrap();
+    post_json(
+        &app,
+        &format!("/api/jokes/{}/comments", reader_joke_id),
+        json!({ "comment": { "content": "You're welcome" }, "reply_to": comment_id }),
+        vec![("Authorization", &bearer(&reader_jwt))],
+    )
+    .await;
+
+    let (status_code, _) = delete_account(&app, Some(&author_jwt), "pass", "Delete").await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let comments = comments_of(&app, reader_joke_id).await;
+    assert_eq!(comments.len(), 1);
+    assert_eq!(comments[0]["author_username"], json!("[deleted]"));
+    assert_eq!(comments[0]["replies"][0]["content"], json!("You're welcome"));
+}
+
+#[actix_rt::test]
+async fn deleting_can_hand_the_jokes_over_to_a_placeholder() {
+    let app = spawn_app().await;
+    let (author_joke_id, author_jwt, reader_joke_id, _) = author_and_reader(&app).await;
+
+    let (status_code, _) = delete_account(&app, Some(&author_jwt), "pass", "Reassign").await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    let (status_code, body) = get_joke_request(&app, author_joke_id, None).await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["author_username"], json!("[deleted]"));
+    assert_eq!(comments_of(&app, author_joke_id).await.len(), 1);
+
+    let comments = comments_of(&app, reader_joke_id).await;
+    assert_eq!(comments.len(), 1);
+    assert_eq!(comments[0]["author_username"], json!("[deleted]"));
+    let (status_code, _) = get(&app, "/api/users/Anicet", vec![]).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn reactions_go_away_with_the_account() {
+    let app = spawn_app().await;
+    let (joke_id, _) = create_joke_as(&app, "Anicet", "anicet@mail.com", &Role::Author).await;
+    let (_, jwt) = create_user_and_login_with_username(&app, "Yoann", "yoann@mail.com", "pass", &Role::None).await;
+    put_json(&app, &format!("/api/jokes/{}/reaction", joke_id), json!({ "reaction": "Laugh" }), vec![("Authorization", &bearer(&jwt))]).await;
+
+    delete_account(&app, Some(&jwt), "pass", "Reassign").await;
+
+    let (_, body) = get_joke_request(&app, joke_id, None).await;
+    assert_eq!(body["reactions"]["laugh"], json!(0));
+}
+
+#[actix_rt::test]
+async fn requires_the_password() {
+    let app = spawn_app().await;
+    let (_, jwt) = create_user_and_login_with_username(&app, "Anicet", "anicet@mail.com", "pass", &Role::None).await;
+
+    let (status_code, body) = delete_account(&app, Some(&jwt), "wrong", "Delete").await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["reason"], json!("InvalidPassword"));
+
+    let (status_code, _) = get(&app, "/api/users/Anicet", vec![("Authorization", &bearer(&jwt))]).await;
+    assert_eq!(status_code, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn the_deleted_users_tokens_stop_working() {
+    let app = spawn_app().await;
+    insert_test_user("Anicet", "anicet@mail.com", "pass", &Role::None, &app.db_conn_pool).await;
+    let (_, tokens) = login(&app, "Anicet", "pass").await;
+    let jwt = tokens["token"].as_str().unwrap();
+
+    delete_account(&app, Some(jwt), "pass", "Delete").await;
+
+    let (status_code, _) = get(&app, "/api/users/Yoann", vec![("Authorization", &bearer(jwt))]).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    let (status_code, _) = refresh(&app, tokens["refresh_token"].as_str().unwrap()).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    let (status_code, _) = login(&app, "Anicet", "pass").await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+}
+
+#[actix_rt::test]
+async fn nobody_can_log_in_as_the_placeholder() {
+    let app = spawn_app().await;
+
+    let (status_code, _) = login(&app, "[deleted]", "!").await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+    let (status_code, _) = login(&app, "[deleted]", "").await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+}
+
+#[actix_rt::test]
+async fn anonymous_cannot_delete_an_account() {
+    let app = spawn_app().await;
+
+    let (status_code, _) = delete_account(&app, None, "pass", "Delete").await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,61 @@
+use crate::api::{
+    bearer, get, post_json, put_json,
+    jokes::{create::{post_create_joke_request, valid_joke}, create_joke_as},
+    spawn_app,
+};
+use camion::core::users::Role;
+use reqwest::StatusCode;
+use serde_json::json;
+
+#[actix_rt::test]
+async fn exports_profile_jokes_comments_and_reactions() {
+    let app = spawn_app().await;
+    let (other_joke_id, _) = create_joke_as(&app, "Yoann", "yoann@mail.com", &Role::Author).await;
+    let (own_joke_id, jwt) = create_joke_as(&app, "Anicet", "anicet@mail.com", &Role::Author).await;
+    // Drafts belong to the archive too
+    post_create_joke_request(&app, valid_joke(), Some(&jwt)).await;
+    post_json(
+        &app,
+        &format!("/api/jokes/{}/comments", other_joke_id),
+        json!({ "comment": { "content": "Nice one" } }),
+        vec![("Authorization", &bearer(&jwt))],
+    )
+    .await;
+    put_json(
+        &app,
+        &format!("/api/jokes/{}/reaction", other_joke_id),
+        json!({ "reaction": "Groan" }),
+        vec![("Authorization", &bearer(&jwt))],
+    )
+    .await;
+
+    let (status_code, body) = get(&app, "/api/users/me/export", vec![("Authorization", &bearer(&jwt))]).await;
+    assert_eq!(status_code, StatusCode::OK);
+
+    assert_eq!(body["profile"]["username"], json!("Anicet"));
+    assert_eq!(body["profile"]["email"], json!("anicet@mail.com"));
+
+    let jokes = body["jokes"].as_array().unwrap();
+    assert_eq!(jokes.len(), 2);
+    assert_eq!(jokes[0]["id"], json!(own_joke_id));
+    assert_eq!(jokes[1]["status"], json!("Draft"));
+    assert_eq!(jokes[0]["lines"].as_array().unwrap().len(), 6);
+
+    assert_eq!(body["comments"].as_array().unwrap().len(), 1);
+    assert_eq!(body["comments"][0]["joke_id"], json!(other_joke_id));
+    assert_eq!(body["comments"][0]["content"], json!("Nice one"));
+
+    assert_eq!(body["reactions"], json!([{
+        "joke_id": other_joke_id,
+        "reaction": "Groan",
+        "reacted_at": body["reactions"][0]["reacted_at"]
+    }]));
+}
+
+#[actix_rt::test]
+async fn anonymous_has_nothing_to_export() {
+    let app = spawn_app().await;
+
+    let (status_code, _) = get(&app, "/api/users/me/export", vec![]).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+}
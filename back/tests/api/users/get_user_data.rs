@@ -1,4 +1,4 @@
-use crate::api::{TestApp, get, insert_test_user, spawn_app, users::create_user_and_login_with_username};
+use crate::api::{auth_headers, bearer, TestApp, get, insert_test_user, spawn_app, users::create_user_and_login_with_username};
 use camion::core::users::Role;
 use reqwest::StatusCode;
 use serde_json::json;
@@ -8,15 +8,10 @@ async fn get_user_data_from_username(
     username: &str,
     jwt: Option<&str>,
 ) -> (reqwest::StatusCode, serde_json::Value) {
-    let headers = match jwt {
-        Some(jwt) => vec![("Authorization", jwt)],
-        None => vec![]
-    };
-    
     get(
         app,
         &format!("/api/users/{}", username),
-        headers,
+        auth_headers(jwt.map(bearer).as_deref()),
     )
     .await
 }
@@ -5,6 +5,10 @@ use super::{TestApp, auth::login::login, insert_test_user};
 pub mod get_user_data;
 pub mod search_users;
 pub mod change_user_role;
+mod update_own_profile;
+mod delete_own_account;
+mod export_own_data;
+mod api_keys;
 
 pub async fn create_user_and_login_with_username(
     app: &TestApp,
@@ -1,5 +1,5 @@
 use crate::api::{
-    insert_test_user, post_json, spawn_app, users::create_user_and_login_with_username, TestApp,
+    auth_headers, bearer, insert_test_user, post_json, spawn_app, users::create_user_and_login_with_username, TestApp,
 };
 use camion::core::users::Role;
 use reqwest::StatusCode;
@@ -10,27 +10,23 @@ async fn post_search_request_by_username(
     query: &str,
     jwt: Option<&str>,
 ) -> (reqwest::StatusCode, serde_json::Value) {
-    let headers = match jwt {
-        Some(jwt) => vec![("Authorization", jwt)],
-        None => vec![]
-    };
     post_json(
         app,
-        &format!("/api/users/search"),
+        "/api/users/search",
         json!({ "query": query }),
-        headers,
+        auth_headers(jwt.map(bearer).as_deref()),
     )
     .await
 }
 
-fn contains_username(results: &Vec<serde_json::Value>, username: &str) -> bool {
+fn contains_username(results: &[serde_json::Value], username: &str) -> bool {
     results
         .iter()
         .any(|r| r["username"].as_str().unwrap() == username)
 }
 
 async fn insert_test_users(app: &TestApp) {
-    let users = vec![
+    let users = [
         ("TrucMachin", "a1@test.fr", "", &Role::Author),
         ("BiduleChouette", "a2@test.fr", "", &Role::Author),
         ("MachtruChou", "a3@test.fr", "", &Role::Admin),
@@ -55,9 +51,9 @@ async fn returns_all_users_with_query_in_username_as_admin() {
     assert_eq!(status_code, StatusCode::OK);
 
     let results = body["results"].as_array().unwrap();
-    assert!(contains_username(&results, "TrucMachin"));
-    assert!(contains_username(&results, "MachtruChou"));
-    assert!(contains_username(&results, "chouetteTruc"));
+    assert!(contains_username(results, "TrucMachin"));
+    assert!(contains_username(results, "MachtruChou"));
+    assert!(contains_username(results, "chouetteTruc"));
     assert_eq!(results.len(), 3);
 }
 
@@ -73,8 +69,8 @@ async fn returns_authors_and_admins_only_with_query_in_username_as_author() {
     assert_eq!(status_code, StatusCode::OK);
 
     let results = body["results"].as_array().unwrap();
-    assert!(contains_username(&results, "TrucMachin"));
-    assert!(contains_username(&results, "MachtruChou"));
+    assert!(contains_username(results, "TrucMachin"));
+    assert!(contains_username(results, "MachtruChou"));
     assert_eq!(results.len(), 2);
 }
 
@@ -90,8 +86,8 @@ async fn returns_authors_and_admins_only_with_query_in_username_as_none() {
     assert_eq!(status_code, StatusCode::OK);
 
     let results = body["results"].as_array().unwrap();
-    assert!(contains_username(&results, "TrucMachin"));
-    assert!(contains_username(&results, "MachtruChou"));
+    assert!(contains_username(results, "TrucMachin"));
+    assert!(contains_username(results, "MachtruChou"));
     assert_eq!(results.len(), 2);
 }
 
@@ -105,7 +101,82 @@ async fn returns_authors_and_admins_only_with_query_in_username_as_anonymous() {
     assert_eq!(status_code, StatusCode::OK);
 
     let results = body["results"].as_array().unwrap();
-    assert!(contains_username(&results, "TrucMachin"));
-    assert!(contains_username(&results, "MachtruChou"));
+    assert!(contains_username(results, "TrucMachin"));
+    assert!(contains_username(results, "MachtruChou"));
     assert_eq!(results.len(), 2);
 }
+
+fn usernames(body: &serde_json::Value) -> Vec<&str> {
+    body["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["username"].as_str().unwrap())
+        .collect()
+}
+
+#[actix_rt::test]
+async fn ranks_exact_then_prefix_then_substring_matches() {
+    let app = spawn_app().await;
+    for (username, email) in [
+        ("the_truc", "a1@test.fr"),
+        ("trucmachin", "a2@test.fr"),
+        ("Truc", "a3@test.fr"),
+    ] {
+        insert_test_user(username, email, "", &Role::Author, &app.db_conn_pool).await;
+    }
+
+    let (_, body) = post_search_request_by_username(&app, "truc", None).await;
+    assert_eq!(usernames(&body), vec!["Truc", "trucmachin", "the_truc"]);
+}
+
+#[actix_rt::test]
+async fn tolerates_typos() {
+    let app = spawn_app().await;
+    insert_test_users(&app).await;
+
+    let (_, body) = post_search_request_by_username(&app, "BiduleChouete", None).await;
+    assert_eq!(usernames(&body), vec!["BiduleChouette"]);
+}
+
+#[actix_rt::test]
+async fn queries_are_plain_text() {
+    let app = spawn_app().await;
+    insert_test_users(&app).await;
+    insert_test_user("under_score", "a6@test.fr", "", &Role::Author, &app.db_conn_pool).await;
+
+    for query in ["(", "[a-z]+", ".*", "%", "\\"] {
+        let (status_code, body) = post_search_request_by_username(&app, query, None).await;
+        assert_eq!(status_code, StatusCode::OK);
+        assert_eq!(body["results"], json!([]));
+    }
+
+    let (_, body) = post_search_request_by_username(&app, "_", None).await;
+    assert_eq!(usernames(&body), vec!["under_score"]);
+}
+
+#[actix_rt::test]
+async fn paginates_results() {
+    let app = spawn_app().await;
+    for i in 0..5 {
+        insert_test_user(
+            &format!("user{}", i),
+            &format!("a{}@test.fr", i),
+            "",
+            &Role::Author,
+            &app.db_conn_pool,
+        )
+        .await;
+    }
+
+    let (_, body) = post_json(
+        &app,
+        "/api/users/search",
+        json!({ "query": "user", "page": 2, "limit": 2 }),
+        vec![],
+    )
+    .await;
+    assert_eq!(usernames(&body), vec!["user2", "user3"]);
+    assert_eq!(body["page"], json!(2));
+    assert_eq!(body["limit"], json!(2));
+}
@@ -0,0 +1,155 @@
+use crate::api::{
+    auth_headers, bearer, auth::login::login, get, insert_test_user, patch_json, spawn_app,
+    users::create_user_and_login_with_username, TestApp,
+};
+use camion::core::users::Role;
+use reqwest::StatusCode;
+use serde_json::json;
+
+async fn update_profile(app: &TestApp, jwt: Option<&str>, update: serde_json::Value) -> (reqwest::StatusCode, serde_json::Value) {
+    patch_json(app, "/api/users/me", update, auth_headers(jwt.map(bearer).as_deref())).await
+}
+
+#[actix_rt::test]
+async fn profile_fields_are_shown_to_everyone() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "Anicet", "anicet@mail.com", "pass", &Role::Author).await;
+
+    let (status_code, body) = update_profile(
+        &app,
+        Some(&jwt),
+        json!({
+            "display_name": "Anicet N.",
+            "bio": "Tells jokes",
+            "website": "https://anicet.dev/jokes"
+        }),
+    )
+    .await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["success"], json!(true));
+    assert_eq!(body["user"]["display_name"], json!("Anicet N."));
+
+    let (_, body) = get(&app, "/api/users/Anicet", vec![]).await;
+    assert_eq!(body["display_name"], json!("Anicet N."));
+    assert_eq!(body["bio"], json!("Tells jokes"));
+    assert_eq!(body["website"], json!("https://anicet.dev/jokes"));
+    assert_eq!(body["email"], serde_json::Value::Null);
+}
+
+#[actix_rt::test]
+async fn missing_fields_are_kept_and_null_clears_them() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "Anicet", "anicet@mail.com", "pass", &Role::Author).await;
+    update_profile(&app, Some(&jwt), json!({ "display_name": "Anicet N.", "bio": "Tells jokes" })).await;
+
+    let (_, body) = update_profile(&app, Some(&jwt), json!({ "bio": null })).await;
+    assert_eq!(body["user"]["display_name"], json!("Anicet N."));
+    assert_eq!(body["user"]["bio"], serde_json::Value::Null);
+    assert_eq!(body["user"]["username"], json!("Anicet"));
+}
+
+#[actix_rt::test]
+async fn username_can_be_changed() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "Anicet", "anicet@mail.com", "pass", &Role::Author).await;
+
+    let (_, body) = update_profile(&app, Some(&jwt), json!({ "username": "Anicet2" })).await;
+    assert_eq!(body["success"], json!(true));
+
+    let (status_code, _) = get(&app, "/api/users/Anicet", vec![]).await;
+    assert_eq!(status_code, StatusCode::NOT_FOUND);
+    let (status_code, _) = login(&app, "Anicet2", "pass").await;
+    assert_eq!(status_code, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn own_username_and_email_do_not_count_as_taken() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "Anicet", "anicet@mail.com", "pass", &Role::Author).await;
+
+    let (_, body) = update_profile(
+        &app,
+        Some(&jwt),
+        json!({ "username": "Anicet", "email": "anicet@mail.com" }),
+    )
+    .await;
+    assert_eq!(body["success"], json!(true));
+    assert!(app.mailer().sent().is_empty());
+}
+
+#[actix_rt::test]
+async fn others_usernames_and_emails_are_taken() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "Anicet", "anicet@mail.com", "pass", &Role::Author).await;
+    insert_test_user("Yoann", "yoann@mail.com", "pass", &Role::None, &app.db_conn_pool).await;
+
+    let (status_code, body) = update_profile(
+        &app,
+        Some(&jwt),
+        json!({ "username": "Yoann", "email": "yoann@mail.com" }),
+    )
+    .await;
+    assert_eq!(status_code, StatusCode::OK);
+    assert_eq!(body["success"], json!(false));
+    assert_eq!(body["issues"]["username"], json!(["NotUnique"]));
+    assert_eq!(body["issues"]["email"], json!(["NotUnique"]));
+
+    let (status_code, _) = get(&app, "/api/users/Anicet", vec![]).await;
+    assert_eq!(status_code, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn invalid_fields_are_refused() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "Anicet", "anicet@mail.com", "pass", &Role::Author).await;
+
+    let (_, body) = update_profile(
+        &app,
+        Some(&jwt),
+        json!({
+            "username": "an",
+            "email": "not an email",
+            "display_name": "   ",
+            "bio": "a".repeat(1001),
+            "website": "javascript:alert(1)"
+        }),
+    )
+    .await;
+    assert_eq!(body["success"], json!(false));
+    assert_eq!(body["issues"]["username"], json!(["TooShort"]));
+    assert_eq!(body["issues"]["email"], json!(["Malformed"]));
+    assert_eq!(body["issues"]["display_name"], json!(["Blank"]));
+    assert_eq!(body["issues"]["bio"], json!(["TooLong"]));
+    assert_eq!(body["issues"]["website"], json!(["Malformed"]));
+}
+
+#[actix_rt::test]
+async fn a_new_email_must_be_verified_again() {
+    let app = spawn_app().await;
+    let (_, jwt) =
+        create_user_and_login_with_username(&app, "Anicet", "anicet@mail.com", "pass", &Role::Author).await;
+    sqlx::query!("UPDATE users SET email_verified_at = NOW()")
+        .execute(&app.db_conn_pool)
+        .await
+        .unwrap();
+
+    let (_, body) = update_profile(&app, Some(&jwt), json!({ "email": "new@mail.com" })).await;
+    assert_eq!(body["success"], json!(true));
+    assert_eq!(body["user"]["email"], json!("new@mail.com"));
+    assert_eq!(body["user"]["email_verified"], json!(false));
+    assert_eq!(app.mailer().sent_to("new@mail.com").len(), 1);
+}
+
+#[actix_rt::test]
+async fn anonymous_cannot_update_a_profile() {
+    let app = spawn_app().await;
+
+    let (status_code, _) = update_profile(&app, None, json!({ "bio": "Nobody" })).await;
+    assert_eq!(status_code, StatusCode::UNAUTHORIZED);
+}